@@ -0,0 +1,236 @@
+//! The ways a program can be executed, and cross-checking one against another.
+
+mod closures;
+
+use std::mem;
+
+use crate::interpreter::error::{InterpreterError, InterpreterErrorReason};
+use crate::interpreter::io::CapturedOutput;
+use crate::interpreter::Interpreter;
+use crate::ir::{CompileError, DEFAULT_OPT_LEVEL, Ir};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+	/// Interprets the source as written, one character at a time.
+	Naive,
+	/// Executes the optimized IR as a flat list of ops.
+	Ir,
+	/// Runs the optimized IR compiled into boxed closures.
+	Closures,
+}
+
+impl Backend {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"naive" => Some(Backend::Naive),
+			"ir" => Some(Backend::Ir),
+			"closures" => Some(Backend::Closures),
+			_ => None,
+		}
+	}
+
+	/// The characters the interpreter runs for `characters` under this backend. Backends that
+	/// don't interpret characters get the IR rendering, for tools stepping through the program.
+	pub fn prepare(self, characters: &[char]) -> Result<Vec<char>, CompileError> {
+		match self {
+			Backend::Naive => Ok(characters.to_vec()),
+			Backend::Ir | Backend::Closures => {
+				Ok(Ir::compile(characters, DEFAULT_OPT_LEVEL)?.to_brainfuck().chars().collect())
+			}
+		}
+	}
+
+	/// Runs `prepared`, as returned by [`Backend::prepare`], to completion on `interpreter`.
+	pub fn run(self, interpreter: &mut Interpreter, prepared: &[char]) -> Result<(), InterpreterError> {
+		match self {
+			Backend::Naive => interpreter.run(prepared),
+			Backend::Ir | Backend::Closures => {
+				let ir = Ir::compile(prepared, DEFAULT_OPT_LEVEL).expect("Prepared programs have balanced brackets");
+				self.run_compiled(interpreter, &ir)
+			}
+		}
+	}
+
+	/// Runs `ir`, compiled already, to completion on `interpreter`. The naive backend steps through
+	/// its rendering.
+	pub fn run_compiled(self, interpreter: &mut Interpreter, ir: &Ir) -> Result<(), InterpreterError> {
+		match self {
+			Backend::Naive => interpreter.run(&ir.to_brainfuck().chars().collect::<Vec<_>>()),
+			Backend::Ir => interpreter.run_ir(ir),
+			Backend::Closures => closures::ClosureProgram::build(ir).run(interpreter),
+		}
+	}
+}
+
+/// Observable outcome of a run: what it printed, what it left on the tape and how it ended.
+#[derive(Debug)]
+pub struct RunSnapshot {
+	pub output: Vec<u8>,
+	pub tape: Vec<u8>,
+	pub data_ptr: usize,
+	pub error: Option<InterpreterErrorReason>,
+	/// Debug rendering of the final machine, for reports.
+	pub state: String,
+}
+
+#[derive(Debug)]
+pub enum DivergenceKind {
+	/// The backend couldn't even compile the program.
+	Compile(CompileError),
+	/// First output byte that differs; `None` when one output is a prefix of the other.
+	Output { offset: usize, expected: Option<u8>, actual: Option<u8> },
+	Tape { cell: usize, expected: u8, actual: u8 },
+	DataPtr { expected: usize, actual: usize },
+	/// One run failed and the other didn't, or they failed for different reasons.
+	Result,
+}
+
+#[derive(Debug)]
+pub struct Divergence {
+	pub kind: DivergenceKind,
+	pub expected: Option<Box<RunSnapshot>>,
+	pub actual: Option<Box<RunSnapshot>>,
+}
+
+/// Runs `prepared` under `backend`, capturing everything observable about the run.
+pub fn run_snapshot(backend: Backend, prepared: &[char], input: &[u8]) -> RunSnapshot {
+	let output = CapturedOutput::new();
+	let mut interpreter = Interpreter::with_output(output.clone());
+	interpreter.feed_input(input);
+	let error = backend.run(&mut interpreter, prepared).err().map(|err| err.reason);
+
+	RunSnapshot {
+		output: output.contents(),
+		tape: interpreter.tape().to_vec(),
+		data_ptr: interpreter.get_data_ptr(),
+		error,
+		state: format!("{interpreter:?}"),
+	}
+}
+
+/// Runs `characters` under both backends with the same input, returning the snapshot of `backend`
+/// if nothing observable differs from `reference`.
+pub fn verify(backend: Backend, reference: Backend, characters: &[char], input: &[u8]) -> Result<RunSnapshot, Divergence> {
+	let prepare = |backend: Backend| backend.prepare(characters).map_err(|err| Divergence {
+		kind: DivergenceKind::Compile(err),
+		expected: None,
+		actual: None,
+	});
+	let expected = run_snapshot(reference, &prepare(reference)?, input);
+	let actual = run_snapshot(backend, &prepare(backend)?, input);
+
+	match first_divergence(&expected, &actual) {
+		None => Ok(actual),
+		Some(kind) => Err(Divergence { kind, expected: Some(Box::new(expected)), actual: Some(Box::new(actual)) }),
+	}
+}
+
+fn first_divergence(expected: &RunSnapshot, actual: &RunSnapshot) -> Option<DivergenceKind> {
+	let same_result = match (&expected.error, &actual.error) {
+		(None, None) => true,
+		(Some(expected), Some(actual)) => mem::discriminant(expected) == mem::discriminant(actual),
+		_ => false,
+	};
+	if !same_result {
+		return Some(DivergenceKind::Result);
+	}
+
+	let output_len = expected.output.len().max(actual.output.len());
+	if let Some(offset) = (0..output_len).find(|&idx| expected.output.get(idx) != actual.output.get(idx)) {
+		return Some(DivergenceKind::Output {
+			offset,
+			expected: expected.output.get(offset).copied(),
+			actual: actual.output.get(offset).copied(),
+		});
+	}
+
+	// Cells past the written range are zero
+	let tape_len = expected.tape.len().max(actual.tape.len());
+	let cell = |tape: &[u8], idx: usize| tape.get(idx).copied().unwrap_or(0);
+	if let Some(idx) = (0..tape_len).find(|&idx| cell(&expected.tape, idx) != cell(&actual.tape, idx)) {
+		return Some(DivergenceKind::Tape {
+			cell: idx,
+			expected: cell(&expected.tape, idx),
+			actual: cell(&actual.tape, idx),
+		});
+	}
+
+	if expected.data_ptr != actual.data_ptr {
+		return Some(DivergenceKind::DataPtr { expected: expected.data_ptr, actual: actual.data_ptr });
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+	fn chars(program: &str) -> Vec<char> {
+		program.chars().collect()
+	}
+
+	#[test]
+	fn closures_run_and_drop_deep_nesting() {
+		let depth = 60_000;
+		// Never entered, then entered all the way down
+		let skipped = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+		let entered = format!("+{}-{}+.", "[".repeat(depth), "]".repeat(depth));
+		for backend in [Backend::Closures, Backend::Ir] {
+			let snapshot = run_snapshot(backend, &backend.prepare(&chars(&skipped)).unwrap(), &[]);
+			assert!(snapshot.error.is_none(), "{backend:?}");
+			let snapshot = run_snapshot(backend, &backend.prepare(&chars(&entered)).unwrap(), &[]);
+			assert_eq!((snapshot.output, snapshot.error.is_none()), (vec![1], true), "{backend:?}");
+		}
+	}
+
+	#[test]
+	fn closures_match_the_ir_backend() {
+		let programs = [
+			(HELLO, ""),
+			(",[.,]", "echo"),
+			(",[->+>++<<]>.>.", "\u{5}"),
+			("+[>+]", ""),
+			(">>+[<]<.", ""),
+			("+[-->-[>>+>-----<<]<--<---]>-.>>>+.>>..+++[.>]<<<<.+++.------.<<-.>>>>+.", ""),
+			("<", ""),
+			("-", ""),
+			("+[-]-", ""),
+		];
+		for (program, input) in programs {
+			if let Err(divergence) = verify(Backend::Closures, Backend::Ir, &chars(program), input.as_bytes()) {
+				panic!("{program}: {:?}", divergence.kind);
+			}
+		}
+	}
+
+	#[test]
+	fn every_backend_stops_at_the_tape_cap() {
+		for limit in [1, 16, 300] {
+			for backend in [Backend::Naive, Backend::Ir, Backend::Closures] {
+				let mut interpreter = Interpreter::new().with_max_tape_cells(limit);
+				let err = backend.run(&mut interpreter, &backend.prepare(&chars("+[>+]")).unwrap()).unwrap_err();
+				assert!(
+					matches!(err.reason, InterpreterErrorReason::TapeLimitExceeded { requested, limit: cap } if requested == limit && cap == limit),
+					"{backend:?} with {limit} cells: {:?}", err.reason
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn miscompiles_are_reported_where_they_diverge() {
+		crate::ir::BREAK_MUL_ADD.set(true);
+		let divergence = verify(Backend::Ir, Backend::Naive, &chars("++[->+++<]>."), &[]).unwrap_err();
+		crate::ir::BREAK_MUL_ADD.set(false);
+
+		assert!(
+			matches!(divergence.kind, DivergenceKind::Output { offset: 0, expected: Some(6), actual: Some(8) }),
+			"{:?}", divergence.kind
+		);
+		let (expected, actual) = (divergence.expected.unwrap(), divergence.actual.unwrap());
+		assert_eq!((expected.tape[1], actual.tape[1]), (6, 8));
+		assert!(verify(Backend::Ir, Backend::Naive, &chars("++[->+++<]>."), &[]).is_ok());
+	}
+}
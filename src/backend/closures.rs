@@ -0,0 +1,99 @@
+//! Backend compiling the IR into boxed closures, one per op with every run of ops between two
+//! brackets called back to back, so the straight-line parts of the program are a chain of direct
+//! calls instead of a dispatch per instruction. Loops stay jumps between those runs: a tree of
+//! closures each owning its body would recurse once per nesting level to run and to drop.
+
+use crate::interpreter::error::InterpreterError;
+use crate::interpreter::Interpreter;
+use crate::ir::{Ir, Op};
+
+type Closure = Box<dyn Fn(&mut Interpreter) -> Result<(), InterpreterError>>;
+
+enum Step {
+	/// Ops without a bracket among them, run one after the other.
+	Block(Vec<Closure>),
+	/// `[`, with the index of the step of its `]`.
+	LoopStart(usize),
+	/// `]`, with the index of the step of its `[`.
+	LoopEnd(usize),
+}
+
+pub struct ClosureProgram {
+	steps: Vec<Step>,
+}
+
+impl ClosureProgram {
+	pub fn build(ir: &Ir) -> Self {
+		let mut steps = Vec::new();
+		let mut open_loops = Vec::new();
+
+		for op in ir.ops() {
+			let closure: Closure = match op {
+				&Op::Move(delta) => Box::new(move |machine| machine.move_ptr(delta)),
+				&Op::Add(delta) => Box::new(move |machine| machine.delta_data_cell(delta)),
+				Op::SetZero => Box::new(|machine| machine.write_memory(0)),
+				Op::ScanRight => Box::new(|machine| machine.scan(true)),
+				Op::ScanLeft => Box::new(|machine| machine.scan(false)),
+				Op::MulAdd(mul_add) => {
+					let mul_add = mul_add.clone();
+					Box::new(move |machine| machine.mul_add(&mul_add))
+				}
+				Op::Print => Box::new(|machine| machine.print_ptr()),
+				Op::Read => Box::new(|machine| machine.read_ptr()),
+				Op::JumpIfZero(_) => {
+					open_loops.push(steps.len());
+					// Pointed at its `]` once that is reached
+					steps.push(Step::LoopStart(0));
+					continue;
+				}
+				Op::JumpIfNonZero(_) => {
+					let start = open_loops.pop().expect("IR jumps are balanced");
+					steps[start] = Step::LoopStart(steps.len());
+					steps.push(Step::LoopEnd(start));
+					continue;
+				}
+			};
+			match steps.last_mut() {
+				Some(Step::Block(block)) => block.push(closure),
+				_ => steps.push(Step::Block(vec![closure])),
+			}
+		}
+
+		ClosureProgram { steps }
+	}
+
+	pub fn run(&self, machine: &mut Interpreter) -> Result<(), InterpreterError> {
+		let result = self.run_steps(machine);
+		if result.is_ok() {
+			machine.halt();
+		}
+		machine.flush_output();
+		result
+	}
+
+	fn run_steps(&self, machine: &mut Interpreter) -> Result<(), InterpreterError> {
+		let mut pc = 0;
+		while let Some(step) = self.steps.get(pc) {
+			pc = match *step {
+				Step::Block(ref block) => {
+					for op in block {
+						op(machine)?;
+					}
+					pc + 1
+				}
+				Step::LoopStart(end) if machine.read_memory()? == 0 => end + 1,
+				Step::LoopEnd(_) if machine.read_memory()? == 0 => pc + 1,
+				// Going into a loop or around it again, where a run can be stopped
+				Step::LoopStart(_) => {
+					machine.check_interrupt()?;
+					pc + 1
+				}
+				Step::LoopEnd(start) => {
+					machine.check_interrupt()?;
+					start + 1
+				}
+			};
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,1095 @@
+//! Command line parsing, from the arguments after the program name to a [`Command`].
+//!
+//! Every subcommand has a table of its [`Flag`]s, which `--help` lists and unknown flags are
+//! caught with before the rest is parsed.
+
+use std::io::{self, Write};
+use std::ops::Range;
+use std::slice;
+use std::str::FromStr;
+use std::time::Duration;
+
+use brainfuck::backend::Backend;
+use brainfuck::corpus;
+use brainfuck::debugger::{self, DEFAULT_JOURNAL_WINDOW};
+use brainfuck::dialect::Dialect;
+use brainfuck::emit::llvm::LlvmOptions;
+use brainfuck::interpreter::{EofPolicy, DEFAULT_DEBUG_CHAR, DEFAULT_MAX_LOOP_DEPTH, MEM_SIZE};
+use brainfuck::ir::DEFAULT_OPT_LEVEL;
+use brainfuck::native::BuildOptions;
+use brainfuck::sandbox::Limits;
+
+use crate::profile::{self, ReportFormat};
+use crate::trace::{self, TraceClass, TraceFormat, TraceOptions};
+use crate::visualize;
+
+/// Why the arguments don't make a [`Command`].
+pub enum CliError {
+	/// `--help` or `-h`, with the subcommand it was given to.
+	Help(&'static str),
+	/// `--version` or `-V`.
+	Version,
+	UnknownFlag(String),
+	/// A missing file or value, or a value that doesn't parse, with the flag or the subcommand it
+	/// was given to and what is wrong with it.
+	Invalid { flag: String, reason: String },
+}
+
+fn invalid(flag: &str, reason: impl Into<String>) -> CliError {
+	CliError::Invalid { flag: flag.to_string(), reason: reason.into() }
+}
+
+/// A flag as `--help` lists it. An empty `value` takes nothing, one starting with `=`, `[` or `<`
+/// is written right after the name, and any other comes in the next argument or after an `=`.
+pub struct Flag {
+	pub name: &'static str,
+	pub value: &'static str,
+	pub help: &'static str,
+}
+
+const fn flag(name: &'static str, value: &'static str, help: &'static str) -> Flag {
+	Flag { name, value, help }
+}
+
+impl Flag {
+	fn joined(&self) -> bool {
+		self.value.starts_with(['=', '[', '<'])
+	}
+
+	/// Whether `arg` is this flag, and so whether the argument after it is its value.
+	fn matches(&self, arg: &str) -> Option<bool> {
+		if self.joined() {
+			let value = arg.strip_prefix(self.name)?;
+			// Only `-O<level>` has its value right after the name, the rest need an `=`
+			let fits = self.value.starts_with('<') || value.is_empty() || value.starts_with('=');
+			return fits.then_some(false);
+		}
+		(arg == self.name).then_some(!self.value.is_empty())
+	}
+}
+
+/// File name reading the program from stdin.
+pub const STDIN_SOURCE: &str = "-";
+
+/// What the program given with `-e` is called where a file name would be.
+pub const EVAL_SOURCE: &str = "<command line>";
+
+pub const RUN_FLAGS: &[Flag] = &[
+	flag("-e", "program", "Run the program given instead of a file"),
+	flag("--eval", "program", "Same as -e"),
+	flag("--backend", "naive|ir|closures", "Engine running the program"),
+	flag("--verify-against", "naive|ir|closures", "Run on both engines and compare, instead of running"),
+	flag("--visualize", "", "Draw the tape as the program runs"),
+	flag("--delay", "=ms", "Time between frames of --visualize"),
+	flag("--heatmap", "", "Print how often every cell was written"),
+	flag("--coverage", "", "Print how much of the program ran and where the rest is"),
+	flag("--coverage-annotate", "", "Print the source with what never ran in braces"),
+	flag("--stats", "", "Print instruction counts to stderr"),
+	flag("--stats-format", "table|json", "Format of --stats and --profile"),
+	flag("--time", "", "Print how long loading and running took to stderr"),
+	flag("--json", "", "Print the result as JSON, the program's output going to stderr"),
+	flag("-v", "", "Print the progress and a one-line summary of the run to stderr"),
+	flag("--quiet", "", "Print no warnings"),
+	flag("--verbose", "", "Same as -v"),
+	flag("--no-loop-warning", "", "Don't warn about loops running for long"),
+	flag("--detect-infinite", "", "Fail loops going around without changing anything"),
+	flag("--enable-debug-char", "[=c]", "Dump the machine on reaching c, ! by default"),
+	flag("--max-steps", "N", "Stop after N instructions"),
+	flag("--max-output", "BYTES", "Fail the run printing more than BYTES"),
+	flag("--max-loop-depth", "N", "Fail loops nested deeper than N"),
+	flag("--max-loop-iterations", "N", "Fail loops going around more than N times in a row"),
+	flag("--timeout", "5s|100ms", "Stop the run after this long"),
+	flag("--timeout-includes-input", "", "Count time waiting for input towards --timeout"),
+	flag("--dump-on-exit", "", "Print a hex dump of the tape after a successful run"),
+	flag("--dump-memory", "[=all|nonzero|start..end]", "Print the tape to stderr after the run, even a failed one"),
+	flag("--dump-memory-to", "file", "Write the written part of the tape to a file after the run, as raw bytes"),
+	flag("--record", "file", "Save every byte the program reads"),
+	flag("--replay", "file", "Read input from a file --record saved"),
+	flag("--input", "file", "Same as --replay, needed by programs read from stdin"),
+	flag("--init", "file", "Fill the tape from cell 0 with the bytes of a file"),
+	flag("--start-ptr", "N", "Start the data pointer on cell N"),
+	flag("--eof", "error|zero|unchanged", "What , does once the input is over, error by default"),
+	flag("--tape-size", "N", "Cells of the tape, at most 30000"),
+	flag("--echo-input", "", "Print the input the program reads"),
+	flag("--deterministic", "", "Read input only from --replay, and print a digest of the run"),
+	flag("--dialect", "bf|ook", "Language of the program, ook for files ending in .ook"),
+	flag("--symbol-map", "file", "Read the program with its instructions renamed as the file lists"),
+	flag("--line-comments", "marker", "Ignore everything from marker to the end of the line"),
+	flag("--bang-input", "", "Take everything after the first ! as the input instead of stdin"),
+	flag("--strict", "", "Refuse to run programs with unmatched brackets, wherever they are"),
+	flag("--check", "", "Only validate the programs, same as the check subcommand"),
+	flag("--trace", "file", "Write every step taken to a file"),
+	flag("--trace-limit", "N", "Stop tracing after N steps"),
+	flag("--trace-filter", "io,jumps,writes", "Steps to trace"),
+	flag("--trace-format", "tsv|chrome", "Format of the trace"),
+	flag("--trace-sample", "N", "Steps between data pointer samples in a Chrome trace"),
+	flag("--coredump", "file", "Save the machine to a file if the run fails"),
+	flag("--profile", "loops|memory|annotate", "Profile loops, cells or instructions"),
+	flag("--profile-format", "table|json", "Same as --stats-format"),
+	flag("--profile-top", "N", "Loops or cells a profile lists"),
+	flag("-o", "file", "File for --profile annotate or the trace"),
+	flag("--shared-tape", "", "Start every program on the tape the one before left"),
+	flag("--concat", "", "Run the files as a single program, joined in order"),
+	flag("--fail-fast", "", "Stop at the first program that fails"),
+];
+
+pub const CHECK_FLAGS: &[Flag] = &[
+	flag("--dialect", "bf|ook", "Language of the programs, ook for files ending in .ook"),
+	flag("--symbol-map", "file", "Read the programs with their instructions renamed as the file lists"),
+	flag("--line-comments", "marker", "Ignore everything from marker to the end of the line"),
+	flag("--bang-input", "", "Check only what comes before the first !"),
+	flag("--concat", "", "Check the files as a single program, joined in order"),
+];
+
+pub const TEST_FLAGS: &[Flag] = &[
+	flag("--filter", "SUBSTR", "Run only the tests whose name contains SUBSTR"),
+	flag("--max-steps", "N", "Fail tests running more than N instructions"),
+	flag("--timeout", "5s|100ms", "Fail tests running for longer, 10s by default"),
+	flag("--eof", "error|zero|unchanged", "What , does once the input is over, error by default"),
+];
+
+pub const COMPILE_FLAGS: &[Flag] = &[
+	flag("--emit", "=bfc|c|ir|llvm|wasm", "Output format"),
+	flag("--opt-level", "N", "Optimizations to run on the IR"),
+	flag("--tape-size", "N", "Cells of the tape of --emit=llvm, at most 30000"),
+	flag("--cell-bits", "8|16|32", "Width of a cell of --emit=llvm"),
+	flag("-o", "output", "Output file"),
+];
+
+pub const BUILD_FLAGS: &[Flag] = &[
+	flag("-O", "<level>", "Optimization level passed to the C compiler"),
+	flag("--keep-temps", "", "Keep the generated C source"),
+	flag("-o", "executable", "Executable to write"),
+];
+
+pub const DEBUG_FLAGS: &[Flag] = &[
+	flag("--input", "file", "File the program reads from"),
+	flag("--journal-window", "steps", "Steps that can be stepped back"),
+	flag("--core", "file", "Core dump to inspect instead of running the program"),
+];
+
+const SUBCOMMANDS: &[&str] = &[
+	"run",
+	"check",
+	"test",
+	"compile",
+	"build",
+	"debug",
+	#[cfg(feature = "tui")]
+	"tui",
+];
+
+/// The flags of `subcommand`.
+fn flags(subcommand: &str) -> &'static [Flag] {
+	match subcommand {
+		"check" => CHECK_FLAGS,
+		"test" => TEST_FLAGS,
+		"compile" => COMPILE_FLAGS,
+		"build" => BUILD_FLAGS,
+		"debug" | "tui" => DEBUG_FLAGS,
+		_ => RUN_FLAGS,
+	}
+}
+
+/// Prints the usage and every flag of `subcommand`.
+pub fn print_help(program_name: &str, subcommand: &'static str) {
+	println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+	print_usage(program_name, &mut io::stdout());
+	let flags = flags(subcommand);
+	if flags.is_empty() {
+		return;
+	}
+	println!("\nFlags of {subcommand}:");
+	let spelled: Vec<String> = flags.iter()
+		.map(|flag| match (flag.value, flag.joined()) {
+			("", _) => flag.name.to_string(),
+			(value, true) => format!("{}{value}", flag.name),
+			(value, false) => format!("{} {value}", flag.name),
+		})
+		.collect();
+	let width = spelled.iter().map(String::len).max().unwrap_or_default();
+	for (flag, spelled) in flags.iter().zip(&spelled) {
+		println!("  {spelled:<width$}  {}", flag.help);
+	}
+}
+
+/// `args` with every `--flag=value` of a flag whose value is the next argument split in two, so
+/// either spelling works.
+fn split_values(args: &[String], flags: &[Flag]) -> Vec<String> {
+	let mut split = Vec::with_capacity(args.len());
+	for arg in args {
+		let value_flag = arg.split_once('=')
+			.filter(|(name, _)| flags.iter().any(|flag| flag.matches(name) == Some(true)));
+		match value_flag {
+			Some((name, value)) => split.extend([name.to_string(), value.to_string()]),
+			None => split.push(arg.clone()),
+		}
+	}
+	split
+}
+
+/// The first argument looking like a flag that none of `flags` is.
+fn unknown_flag<'a>(args: &'a [String], flags: &[Flag]) -> Option<&'a String> {
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		if arg == STDIN_SOURCE || !arg.starts_with('-') {
+			continue;
+		}
+		match flags.iter().find_map(|flag| flag.matches(arg)) {
+			Some(true) => {
+				args.next();
+			}
+			Some(false) => {}
+			None => return Some(arg),
+		}
+	}
+	None
+}
+
+/// `value` given to `flag`, as a number.
+fn number<T: FromStr>(flag: &str, value: &str) -> Result<T, CliError> {
+	value.parse().map_err(|_| invalid(flag, format!("`{value}` isn't a number")))
+}
+
+/// `value` given to `flag`, one of the names `from_name` knows, which `flags` lists.
+fn named<T>(flags: &[Flag], flag: &str, value: &str, from_name: impl Fn(&str) -> Option<T>) -> Result<T, CliError> {
+	from_name(value).ok_or_else(|| {
+		let expected = flags.iter()
+			.find(|known| known.name == flag)
+			.map_or("", |known| known.value.trim_start_matches(['=', '[']).trim_end_matches(']'));
+		invalid(flag, format!("takes {expected}, not `{value}`"))
+	})
+}
+
+/// The arguments of a subcommand, whose values of its `flags` fail naming the flag.
+struct Args<'a> {
+	args: slice::Iter<'a, String>,
+	flags: &'static [Flag],
+}
+
+impl<'a> Args<'a> {
+	fn new(args: &'a [String], flags: &'static [Flag]) -> Self {
+		Args { args: args.iter(), flags }
+	}
+
+	/// The argument after `flag`, its value.
+	fn value(&mut self, flag: &str) -> Result<&'a String, CliError> {
+		self.args.next().ok_or_else(|| invalid(flag, "needs a value"))
+	}
+
+	fn number<T: FromStr>(&mut self, flag: &str) -> Result<T, CliError> {
+		number(flag, self.value(flag)?)
+	}
+
+	fn named<T>(&mut self, flag: &str, from_name: impl Fn(&str) -> Option<T>) -> Result<T, CliError> {
+		let value = self.value(flag)?;
+		named(self.flags, flag, value, from_name)
+	}
+}
+
+impl<'a> Iterator for Args<'a> {
+	type Item = &'a String;
+
+	fn next(&mut self) -> Option<&'a String> {
+		self.args.next()
+	}
+}
+
+pub enum Command {
+	Run(Box<RunOptions>),
+	/// Validate programs without running them, read as they would be run.
+	Check(Box<RunOptions>),
+	/// Run the programs of a directory against the output they should print.
+	Test(TestOptions),
+	Compile(CompileOptions),
+	Build(BuildCommandOptions),
+	Debug(DebugOptions),
+	#[cfg(feature = "tui")]
+	Tui(DebugOptions),
+}
+
+pub struct RunOptions {
+	/// Programs to run one after another, just [`EVAL_SOURCE`] with `-e`.
+	pub filenames: Vec<String>,
+	/// Source given with `-e`, run instead of a file.
+	pub eval: Option<String>,
+	/// Start every program after the first on the tape the previous one left.
+	pub shared_tape: bool,
+	/// Join the files into a single program instead of running them one after another.
+	pub concat: bool,
+	/// Stop at the first program that fails.
+	pub fail_fast: bool,
+	pub heatmap: bool,
+	/// Print the progress of the run and a one-line summary of it.
+	pub verbose: bool,
+	/// Print no warnings.
+	pub quiet: bool,
+	/// Refuse to run programs with unmatched brackets, even ones the run never reaches.
+	pub strict: bool,
+	/// Validate the programs instead of running them, what `check` does.
+	pub check: bool,
+	/// Take everything after the first `!` of the program as its input.
+	pub bang_input: bool,
+	/// Language of the programs, `None` to go by their extension.
+	pub dialect: Option<Dialect>,
+	/// File renaming the instructions, for brainfuck spelled with other tokens.
+	pub symbol_map: Option<String>,
+	/// Marker starting a comment that runs to the end of the line, instructions and all.
+	pub line_comments: Option<String>,
+	/// Read input only from `--replay`, and print a digest of the run.
+	pub deterministic: bool,
+	/// Print how much of the program ran and where the rest is.
+	pub coverage: bool,
+	/// Print the source with the instructions that never ran marked.
+	pub coverage_annotate: bool,
+	/// Print instruction counts to stderr once the run ends.
+	pub stats: bool,
+	/// Print how long loading and running took to stderr.
+	pub time: bool,
+	/// Warn about loops that run for long without I/O.
+	pub loop_warning: bool,
+	/// Dump the machine to stderr wherever this character is in the program.
+	pub debug_char: Option<char>,
+	/// Fail loops that go around without changing anything.
+	pub detect_infinite: bool,
+	/// Stop after this many instructions, or IR ops for the compiled backends.
+	pub max_steps: Option<u64>,
+	/// Print the bytes the program reads along with its output.
+	pub echo_input: bool,
+	/// Fail the program when a loop goes around more than this many times in a row.
+	pub max_loop_iterations: Option<u64>,
+	/// Fail the program when loops nest deeper than this.
+	pub max_loop_depth: usize,
+	/// Fail the program once it prints more than this many bytes.
+	pub max_output: Option<u64>,
+	/// Stop after this much wall-clock time.
+	pub timeout: Option<Duration>,
+	/// Count time blocked reading input towards the timeout.
+	pub timeout_includes_input: bool,
+	/// Print the final machine as JSON on stdout, moving the program's output to stderr.
+	pub json: bool,
+	/// Print the loops with the most iterations to stderr once the run ends, at most this many.
+	pub loop_profile: Option<usize>,
+	/// Print the most accessed cells to stderr once the run ends, at most this many.
+	pub memory_profile: Option<usize>,
+	/// Format of the stats and profiles, JSON putting all of them in one document.
+	pub report_format: ReportFormat,
+	/// Write the source annotated with line execution counts, to the file if one is given.
+	pub annotate: Option<Option<String>>,
+	pub dump_on_exit: bool,
+	pub backend: Backend,
+	pub verify_against: Option<Backend>,
+	/// Delay between frames when visualizing the tape.
+	pub visualize: Option<Duration>,
+	/// File logging every byte the program reads.
+	pub record: Option<String>,
+	/// File whose bytes are fed to the program instead of stdin.
+	pub replay: Option<String>,
+	/// File whose bytes fill the tape from cell 0 before the program starts.
+	pub init: Option<String>,
+	/// Cell the data pointer starts on.
+	pub start_ptr: usize,
+	pub eof_policy: EofPolicy,
+	/// Cells of the tape, at most [`MEM_SIZE`].
+	pub tape_size: usize,
+	pub trace: Option<TraceOptions>,
+	/// File the machine state is saved to when the run fails.
+	pub coredump: Option<String>,
+	/// Cells to dump to stderr once the run ends, or into the result with `--json`.
+	pub dump_memory: Option<MemoryDump>,
+	/// File the written part of the tape is saved to once the run ends, however it ended.
+	pub dump_memory_to: Option<String>,
+}
+
+/// What `--dump-memory` shows of the tape.
+#[derive(Clone)]
+pub enum MemoryDump {
+	/// Hex dump of the written part.
+	Written,
+	/// The address and value of every nonzero cell, one per line.
+	NonZero,
+	/// Hex dump of these cells.
+	Range(Range<usize>),
+}
+
+impl MemoryDump {
+	/// `all`, `nonzero` or a range such as `0..64`.
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"all" => Some(MemoryDump::Written),
+			"nonzero" => Some(MemoryDump::NonZero),
+			range => debugger::parse_range(range)
+				.filter(|range| range.start <= range.end)
+				.map(MemoryDump::Range),
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
+pub enum EmitFormat {
+	Bytecode,
+	C,
+	Ir,
+	Llvm,
+	Wasm,
+}
+
+pub struct CompileOptions {
+	/// Files compiled as one program, joined in order.
+	pub filenames: Vec<String>,
+	pub emit: EmitFormat,
+	pub opt_level: u8,
+	/// Tape and cells of the LLVM IR, the other formats having the interpreter's.
+	pub llvm: LlvmOptions,
+	pub output: Option<String>,
+}
+
+pub struct TestOptions {
+	pub dir: String,
+	/// Substring of the names of the tests to run.
+	pub filter: Option<String>,
+	/// What every test may use.
+	pub limits: Limits,
+	pub eof_policy: EofPolicy,
+}
+
+pub struct BuildCommandOptions {
+	pub filename: String,
+	pub output: String,
+	pub build: BuildOptions,
+}
+
+/// Options of `debug`, also used by `tui`.
+pub struct DebugOptions {
+	pub filename: String,
+	/// File the program's `,` reads from, since stdin carries the debugger commands.
+	pub input: Option<String>,
+	pub journal_window: usize,
+	/// Core dump to inspect instead of running the program.
+	pub core: Option<String>,
+}
+
+/// Writes the usage of every subcommand to `out`, stdout for `--help` and stderr for mistakes.
+pub fn print_usage(program_name: &str, out: &mut dyn Write) {
+	let _ = writeln!(out, "Usage: {program_name} [run] [--backend naive|ir|closures] [--verify-against naive|ir|closures]");
+	let _ = writeln!(out, "       {}  [--visualize [--delay=ms]] [--heatmap] [--coverage] [--coverage-annotate] [--stats [--stats-format table|json]] [--time] [--json] [--no-loop-warning] [--detect-infinite] [--enable-debug-char[=c]] [--max-steps N] [--max-output BYTES] [--max-loop-depth N] [--max-loop-iterations N] [--timeout 5s|100ms [--timeout-includes-input]] [--dump-on-exit] [--dump-memory[=all|nonzero|start..end]] [--dump-memory-to file]", " ".repeat(program_name.len()));
+	let _ = writeln!(out, "       {}  [--record file] [--replay|--input file] [--init file [--start-ptr N]] [--eof error|zero|unchanged] [--tape-size N] [--echo-input] [--deterministic] [--dialect bf|ook | --symbol-map file] [--line-comments // or ;] [--bang-input] [--strict] [--check] [-v] [--quiet]", " ".repeat(program_name.len()));
+	let _ = writeln!(out, "       {}  [--trace file [--trace-limit N] [--trace-filter io,jumps,writes]] [--coredump file]", " ".repeat(program_name.len()));
+	let _ = writeln!(out, "       {}  [--trace-format tsv|chrome [--trace-sample N] [-o file]]", " ".repeat(program_name.len()));
+	let _ = writeln!(out, "       {}  [--profile annotate [-o file]]", " ".repeat(program_name.len()));
+	let _ = writeln!(out, "       {}  [--profile loops|memory [--profile-format table|json] [--profile-top N]]", " ".repeat(program_name.len()));
+	let _ = writeln!(out, "       {}  [--shared-tape] [--fail-fast] [--concat] brainfuck.bf|- [more.bf ...] | -e program", " ".repeat(program_name.len()));
+	let _ = writeln!(out, "       {program_name} check brainfuck.bf [more.bf ...]");
+	let _ = writeln!(out, "       {program_name} test [--filter SUBSTR] [--max-steps N] [--timeout 5s|100ms] [--eof error|zero|unchanged] directory");
+	let _ = writeln!(out, "       {program_name} compile [--emit=bfc|c|ir|llvm|wasm] [--opt-level N] [--tape-size N] [--cell-bits 8|16|32] brainfuck.bf [more.bf ...] [-o output]");
+	let _ = writeln!(out, "       {program_name} build [-O<level>] [--keep-temps] brainfuck.bf -o executable");
+	let _ = writeln!(out, "       {program_name} debug [--input file] [--journal-window steps] [--core file] brainfuck.bf");
+	#[cfg(feature = "tui")]
+	let _ = writeln!(out, "       {program_name} tui [--input file] brainfuck.bf");
+}
+
+/// A duration such as `5s`, `100ms` or `2m`, seconds when there's no unit.
+pub fn parse_duration(duration: &str) -> Option<Duration> {
+	let (number, unit) = duration.split_at(duration.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(duration.len()));
+	let number: f64 = number.parse().ok()?;
+	let seconds = match unit {
+		"ms" => number / 1000.0,
+		"" | "s" => number,
+		"m" => number * 60.0,
+		_ => return None,
+	};
+	Duration::try_from_secs_f64(seconds).ok()
+}
+
+fn parse_emit_format(format: &str) -> Option<EmitFormat> {
+	match format {
+		"bfc" => Some(EmitFormat::Bytecode),
+		"c" => Some(EmitFormat::C),
+		"ir" => Some(EmitFormat::Ir),
+		"llvm" => Some(EmitFormat::Llvm),
+		"wasm" => Some(EmitFormat::Wasm),
+		_ => None,
+	}
+}
+
+fn parse_run_options(args: &[String]) -> Result<RunOptions, CliError> {
+	let mut filenames = Vec::new();
+	let mut shared_tape = false;
+	let mut concat = false;
+	let mut fail_fast = false;
+	let mut heatmap = false;
+	let mut coverage = false;
+	let mut coverage_annotate = false;
+	let mut deterministic = false;
+	let mut line_comments = None;
+	let mut dialect = None;
+	let mut symbol_map = None;
+	let mut bang_input = false;
+	let mut strict = false;
+	let mut check = false;
+	let mut eval = None;
+	let mut verbose = false;
+	let mut quiet = false;
+	let mut stats = false;
+	let mut time = false;
+	let mut loop_warning = true;
+	let mut detect_infinite = false;
+	let mut debug_char = None;
+	let mut max_steps = None;
+	let mut timeout = None;
+	let mut echo_input = false;
+	let mut max_output = None;
+	let mut max_loop_depth = DEFAULT_MAX_LOOP_DEPTH;
+	let mut max_loop_iterations = None;
+	let mut timeout_includes_input = false;
+	let mut json = false;
+	let mut loop_profile = false;
+	let mut annotate = false;
+	let mut memory_profile = false;
+	let mut profile_output = None;
+	let mut report_format = ReportFormat::Table;
+	let mut profile_top = profile::DEFAULT_TOP;
+	let mut dump_on_exit = false;
+	let mut backend = Backend::Naive;
+	let mut verify_against = None;
+	let mut visualize = false;
+	let mut delay = Duration::from_millis(visualize::DEFAULT_DELAY_MS);
+	let mut record = None;
+	let mut replay = None;
+	let mut init = None;
+	let mut start_ptr = 0;
+	let mut eof_policy = EofPolicy::default();
+	let mut tape_size = MEM_SIZE;
+	let mut trace = None;
+	let mut trace_limit = None;
+	let mut trace_filter = Vec::new();
+	let mut trace_format = None;
+	let mut trace_sample = trace::DEFAULT_SAMPLE_STEPS;
+	let mut coredump = None;
+	let mut dump_memory = None;
+	let mut dump_memory_to = None;
+
+	let mut args = Args::new(args, RUN_FLAGS);
+	while let Some(arg) = args.next() {
+		if arg == "--backend" {
+			backend = args.named(arg, Backend::from_name)?;
+		} else if arg == "--verify-against" {
+			verify_against = Some(args.named(arg, Backend::from_name)?);
+		} else if arg == "--visualize" {
+			visualize = true;
+		} else if let Some(delay_ms) = arg.strip_prefix("--delay=") {
+			delay = Duration::from_millis(number("--delay", delay_ms)?);
+		} else if arg == "--record" {
+			record = Some(args.value(arg)?.clone());
+		} else if arg == "--replay" || arg == "--input" {
+			replay = Some(args.value(arg)?.clone());
+		} else if arg == "--trace" {
+			trace = Some(args.value(arg)?.clone());
+		} else if arg == "--trace-limit" {
+			trace_limit = Some(args.number(arg)?);
+		} else if arg == "--trace-format" {
+			trace_format = Some(args.named(arg, TraceFormat::from_name)?);
+		} else if arg == "--trace-sample" {
+			trace_sample = args.number(arg)?;
+		} else if arg == "--trace-filter" {
+			let classes = args.value(arg)?;
+			trace_filter = classes.split(',')
+				.map(|class| named(RUN_FLAGS, arg, class, TraceClass::from_name))
+				.collect::<Result<_, _>>()?;
+		} else if arg == "--coredump" {
+			coredump = Some(args.value(arg)?.clone());
+		} else if arg == "--init" {
+			init = Some(args.value(arg)?.clone());
+		} else if arg == "--start-ptr" {
+			start_ptr = args.number(arg)?;
+		} else if arg == "--eof" {
+			eof_policy = args.named(arg, EofPolicy::from_name)?;
+		} else if arg == "--tape-size" {
+			tape_size = args.number(arg)?;
+			if !(1..=MEM_SIZE).contains(&tape_size) {
+				return Err(invalid(arg, format!("must be between 1 and {MEM_SIZE}, not {tape_size}")));
+			}
+		} else if arg == "--dump-memory-to" {
+			dump_memory_to = Some(args.value(arg)?.clone());
+		} else if arg == "--dump-memory" {
+			dump_memory = Some(MemoryDump::Written);
+		} else if let Some(dump) = arg.strip_prefix("--dump-memory=") {
+			dump_memory = Some(named(RUN_FLAGS, "--dump-memory", dump, MemoryDump::from_name)?);
+		} else if arg == "--heatmap" {
+			heatmap = true;
+		} else if arg == "--coverage" {
+			coverage = true;
+		} else if arg == "-e" || arg == "--eval" {
+			eval = Some(args.value(arg)?.clone());
+		} else if arg == "-v" || arg == "--verbose" {
+			verbose = true;
+		} else if arg == "--quiet" {
+			quiet = true;
+		} else if arg == "--strict" {
+			strict = true;
+		} else if arg == "--check" {
+			check = true;
+		} else if arg == "--bang-input" {
+			bang_input = true;
+		} else if arg == "--symbol-map" {
+			symbol_map = Some(args.value(arg)?.clone());
+		} else if arg == "--dialect" {
+			dialect = Some(args.named(arg, Dialect::from_name)?);
+		} else if arg == "--line-comments" {
+			line_comments = Some(args.value(arg)?.clone());
+		} else if arg == "--deterministic" {
+			deterministic = true;
+		} else if arg == "--coverage-annotate" {
+			coverage_annotate = true;
+		} else if arg == "--stats" {
+			stats = true;
+		} else if arg == "--time" {
+			time = true;
+		} else if arg == "--no-loop-warning" {
+			loop_warning = false;
+		} else if arg == "--enable-debug-char" {
+			debug_char = Some(DEFAULT_DEBUG_CHAR);
+		} else if let Some(character) = arg.strip_prefix("--enable-debug-char=") {
+			let mut characters = character.chars();
+			debug_char = characters.next();
+			if debug_char.is_none() || characters.next().is_some() {
+				return Err(invalid("--enable-debug-char", format!("takes a single character, not `{character}`")));
+			}
+		} else if arg == "--detect-infinite" {
+			detect_infinite = true;
+		} else if arg == "--max-steps" {
+			max_steps = Some(args.number(arg)?);
+		} else if arg == "--timeout" {
+			timeout = Some(args.named(arg, parse_duration)?);
+		} else if arg == "--timeout-includes-input" {
+			timeout_includes_input = true;
+		} else if arg == "--echo-input" {
+			echo_input = true;
+		} else if arg == "--max-loop-iterations" {
+			max_loop_iterations = Some(args.number(arg)?);
+		} else if arg == "--max-loop-depth" {
+			max_loop_depth = args.number(arg)?;
+		} else if arg == "--max-output" {
+			max_output = Some(args.number(arg)?);
+		} else if arg == "--json" {
+			json = true;
+		} else if arg == "--profile" {
+			match args.value(arg)?.as_str() {
+				"loops" => loop_profile = true,
+				"annotate" => annotate = true,
+				"memory" => memory_profile = true,
+				profile => return Err(invalid(arg, format!("takes loops|memory|annotate, not `{profile}`"))),
+			}
+		} else if arg == "-o" {
+			profile_output = Some(args.value(arg)?.clone());
+		} else if arg == "--profile-format" || arg == "--stats-format" {
+			// Both formats cover every report
+			report_format = args.named(arg, ReportFormat::from_name)?;
+		} else if arg == "--profile-top" {
+			profile_top = args.number(arg)?;
+		} else if arg == "--dump-on-exit" {
+			dump_on_exit = true;
+		} else if arg == "--shared-tape" {
+			shared_tape = true;
+		} else if arg == "--concat" {
+			concat = true;
+		} else if arg == "--fail-fast" {
+			fail_fast = true;
+		} else if arg.starts_with("--") {
+			return Err(CliError::UnknownFlag(arg.clone()));
+		} else {
+			filenames.push(arg.clone());
+		}
+	}
+
+	// `-o` names the annotated listing or the trace, whichever is asked for without a file
+	let trace = match (trace, trace_format) {
+		(None, Some(_)) if !annotate => profile_output.clone(),
+		(trace, _) => trace,
+	};
+	if profile_output.is_some() && !annotate && trace.is_none() {
+		return Err(invalid("-o", "names the file of --profile annotate or a trace, neither is asked for"));
+	}
+
+	match (&eval, filenames.is_empty()) {
+		(Some(_), true) => filenames.push(EVAL_SOURCE.to_string()),
+		// Either a program or files, never both nor neither
+		(Some(_), false) => return Err(invalid("-e", "runs instead of files, not along with them")),
+		(None, true) => return Err(invalid("run", "needs a program")),
+		(None, false) => {}
+	}
+	// A wall clock limit stops runs wherever the machine happens to be
+	if deterministic && timeout.is_some() {
+		return Err(invalid("--timeout", "can't be used with --deterministic"));
+	}
+	if start_ptr >= tape_size {
+		return Err(invalid("--start-ptr", format!("must be a cell of the tape, below {tape_size}")));
+	}
+	// Both say what the source is written in
+	if dialect.is_some() && symbol_map.is_some() {
+		return Err(invalid("--symbol-map", "can't be used with --dialect"));
+	}
+	// Checking reads files, there's none for `-e`
+	if check && eval.is_some() {
+		return Err(invalid("--check", "checks files, not -e"));
+	}
+
+	Ok(RunOptions {
+		filenames,
+		eval,
+		shared_tape,
+		concat,
+		fail_fast,
+		heatmap,
+		coverage,
+		coverage_annotate,
+		verbose,
+		quiet,
+		strict,
+		check,
+		bang_input,
+		dialect,
+		symbol_map,
+		line_comments,
+		deterministic,
+		stats,
+		time,
+		loop_warning,
+		detect_infinite,
+		debug_char,
+		max_steps,
+		echo_input,
+		max_output,
+		max_loop_depth,
+		max_loop_iterations,
+		timeout,
+		timeout_includes_input,
+		json,
+		loop_profile: loop_profile.then_some(profile_top),
+		memory_profile: memory_profile.then_some(profile_top),
+		report_format,
+		annotate: annotate.then_some(profile_output),
+		dump_on_exit,
+		backend,
+		verify_against,
+		visualize: visualize.then_some(delay),
+		record,
+		replay,
+		init,
+		start_ptr,
+		eof_policy,
+		tape_size,
+		trace: trace.map(|path| TraceOptions {
+			path,
+			format: trace_format.unwrap_or(TraceFormat::Tsv),
+			limit: trace_limit,
+			filter: trace_filter,
+			sample: trace_sample,
+		}),
+		coredump,
+		dump_memory,
+		dump_memory_to,
+	})
+}
+
+fn parse_test_options(args: &[String]) -> Result<TestOptions, CliError> {
+	let mut dir = None;
+	let mut filter = None;
+	let mut eof_policy = EofPolicy::default();
+	let mut limits = Limits {
+		timeout: Some(Duration::from_secs(corpus::DEFAULT_TEST_TIMEOUT_SECS)),
+		..Limits::default()
+	};
+
+	let mut args = Args::new(args, TEST_FLAGS);
+	while let Some(arg) = args.next() {
+		if arg == "--filter" {
+			filter = Some(args.value(arg)?.clone());
+		} else if arg == "--max-steps" {
+			limits.fuel = args.number(arg)?;
+		} else if arg == "--timeout" {
+			limits.timeout = Some(args.named(arg, parse_duration)?);
+		} else if arg == "--eof" {
+			eof_policy = args.named(arg, EofPolicy::from_name)?;
+		} else if dir.is_none() {
+			dir = Some(arg.clone());
+		} else {
+			return Err(invalid("test", format!("takes a single directory, not `{arg}` too")));
+		}
+	}
+
+	let dir = dir.ok_or_else(|| invalid("test", "needs a directory"))?;
+	Ok(TestOptions { dir, filter, limits, eof_policy })
+}
+
+fn parse_compile_options(args: &[String]) -> Result<CompileOptions, CliError> {
+	let mut filenames = Vec::new();
+	let mut emit = EmitFormat::Bytecode;
+	let mut opt_level = DEFAULT_OPT_LEVEL;
+	let mut llvm = LlvmOptions::default();
+	let mut llvm_flag = None;
+	let mut output = None;
+
+	let mut args = Args::new(args, COMPILE_FLAGS);
+	while let Some(arg) = args.next() {
+		if let Some(format) = arg.strip_prefix("--emit=") {
+			emit = named(COMPILE_FLAGS, "--emit", format, parse_emit_format)?;
+		} else if arg == "--opt-level" {
+			opt_level = args.number(arg)?;
+		} else if arg == "--tape-size" {
+			llvm.tape_size = args.number(arg)?;
+			if !(1..=MEM_SIZE).contains(&llvm.tape_size) {
+				return Err(invalid(arg, format!("must be between 1 and {MEM_SIZE}, not {}", llvm.tape_size)));
+			}
+			llvm_flag = Some(arg);
+		} else if arg == "--cell-bits" {
+			llvm.cell_bits = args.named(arg, |bits| match bits {
+				"8" => Some(8),
+				"16" => Some(16),
+				"32" => Some(32),
+				_ => None,
+			})?;
+			llvm_flag = Some(arg);
+		} else if arg == "-o" {
+			output = Some(args.value(arg)?.clone());
+		} else {
+			filenames.push(arg.clone());
+		}
+	}
+
+	if filenames.is_empty() {
+		return Err(invalid("compile", "needs a program"));
+	}
+	// The other formats run on the interpreter's tape
+	if let (Some(flag), false) = (llvm_flag, matches!(emit, EmitFormat::Llvm)) {
+		return Err(invalid(flag, "only applies to --emit=llvm"));
+	}
+	Ok(CompileOptions { filenames, emit, opt_level, llvm, output })
+}
+
+fn parse_build_options(args: &[String]) -> Result<BuildCommandOptions, CliError> {
+	let mut filename = None;
+	let mut output = None;
+	let mut build = BuildOptions::default();
+
+	let mut args = Args::new(args, BUILD_FLAGS);
+	while let Some(arg) = args.next() {
+		if arg.starts_with("-O") {
+			build.cc_flags = vec![arg.clone()];
+		} else if arg == "--keep-temps" {
+			build.keep_temps = true;
+		} else if arg == "-o" {
+			output = Some(args.value(arg)?.clone());
+		} else if filename.is_none() {
+			filename = Some(arg.clone());
+		} else {
+			return Err(invalid("build", format!("takes a single program, not `{arg}` too")));
+		}
+	}
+
+	let filename = filename.ok_or_else(|| invalid("build", "needs a program"))?;
+	let output = output.ok_or_else(|| invalid("-o", "is needed, naming the executable"))?;
+	Ok(BuildCommandOptions { filename, output, build })
+}
+
+fn parse_debug_options(args: &[String]) -> Result<DebugOptions, CliError> {
+	let mut filename = None;
+	let mut input = None;
+	let mut journal_window = DEFAULT_JOURNAL_WINDOW;
+	let mut core = None;
+
+	let mut args = Args::new(args, DEBUG_FLAGS);
+	while let Some(arg) = args.next() {
+		if arg == "--input" {
+			input = Some(args.value(arg)?.clone());
+		} else if arg == "--journal-window" {
+			journal_window = args.number(arg)?;
+		} else if arg == "--core" {
+			core = Some(args.value(arg)?.clone());
+		} else if filename.is_none() {
+			filename = Some(arg.clone());
+		} else {
+			return Err(invalid("debug", format!("takes a single program, not `{arg}` too")));
+		}
+	}
+
+	let filename = filename.ok_or_else(|| invalid("debug", "needs a program"))?;
+	Ok(DebugOptions { filename, input, journal_window, core })
+}
+
+/// Parses the arguments after the program name, `run` being the subcommand when none is given.
+pub fn parse(args: &[String]) -> Result<Command, CliError> {
+	let (subcommand, rest) = match args.split_first() {
+		Some((command, rest)) => match SUBCOMMANDS.iter().find(|name| *name == command) {
+			Some(name) => (*name, rest),
+			None => ("run", args),
+		},
+		None => ("run", args),
+	};
+	if rest.iter().any(|arg| arg == "--help" || arg == "-h") {
+		return Err(CliError::Help(subcommand));
+	}
+	if rest.iter().any(|arg| arg == "--version" || arg == "-V") {
+		return Err(CliError::Version);
+	}
+	let rest = &split_values(rest, flags(subcommand))[..];
+	if let Some(flag) = unknown_flag(rest, flags(subcommand)) {
+		return Err(CliError::UnknownFlag(flag.clone()));
+	}
+
+	match subcommand {
+		"check" if rest.is_empty() => Err(invalid("check", "needs a program")),
+		"check" => parse_run_options(rest).map(|options| Command::Check(Box::new(options))),
+		"test" => parse_test_options(rest).map(Command::Test),
+		"compile" => parse_compile_options(rest).map(Command::Compile),
+		"build" => parse_build_options(rest).map(Command::Build),
+		"debug" => parse_debug_options(rest).map(Command::Debug),
+		#[cfg(feature = "tui")]
+		"tui" => parse_debug_options(rest).map(Command::Tui),
+		_ => parse_run_options(rest).map(|options| {
+			if options.check {
+				Command::Check(Box::new(options))
+			} else {
+				Command::Run(Box::new(options))
+			}
+		}),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse_line(line: &str) -> Result<Command, CliError> {
+		parse(&line.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+	}
+
+	fn run_options(line: &str) -> RunOptions {
+		match parse_line(line) {
+			Ok(Command::Run(options)) => *options,
+			_ => panic!("`{line}` isn't a run"),
+		}
+	}
+
+	#[test]
+	fn run_is_the_default_subcommand() {
+		assert_eq!(run_options("hello.bf").filenames, ["hello.bf"]);
+		assert_eq!(run_options("run a.bf b.bf").filenames, ["a.bf", "b.bf"]);
+		assert_eq!(run_options("-").filenames, [STDIN_SOURCE]);
+	}
+
+	#[test]
+	fn eval_runs_instead_of_a_file() {
+		let options = run_options("-e +.");
+		assert_eq!(options.eval.as_deref(), Some("+."));
+		assert_eq!(options.filenames, [EVAL_SOURCE]);
+		assert!(matches!(parse_line("-e +. hello.bf"), Err(CliError::Invalid { .. })));
+	}
+
+	#[test]
+	fn values_come_next_or_after_an_equals_sign() {
+		assert_eq!(run_options("--max-steps 10 hello.bf").max_steps, Some(10));
+		assert_eq!(run_options("--max-steps=10 hello.bf").max_steps, Some(10));
+		assert_eq!(run_options("--backend=ir hello.bf").backend, Backend::Ir);
+		assert!(matches!(parse_line("hello.bf --max-steps"), Err(CliError::Invalid { .. })));
+		assert!(matches!(parse_line("--max-steps ten hello.bf"), Err(CliError::Invalid { .. })));
+	}
+
+	#[test]
+	fn subcommands_take_their_own_flags() {
+		assert!(matches!(parse_line("check a.bf b.bf"), Ok(Command::Check(options)) if options.filenames == ["a.bf", "b.bf"]));
+		assert!(matches!(parse_line("--check a.bf"), Ok(Command::Check(options)) if options.filenames == ["a.bf"]));
+		// Read the same as for running them
+		let Ok(Command::Check(options)) = parse_line("--check --line-comments # --bang-input --strict --dialect ook a.ook") else {
+			panic!("expected a check");
+		};
+		assert_eq!(options.line_comments.as_deref(), Some("#"));
+		assert!(options.bang_input && options.strict && options.dialect == Some(Dialect::Ook));
+		assert!(matches!(parse_line("check --symbol-map ook.map a.txt"), Ok(Command::Check(options)) if options.symbol_map.is_some()));
+		assert!(matches!(parse_line("check --heatmap a.bf"), Err(CliError::UnknownFlag(_))));
+		assert!(matches!(parse_line("check"), Err(CliError::Invalid { .. })));
+		let Ok(Command::Compile(options)) = parse_line("compile --emit=c -o out.c hello.bf") else {
+			panic!("expected a compile");
+		};
+		assert!(matches!(options.emit, EmitFormat::C));
+		assert_eq!(options.output.as_deref(), Some("out.c"));
+		let Ok(Command::Compile(options)) = parse_line("compile --emit=llvm --tape-size 100 --cell-bits 16 hello.bf") else {
+			panic!("expected a compile");
+		};
+		assert_eq!((options.llvm.tape_size, options.llvm.cell_bits), (100, 16));
+		assert!(matches!(parse_line("compile --emit=c --tape-size 100 hello.bf"), Err(CliError::Invalid { .. })));
+		assert!(matches!(parse_line("compile --emit=llvm --cell-bits 12 hello.bf"), Err(CliError::Invalid { .. })));
+		let Ok(Command::Build(options)) = parse_line("build -O2 hello.bf -o hello") else {
+			panic!("expected a build");
+		};
+		assert_eq!(options.build.cc_flags, ["-O2"]);
+		assert_eq!(options.output, "hello");
+	}
+
+	#[test]
+	fn help_version_and_unknown_flags() {
+		assert!(matches!(parse_line("compile --help"), Err(CliError::Help("compile"))));
+		assert!(matches!(parse_line("-h"), Err(CliError::Help("run"))));
+		assert!(matches!(parse_line("--version"), Err(CliError::Version)));
+		assert!(matches!(parse_line("--frobnicate hello.bf"), Err(CliError::UnknownFlag(flag)) if flag == "--frobnicate"));
+		// A run flag isn't one of compile's
+		assert!(matches!(parse_line("compile --heatmap hello.bf"), Err(CliError::UnknownFlag(_))));
+	}
+
+	#[test]
+	fn invalid_arguments_tell_what_is_wrong() {
+		let table = [
+			("--max-steps ten hello.bf", "--max-steps", "`ten` isn't a number"),
+			("hello.bf --max-steps", "--max-steps", "needs a value"),
+			("--tape-size 0 hello.bf", "--tape-size", "must be between 1 and 30000, not 0"),
+			("--deterministic --timeout 1s hello.bf", "--timeout", "can't be used with --deterministic"),
+			("--backend jit hello.bf", "--backend", "takes naive|ir|closures, not `jit`"),
+			("compile --emit=exe hello.bf", "--emit", "takes bfc|c|ir|llvm|wasm, not `exe`"),
+			("build hello.bf", "-o", "is needed, naming the executable"),
+			("", "run", "needs a program"),
+		];
+		for (line, expected_flag, expected_reason) in table {
+			let Err(CliError::Invalid { flag, reason }) = parse_line(line) else {
+				panic!("`{line}` isn't invalid");
+			};
+			assert_eq!((flag.as_str(), reason.as_str()), (expected_flag, expected_reason), "{line}");
+		}
+	}
+
+	#[test]
+	fn semantic_flags_configure_the_machine() {
+		let table: &[(&str, EofPolicy, usize, usize)] = &[
+			("hello.bf", EofPolicy::Error, MEM_SIZE, 0),
+			("--eof zero hello.bf", EofPolicy::Zero, MEM_SIZE, 0),
+			("--eof=unchanged --tape-size 100 hello.bf", EofPolicy::Unchanged, 100, 0),
+			("--tape-size 100 --start-ptr 99 hello.bf", EofPolicy::Error, 100, 99),
+		];
+		for &(line, eof_policy, tape_size, start_ptr) in table {
+			let options = run_options(line);
+			assert_eq!((options.eof_policy, options.tape_size, options.start_ptr), (eof_policy, tape_size, start_ptr), "{line}");
+		}
+	}
+
+	#[test]
+	fn semantic_flags_reject_bad_values() {
+		for line in [
+			"--eof maybe hello.bf",
+			"--tape-size 0 hello.bf",
+			"--tape-size 30001 hello.bf",
+			"--tape-size 100 --start-ptr 100 hello.bf",
+			"--start-ptr 30000 hello.bf",
+		] {
+			assert!(matches!(parse_line(line), Err(CliError::Invalid { .. })), "{line}");
+		}
+	}
+
+	#[test]
+	fn memory_dumps_parse_their_range() {
+		assert!(matches!(run_options("--dump-memory hello.bf").dump_memory, Some(MemoryDump::Written)));
+		assert!(matches!(run_options("--dump-memory=nonzero hello.bf").dump_memory, Some(MemoryDump::NonZero)));
+		assert!(matches!(run_options("--dump-memory=0..64 hello.bf").dump_memory, Some(MemoryDump::Range(range)) if range == (0..64)));
+		for line in ["--dump-memory=64..0 hello.bf", "--dump-memory=some hello.bf", "--dump-memory=0..x hello.bf", "--dump-memory= hello.bf"] {
+			assert!(matches!(parse_line(line), Err(CliError::Invalid { .. })), "{line}");
+		}
+	}
+}
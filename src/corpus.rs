@@ -0,0 +1,257 @@
+//! Test suites of programs with the output they should print: `name.bf`, the input it reads in
+//! `name.in`, empty without one, and the expected output in `name.out`.
+//!
+//! Every program runs in the [`sandbox`], so a hanging one fails its test on the [`Limits`] instead
+//! of stalling the suite.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::interpreter::EofPolicy;
+use crate::sandbox::{self, Limits, Outcome};
+use crate::symbol;
+
+/// How long each program may run unless told otherwise.
+pub const DEFAULT_TEST_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+	/// File name of the program without the `.bf`.
+	pub name: String,
+	pub program: String,
+	pub input: Vec<u8>,
+	pub expected: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum TestResult {
+	Passed,
+	/// The program finished, printing something else.
+	Mismatch { output: Vec<u8> },
+	/// The program didn't finish: it failed, hit a limit or doesn't parse.
+	Failed(Outcome),
+}
+
+impl TestResult {
+	pub fn passed(&self) -> bool {
+		matches!(self, TestResult::Passed)
+	}
+}
+
+/// The tests in `dir`, every `.bf` with a `.out` next to it whose name contains `filter`, sorted by
+/// name. Programs without an expected output aren't tests and are left out.
+pub fn discover(dir: &Path, filter: Option<&str>) -> io::Result<Vec<TestCase>> {
+	let mut cases = Vec::new();
+	for entry in fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.extension().is_none_or(|extension| extension != "bf") {
+			continue;
+		}
+		let Some(name) = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()) else {
+			continue;
+		};
+		if filter.is_some_and(|filter| !name.contains(filter)) {
+			continue;
+		}
+		let expected = match fs::read(path.with_extension("out")) {
+			Ok(expected) => expected,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+			Err(err) => return Err(err),
+		};
+		let input = match fs::read(path.with_extension("in")) {
+			Ok(input) => input,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+			Err(err) => return Err(err),
+		};
+		let program = symbol::decode_source(&fs::read(&path)?).into_iter().collect();
+		cases.push(TestCase { name, program, input, expected });
+	}
+	cases.sort_by(|a, b| a.name.cmp(&b.name));
+	Ok(cases)
+}
+
+/// Runs `case` within `limits`, comparing what it printed with what it should have. Reading past
+/// the end of the input does what `eof_policy` says.
+pub fn run_case(case: &TestCase, limits: Limits, eof_policy: EofPolicy) -> TestResult {
+	match sandbox::run_with_eof(&case.program, &case.input, limits, eof_policy) {
+		Outcome::Completed { output, .. } if output == case.expected => TestResult::Passed,
+		Outcome::Completed { output, .. } => TestResult::Mismatch { output },
+		outcome => TestResult::Failed(outcome),
+	}
+}
+
+/// Most lines of `expected` times lines of `actual` left to line up once the common start and end are
+/// taken off, past which the rest is shown as removed and then added rather than aligned.
+const MAX_DIFF_CELLS: usize = 1 << 20;
+
+/// A unified-style diff of the lines of `expected` against `actual`: common lines start with a
+/// space, missing ones with `-` and extra ones with `+`.
+pub fn diff(expected: &[u8], actual: &[u8]) -> String {
+	let expected: Vec<&[u8]> = expected.split_inclusive(|&byte| byte == b'\n').collect();
+	let actual: Vec<&[u8]> = actual.split_inclusive(|&byte| byte == b'\n').collect();
+	let prefix = expected.iter().zip(&actual).take_while(|(expected, actual)| expected == actual).count();
+	let suffix = expected[prefix..].iter().rev().zip(actual[prefix..].iter().rev())
+		.take_while(|(expected, actual)| expected == actual)
+		.count();
+	let (expected_middle, actual_middle) = (&expected[prefix..expected.len() - suffix], &actual[prefix..actual.len() - suffix]);
+
+	let mut diff = String::from("--- expected\n+++ actual\n");
+	let mut push = |marker: char, line: &[u8]| {
+		diff.push(marker);
+		diff.push_str(&String::from_utf8_lossy(line.strip_suffix(b"\n").unwrap_or(line)));
+		if !line.ends_with(b"\n") {
+			diff.push_str("\n\\ No newline at end of file");
+		}
+		diff.push('\n');
+	};
+	for line in &expected[..prefix] {
+		push(' ', line);
+	}
+	let cells = (expected_middle.len() + 1).saturating_mul(actual_middle.len() + 1);
+	if cells <= MAX_DIFF_CELLS {
+		aligned_diff(expected_middle, actual_middle, &mut push);
+	} else {
+		expected_middle.iter().for_each(|line| push('-', line));
+		actual_middle.iter().for_each(|line| push('+', line));
+	}
+	for line in &expected[expected.len() - suffix..] {
+		push(' ', line);
+	}
+	diff
+}
+
+/// The lines of `expected` and `actual` lined up on their longest common subsequence, handed to
+/// `push` with their marker.
+fn aligned_diff(expected: &[&[u8]], actual: &[&[u8]], push: &mut impl FnMut(char, &[u8])) {
+	// Longest common subsequence of the lines from each position on
+	let mut common = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+	for i in (0..expected.len()).rev() {
+		for j in (0..actual.len()).rev() {
+			common[i][j] = if expected[i] == actual[j] {
+				common[i + 1][j + 1] + 1
+			} else {
+				common[i + 1][j].max(common[i][j + 1])
+			};
+		}
+	}
+
+	let (mut i, mut j) = (0, 0);
+	while i < expected.len() || j < actual.len() {
+		if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+			push(' ', expected[i]);
+			(i, j) = (i + 1, j + 1);
+		} else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1]) {
+			push('-', expected[i]);
+			i += 1;
+		} else {
+			push('+', actual[j]);
+			j += 1;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::interpreter::report::Limit;
+	use std::path::PathBuf;
+
+	/// A fresh directory holding `files`, names along with their contents.
+	fn suite(name: &str, files: &[(&str, &str)]) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("brainfuck-corpus-{name}-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		for (file, contents) in files {
+			fs::write(dir.join(file), contents).unwrap();
+		}
+		dir
+	}
+
+	fn case(program: &str, input: &str, expected: &str) -> TestCase {
+		TestCase {
+			name: "case".to_string(),
+			program: program.to_string(),
+			input: input.as_bytes().to_vec(),
+			expected: expected.as_bytes().to_vec(),
+		}
+	}
+
+	#[test]
+	fn discover_reads_sorted_cases() {
+		let dir = suite("sorted", &[
+			("b.bf", ",."), ("b.in", "x"), ("b.out", "x"),
+			("a.bf", "+."), ("a.out", "\u{1}"),
+		]);
+		let cases = discover(&dir, None).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+		assert_eq!(cases, vec![
+			TestCase { name: "a".to_string(), program: "+.".to_string(), input: Vec::new(), expected: vec![1] },
+			TestCase { name: "b".to_string(), program: ",.".to_string(), input: b"x".to_vec(), expected: b"x".to_vec() },
+		]);
+	}
+
+	#[test]
+	fn discover_skips_programs_without_output() {
+		let dir = suite("no-out", &[("a.bf", "+"), ("a.in", "x"), ("b.bf", "+"), ("b.out", ""), ("notes.txt", "")]);
+		let cases = discover(&dir, None).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+		assert_eq!(cases.iter().map(|case| case.name.as_str()).collect::<Vec<_>>(), ["b"]);
+	}
+
+	#[test]
+	fn discover_filters_by_name() {
+		let dir = suite("filter", &[("hello.bf", ""), ("hello.out", ""), ("cat.bf", ""), ("cat.out", "")]);
+		let cases = discover(&dir, Some("ell")).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+		assert_eq!(cases.iter().map(|case| case.name.as_str()).collect::<Vec<_>>(), ["hello"]);
+	}
+
+	#[test]
+	fn discover_fails_on_missing_dir() {
+		let dir = std::env::temp_dir().join(format!("brainfuck-corpus-missing-{}", std::process::id()));
+		assert!(discover(&dir, None).is_err());
+	}
+
+	#[test]
+	fn run_case_passes_matching_output() {
+		assert!(run_case(&case(",[.,]", "hi", "hi"), Limits::default(), EofPolicy::Zero).passed());
+	}
+
+	#[test]
+	fn run_case_reports_mismatch() {
+		match run_case(&case(",[.,]", "hi", "ho"), Limits::default(), EofPolicy::Zero) {
+			TestResult::Mismatch { output } => assert_eq!(output, b"hi"),
+			result => panic!("expected a mismatch, got {result:?}"),
+		}
+	}
+
+	#[test]
+	fn run_case_stops_hanging_program() {
+		let limits = Limits { fuel: 1000, ..Limits::default() };
+		match run_case(&case("+[]", "", ""), limits, EofPolicy::Zero) {
+			TestResult::Failed(Outcome::LimitExceeded { which: Limit::Fuel, .. }) => {}
+			result => panic!("expected to run out of fuel, got {result:?}"),
+		}
+	}
+
+	#[test]
+	fn diff_marks_changed_lines() {
+		assert_eq!(diff(b"a\nb\nc\n", b"a\nx\nc\n"), "--- expected\n+++ actual\n a\n-b\n+x\n c\n");
+	}
+
+	#[test]
+	fn diff_marks_missing_newline() {
+		assert_eq!(diff(b"a\n", b"a"), "--- expected\n+++ actual\n-a\n+a\n\\ No newline at end of file\n");
+	}
+
+	#[test]
+	fn diff_of_large_outputs_stays_unaligned() {
+		let expected: String = (0..2000).map(|line| format!("{line}\n")).collect();
+		let actual: String = (0..2000).map(|line| format!("{}\n", line * 2)).collect();
+		let diff = diff(expected.as_bytes(), actual.as_bytes());
+		// Only the first line is common, the rest comes out whole rather than lined up
+		assert!(diff.starts_with("--- expected\n+++ actual\n 0\n-1\n-2\n"));
+		assert!(diff.ends_with("+3996\n+3998\n"));
+	}
+}
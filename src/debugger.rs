@@ -0,0 +1,834 @@
+//! Interactive debugger driving an [`Interpreter`] one step at a time from text commands.
+
+use std::fmt::{Display, Formatter};
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+use crate::interpreter::coredump::{self, CoreDump};
+use crate::interpreter::error::InterpreterError;
+use crate::interpreter::history::RevertedStep;
+use crate::interpreter::watch::{WatchHit, Watchpoint};
+use crate::interpreter::{Interpreter, InterpreterState};
+use crate::ir::Span;
+use crate::InterpreterSymbol;
+
+use self::condition::{Condition, ConditionError};
+
+pub mod condition;
+
+pub const PROMPT: &str = "(bfdb) ";
+/// Steps kept for reverse execution unless configured otherwise.
+pub const DEFAULT_JOURNAL_WINDOW: usize = 100_000;
+
+const HELP: &str = "\
+break <index>|<line>:<col>|+<n>  stop before the instruction there, or n instructions ahead
+  [if <condition>]               only when the condition holds, e.g. `if cell 3 > 100 && ptr == 3`,
+                                 over `cell <n>`, `ptr`, `ip`, `depth`, numbers, comparisons, `!`,
+                                 `&&`, `||` and parentheses
+watch <cell> [== <value>]        stop when the cell is written, or when it becomes the value
+rwatch <cell>                    stop when `.` or `[` reads the cell
+info breaks                      list the breakpoints and watchpoints
+delete <id>                      remove a breakpoint or watchpoint
+disable <id>, enable <id>        turn one off and back on
+run, restart                     start the program, from the beginning if it was running
+step [n]                         execute n instructions, 1 by default
+continue                         resume until the next breakpoint or the end
+reverse-step [n]                 undo n instructions, 1 by default
+reverse-continue                 go back to the previous breakpoint or watchpoint hit
+print ptr|ip|cell <n>            show the data pointer, instruction pointer or a cell
+tape <start>..<end>              hex dump of the cells in the range
+stack                            show the loops being run
+recent                           show the last instructions run before a core dump
+quit                             leave the debugger";
+
+/// Where a breakpoint goes: a character index into the source, a 1-based line and column, or a
+/// number of instructions after the current one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+	Index(usize),
+	LineColumn { line: usize, column: usize },
+	Ahead(usize),
+}
+
+impl Display for Location {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Location::Index(index) => write!(f, "instruction {index}"),
+			Location::LineColumn { line, column } => write!(f, "line {line}, col {column}"),
+			Location::Ahead(count) => write!(f, "{count} instructions ahead"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintTarget {
+	DataPtr,
+	InstructionPtr,
+	Cell(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugCommand {
+	Break(Location, Option<Condition>),
+	Watch(Watchpoint),
+	InfoBreaks,
+	Delete(usize),
+	Disable(usize),
+	Enable(usize),
+	Run,
+	Step(usize),
+	Continue,
+	ReverseStep(usize),
+	ReverseContinue,
+	Print(PrintTarget),
+	Tape(Range<usize>),
+	Stack,
+	Recent,
+	Help,
+	Quit,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandError {
+	Unknown(String),
+	MissingArgument(&'static str),
+	InvalidArgument { command: &'static str, argument: String },
+	InvalidCondition(ConditionError),
+}
+
+impl Display for CommandError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			CommandError::Unknown(command) => write!(f, "Unknown command `{command}`, try `help`"),
+			CommandError::MissingArgument(command) => write!(f, "`{command}` needs an argument, try `help`"),
+			CommandError::InvalidArgument { command, argument } => {
+				write!(f, "Invalid argument `{argument}` for `{command}`")
+			}
+			CommandError::InvalidCondition(err) => write!(f, "Invalid condition: {err}"),
+		}
+	}
+}
+
+impl DebugCommand {
+	pub fn parse(line: &str) -> Result<Self, CommandError> {
+		let mut words = line.split_whitespace();
+		let command = words.next().unwrap_or_default();
+		let argument = words.next();
+
+		match command {
+			"break" | "b" => {
+				let argument = argument.ok_or(CommandError::MissingArgument("break"))?;
+				let location = parse_location(argument).ok_or_else(|| invalid_argument("break", argument))?;
+				let condition = match words.next() {
+					None => None,
+					Some("if") => {
+						let condition = words.collect::<Vec<_>>().join(" ");
+						Some(Condition::parse(&condition).map_err(CommandError::InvalidCondition)?)
+					}
+					Some(other) => return Err(invalid_argument("break", other)),
+				};
+				Ok(DebugCommand::Break(location, condition))
+			}
+			"watch" | "w" => {
+				let address = argument.ok_or(CommandError::MissingArgument("watch"))?;
+				let address = address.parse().map_err(|_| invalid_argument("watch", address))?;
+				match (words.next(), words.next()) {
+					(None, _) => Ok(DebugCommand::Watch(Watchpoint::write(address))),
+					(Some("=="), Some(value)) => {
+						let value = value.parse().map_err(|_| invalid_argument("watch", value))?;
+						Ok(DebugCommand::Watch(Watchpoint::becomes(address, value)))
+					}
+					(Some("=="), None) => Err(CommandError::MissingArgument("watch ==")),
+					(Some(other), _) => Err(invalid_argument("watch", other)),
+				}
+			}
+			"rwatch" => {
+				let address = argument.ok_or(CommandError::MissingArgument("rwatch"))?;
+				let address = address.parse().map_err(|_| invalid_argument("rwatch", address))?;
+				Ok(DebugCommand::Watch(Watchpoint::read(address)))
+			}
+			"info" | "i" => match argument {
+				Some("breaks" | "breakpoints" | "b") => Ok(DebugCommand::InfoBreaks),
+				Some(other) => Err(invalid_argument("info", other)),
+				None => Err(CommandError::MissingArgument("info")),
+			},
+			"delete" | "d" => parse_id("delete", argument).map(DebugCommand::Delete),
+			"disable" => parse_id("disable", argument).map(DebugCommand::Disable),
+			"enable" => parse_id("enable", argument).map(DebugCommand::Enable),
+			"run" | "r" | "restart" => Ok(DebugCommand::Run),
+			"step" | "s" => match argument {
+				None => Ok(DebugCommand::Step(1)),
+				Some(count) => count.parse().map(DebugCommand::Step).map_err(|_| invalid_argument("step", count)),
+			},
+			"continue" | "c" => Ok(DebugCommand::Continue),
+			"reverse-step" | "rs" => match argument {
+				None => Ok(DebugCommand::ReverseStep(1)),
+				Some(count) => count.parse()
+					.map(DebugCommand::ReverseStep)
+					.map_err(|_| invalid_argument("reverse-step", count)),
+			},
+			"reverse-continue" | "rc" => Ok(DebugCommand::ReverseContinue),
+			"print" | "p" => {
+				let target = match argument.ok_or(CommandError::MissingArgument("print"))? {
+					"ptr" => PrintTarget::DataPtr,
+					"ip" => PrintTarget::InstructionPtr,
+					"cell" => {
+						let address = words.next().ok_or(CommandError::MissingArgument("print cell"))?;
+						PrintTarget::Cell(address.parse().map_err(|_| invalid_argument("print cell", address))?)
+					}
+					other => return Err(invalid_argument("print", other)),
+				};
+				Ok(DebugCommand::Print(target))
+			}
+			"tape" | "t" => {
+				let argument = argument.ok_or(CommandError::MissingArgument("tape"))?;
+				parse_range(argument)
+					.map(DebugCommand::Tape)
+					.ok_or_else(|| invalid_argument("tape", argument))
+			}
+			"stack" => Ok(DebugCommand::Stack),
+			"recent" => Ok(DebugCommand::Recent),
+			"help" | "h" => Ok(DebugCommand::Help),
+			"quit" | "q" => Ok(DebugCommand::Quit),
+			other => Err(CommandError::Unknown(other.to_string())),
+		}
+	}
+}
+
+fn invalid_argument(command: &'static str, argument: &str) -> CommandError {
+	CommandError::InvalidArgument { command, argument: argument.to_string() }
+}
+
+fn parse_id(command: &'static str, argument: Option<&str>) -> Result<usize, CommandError> {
+	let argument = argument.ok_or(CommandError::MissingArgument(command))?;
+	argument.parse().map_err(|_| invalid_argument(command, argument))
+}
+
+fn parse_location(argument: &str) -> Option<Location> {
+	if let Some(count) = argument.strip_prefix('+') {
+		return Some(Location::Ahead(count.parse().ok()?));
+	}
+	match argument.split_once(':') {
+		Some((line, column)) => Some(Location::LineColumn { line: line.parse().ok()?, column: column.parse().ok()? }),
+		None => Some(Location::Index(argument.parse().ok()?)),
+	}
+}
+
+/// Parses a `start..end` cell range.
+pub fn parse_range(argument: &str) -> Option<Range<usize>> {
+	let (start, end) = argument.split_once("..")?;
+	Some(start.parse().ok()?..end.parse().ok()?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpointKind {
+	/// Stops before the instruction at this instruction pointer.
+	Instruction(usize),
+	Watch(Watchpoint),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+	pub id: usize,
+	pub kind: BreakpointKind,
+	pub enabled: bool,
+	/// Checked against the interpreter whenever the breakpoint is reached, which only stops when it
+	/// holds.
+	pub condition: Option<Condition>,
+}
+
+impl Breakpoint {
+	/// Whether the breakpoint stops before the instruction at `ip`, given the interpreter's state.
+	fn stops_at(&self, ip: usize, interpreter: &Interpreter) -> bool {
+		self.enabled
+			&& self.kind == BreakpointKind::Instruction(ip)
+			&& self.condition.as_ref().is_none_or(|condition| condition.holds(interpreter))
+	}
+}
+
+/// Why execution handed control back to the debugger.
+enum Stop {
+	Breakpoint(Breakpoint),
+	Watch(WatchHit),
+	Stepped,
+	Halted,
+	Failed(InterpreterError),
+	/// Stepping back ran out of recorded steps.
+	HistoryStart,
+}
+
+pub struct Debugger {
+	characters: Vec<char>,
+	spans: Vec<Span>,
+	start: Box<dyn Fn() -> Interpreter>,
+	interpreter: Interpreter,
+	breakpoints: Vec<Breakpoint>,
+	next_breakpoint_id: usize,
+	journal_window: usize,
+	started: bool,
+	finished: bool,
+	/// Set when inspecting a core dump, which can't be run.
+	core: Option<CoreDump>,
+}
+
+impl Debugger {
+	/// Debugs `characters`, running them on interpreters made by `start`, which is called again on
+	/// every restart so it has to provide the program input afresh.
+	pub fn new(characters: Vec<char>, start: impl Fn() -> Interpreter + 'static) -> Self {
+		Debugger {
+			spans: Span::table(&characters),
+			characters,
+			interpreter: start().with_history(DEFAULT_JOURNAL_WINDOW),
+			start: Box::new(start),
+			breakpoints: Vec::new(),
+			next_breakpoint_id: 1,
+			journal_window: DEFAULT_JOURNAL_WINDOW,
+			started: false,
+			finished: false,
+			core: None,
+		}
+	}
+
+	/// Inspects the machine frozen in `core`, taken from a run of `characters`. Commands that
+	/// would execute anything are refused.
+	pub fn from_core(characters: Vec<char>, core: CoreDump) -> Self {
+		let frozen = core.clone();
+		let mut debugger = Debugger::new(characters, move || frozen.restore(io::sink()));
+		debugger.started = true;
+		debugger.core = Some(core);
+		debugger
+	}
+
+	/// How many of the most recent steps can be undone by the reverse commands.
+	pub fn with_journal_window(mut self, steps: usize) -> Self {
+		self.journal_window = steps;
+		self.interpreter = self.fresh_interpreter();
+		self.sync_watchpoints();
+		self
+	}
+
+	fn fresh_interpreter(&self) -> Interpreter {
+		(self.start)().with_history(self.journal_window)
+	}
+
+	pub fn interpreter(&self) -> &Interpreter {
+		&self.interpreter
+	}
+
+	pub fn breakpoints(&self) -> &[Breakpoint] {
+		&self.breakpoints
+	}
+
+	/// Reads commands line by line until `quit` or the end of `commands`, writing every prompt and
+	/// reply to `transcript`.
+	pub fn repl(&mut self, mut commands: impl BufRead, mut transcript: impl Write) -> io::Result<()> {
+		if self.core.is_some() {
+			self.report_core(&mut transcript)?;
+		}
+		let mut line = String::new();
+		loop {
+			write!(transcript, "{PROMPT}")?;
+			transcript.flush()?;
+			line.clear();
+			if commands.read_line(&mut line)? == 0 {
+				writeln!(transcript)?;
+				return Ok(());
+			}
+			if line.trim().is_empty() {
+				continue;
+			}
+
+			match DebugCommand::parse(line.trim()) {
+				Ok(DebugCommand::Quit) => return Ok(()),
+				Ok(command) => self.execute(&command, &mut transcript)?,
+				Err(err) => writeln!(transcript, "{err}")?,
+			}
+		}
+	}
+
+	pub fn execute(&mut self, command: &DebugCommand, transcript: &mut impl Write) -> io::Result<()> {
+		let executes = matches!(
+			command,
+			DebugCommand::Run
+				| DebugCommand::Step(_)
+				| DebugCommand::Continue
+				| DebugCommand::ReverseStep(_)
+				| DebugCommand::ReverseContinue
+		);
+		if executes && self.core.is_some() {
+			return writeln!(transcript, "A core dump is read-only, it can only be inspected");
+		}
+
+		match command {
+			DebugCommand::Break(location, condition) => self.set_breakpoint(*location, condition.clone(), transcript),
+			DebugCommand::Watch(watchpoint) => {
+				let id = self.add_breakpoint(BreakpointKind::Watch(*watchpoint), None);
+				writeln!(transcript, "Watchpoint {id}: {watchpoint}")
+			}
+			DebugCommand::InfoBreaks => self.list_breakpoints(transcript),
+			DebugCommand::Delete(id) => {
+				let len = self.breakpoints.len();
+				self.breakpoints.retain(|breakpoint| breakpoint.id != *id);
+				self.sync_watchpoints();
+				if self.breakpoints.len() == len {
+					writeln!(transcript, "No breakpoint {id}")
+				} else {
+					writeln!(transcript, "Deleted breakpoint {id}")
+				}
+			}
+			DebugCommand::Disable(id) => self.set_enabled(*id, false, transcript),
+			DebugCommand::Enable(id) => self.set_enabled(*id, true, transcript),
+			DebugCommand::Run => {
+				if self.started {
+					self.interpreter = self.fresh_interpreter();
+					self.sync_watchpoints();
+					self.finished = false;
+					writeln!(transcript, "Restarting the program")?;
+				}
+				self.started = true;
+				self.resume(None, false, transcript)
+			}
+			DebugCommand::Continue if !self.started => writeln!(transcript, "The program isn't running, use `run`"),
+			DebugCommand::Continue => self.resume(None, true, transcript),
+			DebugCommand::Step(count) => {
+				self.started = true;
+				self.resume(Some(*count), true, transcript)
+			}
+			DebugCommand::ReverseStep(count) => self.reverse(Some(*count), transcript),
+			DebugCommand::ReverseContinue => self.reverse(None, transcript),
+			DebugCommand::Print(PrintTarget::DataPtr) => {
+				writeln!(transcript, "ptr = {}", self.interpreter.get_data_ptr())
+			}
+			DebugCommand::Print(PrintTarget::InstructionPtr) => {
+				writeln!(transcript, "ip = {}", self.interpreter.get_instruction_ptr())
+			}
+			DebugCommand::Print(PrintTarget::Cell(address)) => match self.interpreter.cell(*address) {
+				Some(value) => writeln!(transcript, "cell {address} = {value}"),
+				None => writeln!(transcript, "Cell {address} is past the end of the tape"),
+			},
+			DebugCommand::Tape(range) => self.interpreter.dump_memory(range.clone(), transcript),
+			DebugCommand::Stack if self.interpreter.loop_stack().is_empty() => {
+				writeln!(transcript, "Not inside any loop")
+			}
+			DebugCommand::Stack => writeln!(transcript, "Inside loops at {:?}", self.interpreter.loop_stack()),
+			DebugCommand::Recent => self.list_recent(transcript),
+			DebugCommand::Help => writeln!(transcript, "{HELP}"),
+			DebugCommand::Quit => Ok(()),
+		}
+	}
+
+	fn report_core(&self, transcript: &mut impl Write) -> io::Result<()> {
+		let Some(core) = &self.core else {
+			return Ok(());
+		};
+		let ip = self.interpreter.get_instruction_ptr();
+		writeln!(transcript, "Core dump of a run that failed with {} at {}", core.error, self.describe(ip))?;
+		write!(transcript, "{}", self.source_excerpt(ip))?;
+		if core.program_hash != coredump::program_hash(&self.characters) {
+			writeln!(transcript, "Warning: the dump was taken from a different program, positions may not match")?;
+		}
+		Ok(())
+	}
+
+	fn list_recent(&self, transcript: &mut impl Write) -> io::Result<()> {
+		let Some(core) = &self.core else {
+			return writeln!(transcript, "No core dump loaded");
+		};
+		if core.recent.is_empty() {
+			return writeln!(transcript, "No instructions were recorded");
+		}
+		for step in &core.recent {
+			writeln!(
+				transcript,
+				"`{}` at {}: ptr = {}, cell = {}",
+				step.instruction.to_char(),
+				self.describe(step.instruction_ptr),
+				step.data_ptr,
+				step.cell
+			)?;
+		}
+		Ok(())
+	}
+
+	fn set_breakpoint(
+		&mut self,
+		location: Location,
+		condition: Option<Condition>,
+		transcript: &mut impl Write,
+	) -> io::Result<()> {
+		let Some(index) = self.resolve(location) else {
+			return writeln!(transcript, "No instruction at {location}");
+		};
+
+		let suffix = condition.as_ref().map(|condition| format!(" if {condition}")).unwrap_or_default();
+		let id = self.add_breakpoint(BreakpointKind::Instruction(index), condition);
+		writeln!(transcript, "Breakpoint {id} at {}{suffix}", self.describe(index))
+	}
+
+	fn add_breakpoint(&mut self, kind: BreakpointKind, condition: Option<Condition>) -> usize {
+		let id = self.next_breakpoint_id;
+		self.next_breakpoint_id += 1;
+		self.breakpoints.push(Breakpoint { id, kind, enabled: true, condition });
+		self.sync_watchpoints();
+		id
+	}
+
+	/// Hands the enabled watchpoints to the interpreter, which checks them on every cell access.
+	fn sync_watchpoints(&mut self) {
+		self.interpreter.clear_watchpoints();
+		for breakpoint in &self.breakpoints {
+			if let (BreakpointKind::Watch(watchpoint), true) = (breakpoint.kind, breakpoint.enabled) {
+				self.interpreter.add_watchpoint(watchpoint);
+			}
+		}
+	}
+
+	/// The instruction pointer `location` refers to. Line and column positions that aren't an
+	/// instruction move forward to the next instruction on the same line.
+	fn resolve(&self, location: Location) -> Option<usize> {
+		match location {
+			Location::Index(index) => Some(index).filter(|&index| self.is_instruction(index)),
+			Location::LineColumn { line, column } => self.spans.iter()
+				.skip_while(|span| span.line < line || (span.line == line && span.column < column))
+				.take_while(|span| span.line == line)
+				.map(|span| span.offset)
+				.find(|&index| self.is_instruction(index)),
+			Location::Ahead(count) => (self.interpreter.get_instruction_ptr() + 1..self.characters.len())
+				.filter(|&index| self.is_instruction(index))
+				.nth(count.checked_sub(1)?),
+		}
+	}
+
+	fn list_breakpoints(&self, transcript: &mut impl Write) -> io::Result<()> {
+		if self.breakpoints.is_empty() {
+			return writeln!(transcript, "No breakpoints");
+		}
+		for breakpoint in &self.breakpoints {
+			let status = if breakpoint.enabled { "enabled" } else { "disabled" };
+			let mut what = match breakpoint.kind {
+				BreakpointKind::Instruction(index) => self.describe(index),
+				BreakpointKind::Watch(watchpoint) => watchpoint.to_string(),
+			};
+			if let Some(condition) = &breakpoint.condition {
+				what.push_str(&format!(" if {condition}"));
+			}
+			writeln!(transcript, "{:>3} {status:<8} {what}", breakpoint.id)?;
+		}
+		Ok(())
+	}
+
+	fn set_enabled(&mut self, id: usize, enabled: bool, transcript: &mut impl Write) -> io::Result<()> {
+		match self.breakpoints.iter_mut().find(|breakpoint| breakpoint.id == id) {
+			Some(breakpoint) => {
+				breakpoint.enabled = enabled;
+				self.sync_watchpoints();
+				let action = if enabled { "Enabled" } else { "Disabled" };
+				writeln!(transcript, "{action} breakpoint {id}")
+			}
+			None => writeln!(transcript, "No breakpoint {id}"),
+		}
+	}
+
+	fn is_instruction(&self, index: usize) -> bool {
+		matches!(InterpreterSymbol::from_char(self.characters.get(index)), InterpreterSymbol::Instruction(_))
+	}
+
+	fn describe(&self, index: usize) -> String {
+		match self.spans.get(index) {
+			Some(span) => format!("instruction {index} (line {}, col {})", span.line, span.column),
+			None => format!("instruction {index}"),
+		}
+	}
+
+	/// The source line holding `index`, with a caret under it.
+	fn source_excerpt(&self, index: usize) -> String {
+		let Some(span) = self.spans.get(index) else {
+			return String::new();
+		};
+		let line: String = self.characters[index + 1 - span.column..].iter()
+			.take_while(|&&c| c != '\n')
+			.collect();
+		let gutter = span.line.to_string();
+		format!(
+			" {gutter} | {line}\n {} | {}^\n",
+			" ".repeat(gutter.len()),
+			" ".repeat(span.column - 1)
+		)
+	}
+
+	/// Steps until `step_limit` instructions ran, a breakpoint is reached or the program ends.
+	/// A breakpoint on the current instruction is stepped over when `resuming`.
+	fn resume(&mut self, step_limit: Option<usize>, resuming: bool, transcript: &mut impl Write) -> io::Result<()> {
+		if self.finished {
+			return writeln!(transcript, "The program has finished, use `run` to restart it");
+		}
+
+		let stop = self.run_until_stop(step_limit, resuming);
+		self.interpreter.flush_output();
+		self.report(stop, transcript)
+	}
+
+	/// Steps back until `step_limit` instructions were undone, or without a limit until landing
+	/// where a forward run would have stopped for a breakpoint or watchpoint.
+	fn reverse(&mut self, step_limit: Option<usize>, transcript: &mut impl Write) -> io::Result<()> {
+		if !self.started {
+			return writeln!(transcript, "The program isn't running, use `run`");
+		}
+
+		let mut undone = 0usize;
+		let stop = loop {
+			if step_limit == Some(undone) {
+				break Stop::Stepped;
+			}
+			let Some(reverted) = self.interpreter.step_back() else {
+				break Stop::HistoryStart;
+			};
+			if let Some(byte) = reverted.output {
+				writeln!(transcript, "Would un-output {:?}", byte as char)?;
+			}
+
+			let ip = self.interpreter.get_instruction_ptr();
+			if self.interpreter.get_state() != InterpreterState::Running || !self.is_instruction(ip) {
+				continue;
+			}
+			undone += 1;
+			if step_limit.is_none() {
+				if let Some(stop) = self.reverse_hit(&reverted) {
+					break stop;
+				}
+			}
+		};
+
+		self.finished = false;
+		self.report(stop, transcript)
+	}
+
+	/// The breakpoint or watchpoint that would have stopped a forward run at the step just undone.
+	fn reverse_hit(&self, reverted: &RevertedStep) -> Option<Stop> {
+		let ip = reverted.instruction_ptr;
+		let reads = matches!(self.characters.get(ip), Some('.' | '['));
+		let data_ptr = self.interpreter.get_data_ptr();
+
+		for breakpoint in self.breakpoints.iter().filter(|breakpoint| breakpoint.enabled) {
+			let watchpoint = match breakpoint.kind {
+				BreakpointKind::Instruction(_) if breakpoint.stops_at(ip, &self.interpreter) => {
+					return Some(Stop::Breakpoint(breakpoint.clone()));
+				}
+				BreakpointKind::Instruction(_) => continue,
+				BreakpointKind::Watch(watchpoint) => watchpoint,
+			};
+
+			let values = match reverted.cell {
+				Some((address, old_value, new_value)) if watchpoint.fires_on_write(address, old_value, new_value) => {
+					Some((old_value, new_value))
+				}
+				_ if reads && watchpoint.fires_on_read(data_ptr) => {
+					let value = self.interpreter.cell(data_ptr).unwrap_or_default();
+					Some((value, value))
+				}
+				_ => None,
+			};
+			if let Some((old_value, new_value)) = values {
+				return Some(Stop::Watch(WatchHit { watchpoint, instruction_ptr: ip, old_value, new_value }));
+			}
+		}
+		None
+	}
+
+	fn report(&mut self, stop: Stop, transcript: &mut impl Write) -> io::Result<()> {
+		let ip = self.interpreter.get_instruction_ptr();
+		match stop {
+			Stop::Breakpoint(breakpoint) => {
+				writeln!(transcript, "Breakpoint {} hit at {}", breakpoint.id, self.describe(ip))?;
+				write!(transcript, "{}", self.source_excerpt(ip))
+			}
+			Stop::Watch(hit) => {
+				let id = self.breakpoints.iter()
+					.find(|breakpoint| breakpoint.kind == BreakpointKind::Watch(hit.watchpoint))
+					.map_or(0, |breakpoint| breakpoint.id);
+				writeln!(
+					transcript,
+					"Watchpoint {id} ({}) hit at {}: {} -> {}",
+					hit.watchpoint,
+					self.describe(hit.instruction_ptr),
+					hit.old_value,
+					hit.new_value
+				)?;
+				write!(transcript, "{}", self.source_excerpt(hit.instruction_ptr))
+			}
+			Stop::Stepped if ip < self.characters.len() => {
+				writeln!(transcript, "Stopped before `{}` at {}", self.characters[ip], self.describe(ip))?;
+				write!(transcript, "{}", self.source_excerpt(ip))
+			}
+			Stop::Stepped => writeln!(transcript, "Stopped at the end of the program"),
+			Stop::Halted => {
+				self.finished = true;
+				writeln!(transcript, "Program finished")
+			}
+			Stop::Failed(err) => {
+				self.finished = true;
+				writeln!(transcript, "Program failed at {}: {:?}", self.describe(ip), err.reason)
+			}
+			Stop::HistoryStart => {
+				writeln!(transcript, "Reached the oldest recorded step, at {}", self.describe(ip))?;
+				write!(transcript, "{}", self.source_excerpt(ip))
+			}
+		}
+	}
+
+	fn run_until_stop(&mut self, step_limit: Option<usize>, resuming: bool) -> Stop {
+		let mut executed = 0usize;
+		self.interpreter.take_watch_hit();
+		loop {
+			if self.interpreter.is_halted() {
+				return Stop::Halted;
+			}
+
+			let ip = self.interpreter.get_instruction_ptr();
+			let executes = self.interpreter.get_state() == InterpreterState::Running && self.is_instruction(ip);
+			// Past comments, so the stop is before what runs next
+			if step_limit == Some(executed) && executes {
+				return Stop::Stepped;
+			}
+			if executes && (executed > 0 || !resuming) {
+				let hit = self.breakpoints.iter().find(|breakpoint| breakpoint.stops_at(ip, &self.interpreter));
+				if let Some(breakpoint) = hit {
+					return Stop::Breakpoint(breakpoint.clone());
+				}
+			}
+
+			if let Err(err) = self.interpreter.step(&self.characters) {
+				return Stop::Failed(err);
+			}
+			if executes {
+				executed += 1;
+			}
+			if let Some(hit) = self.interpreter.take_watch_hit() {
+				return Stop::Watch(hit);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::interpreter::io::CapturedOutput;
+
+	/// A debugger over `program`, with the output the program prints.
+	fn debug(program: &str, input: &'static [u8]) -> (Debugger, CapturedOutput) {
+		let output = CapturedOutput::new();
+		let program_output = output.clone();
+		let debugger = Debugger::new(program.chars().collect(), move || {
+			let mut interpreter = Interpreter::with_output(program_output.clone());
+			interpreter.feed_input(input);
+			interpreter
+		});
+		(debugger, output)
+	}
+
+	fn script(debugger: &mut Debugger, commands: &str) -> String {
+		let mut transcript = Vec::new();
+		debugger.repl(commands.as_bytes(), &mut transcript).unwrap();
+		String::from_utf8(transcript).unwrap()
+	}
+
+	#[test]
+	fn scripted_session_transcript() {
+		let (mut debugger, output) = debug(",+.\n>++[->+<]", b"A");
+		let transcript = script(
+			&mut debugger,
+			"break 2\nrun\nprint cell 0\nstep\nstep 3\nprint ptr\nprint ip\nstack\nstep\nstack\ncontinue\ntape 0..3\nquit\n",
+		);
+		assert_eq!(transcript, concat!(
+			"(bfdb) Breakpoint 1 at instruction 2 (line 1, col 3)\n",
+			"(bfdb) Breakpoint 1 hit at instruction 2 (line 1, col 3)\n",
+			" 1 | ,+.\n",
+			"   |   ^\n",
+			"(bfdb) cell 0 = 66\n",
+			"(bfdb) Stopped before `>` at instruction 4 (line 2, col 1)\n",
+			" 2 | >++[->+<]\n",
+			"   | ^\n",
+			"(bfdb) Stopped before `[` at instruction 7 (line 2, col 4)\n",
+			" 2 | >++[->+<]\n",
+			"   |    ^\n",
+			"(bfdb) ptr = 1\n",
+			"(bfdb) ip = 7\n",
+			"(bfdb) Not inside any loop\n",
+			"(bfdb) Stopped before `-` at instruction 8 (line 2, col 5)\n",
+			" 2 | >++[->+<]\n",
+			"   |     ^\n",
+			"(bfdb) Inside loops at [7]\n",
+			"(bfdb) Program finished\n",
+			"(bfdb) 00000000: 4200 02                                  B..\n",
+			"(bfdb) ",
+		));
+		assert_eq!(output.contents(), b"B");
+	}
+
+	#[test]
+	fn breakpoints_by_position_survive_restarts() {
+		let (mut debugger, _) = debug("set up\n+++ [\n  ->+< ]\n>.", b"");
+		let transcript = script(&mut debugger, "break 3:1\nrun\n");
+		let ip = debugger.interpreter().get_instruction_ptr();
+		assert_eq!((ip, debugger.characters[ip]), (15, '-'), "{transcript}");
+		assert!(transcript.contains("Breakpoint 1 hit at instruction 15 (line 3, col 3)\n 3 |   ->+< ]\n   |   ^\n"));
+
+		// Two instructions on, then over the first breakpoint once disabled
+		script(&mut debugger, "break +2\ndisable 1\ncontinue\n");
+		assert_eq!(debugger.interpreter().get_instruction_ptr(), 17);
+		let listed = script(&mut debugger, "info breaks\n");
+		assert!(listed.contains("  1 disabled instruction 15 (line 3, col 3)\n  2 enabled  instruction 17 (line 3, col 5)\n"));
+
+		script(&mut debugger, "delete 2\nenable 1\nrun\n");
+		assert_eq!(debugger.interpreter().get_instruction_ptr(), 15);
+		assert_eq!(debugger.breakpoints().len(), 1);
+		assert!(script(&mut debugger, "break 1:2\n").contains("No instruction at line 1, col 2"));
+	}
+
+	#[test]
+	fn watchpoints_stop_at_the_instruction_touching_the_cell() {
+		const PROGRAM: &str = "+>+>+<<+>>+<.";
+		let (mut debugger, _) = debug(PROGRAM, b"");
+		let transcript = script(&mut debugger, "watch 2\nrun\n");
+		assert!(transcript.contains("Watchpoint 1 (watch 2) hit at instruction 4 (line 1, col 5): 0 -> 1\n"), "{transcript}");
+		assert_eq!(debugger.interpreter().get_instruction_ptr(), 5);
+		assert!(script(&mut debugger, "continue\n").contains("hit at instruction 10 (line 1, col 11): 1 -> 2\n"));
+
+		let (mut debugger, _) = debug(PROGRAM, b"");
+		let transcript = script(&mut debugger, "watch 2 == 2\nrwatch 1\nrun\ncontinue\n");
+		assert!(transcript.contains("hit at instruction 10 (line 1, col 11): 1 -> 2\n"), "{transcript}");
+		assert!(transcript.contains("Watchpoint 2 (rwatch 1) hit at instruction 12 (line 1, col 13): 1 -> 1\n"), "{transcript}");
+	}
+
+	#[test]
+	fn reverse_commands_go_back_to_earlier_stops() {
+		let (mut debugger, _) = debug("+++[>+.<-]", b"");
+		script(&mut debugger, "step 8\n");
+		let forward = debugger.interpreter().snapshot();
+		let transcript = script(&mut debugger, "step 6\nreverse-step 6\n");
+		assert!(transcript.contains("Would un-output '\\u{2}'\n"), "{transcript}");
+		assert_eq!(debugger.interpreter().snapshot(), forward);
+
+		// Back to the last time the breakpoint was reached
+		let transcript = script(&mut debugger, "break 6\nreverse-continue\n");
+		assert!(transcript.contains("Breakpoint 1 hit at instruction 6 (line 1, col 7)\n"), "{transcript}");
+		assert_eq!((debugger.interpreter().get_instruction_ptr(), debugger.interpreter().cell(1)), (6, Some(1)));
+		let transcript = script(&mut debugger, "reverse-continue\n");
+		assert!(transcript.contains("Reached the oldest recorded step, at instruction 0"), "{transcript}");
+		assert_eq!(debugger.interpreter().snapshot(), debug("", b"").0.interpreter().snapshot());
+	}
+
+	#[test]
+	fn conditional_breakpoints_stop_on_the_fifth_iteration() {
+		let (mut debugger, _) = debug("++++++++[>+<-]", b"");
+		let transcript = script(&mut debugger, "break 10 if cell 1 == 4\nrun\n");
+		assert!(transcript.contains("Breakpoint 1 at instruction 10 (line 1, col 11) if cell 1 == 4\n"), "{transcript}");
+		assert!(transcript.contains("Breakpoint 1 hit at instruction 10"), "{transcript}");
+		// Four iterations done, the fifth about to add
+		assert_eq!((debugger.interpreter().cell(0), debugger.interpreter().cell(1)), (Some(4), Some(4)));
+
+		assert!(script(&mut debugger, "continue\n").contains("Program finished"));
+		assert!(script(&mut debugger, "break 10 if cell <\n").contains("Invalid condition: unexpected `<`"));
+	}
+}
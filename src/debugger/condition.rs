@@ -0,0 +1,356 @@
+//! Conditions attached to breakpoints, as in `break 88 if cell 3 > 100 && ptr == 3`.
+//!
+//! Operands are integer literals, `ptr`, `ip`, `depth` (the number of loops being run) and
+//! `cell <operand>`, the value of a cell, 0 past the end of the tape. From loosest to tightest:
+//!
+//! 1. `||`
+//! 2. `&&`
+//! 3. `==`, `!=`, `<`, `<=`, `>`, `>=`, which don't chain
+//! 4. `!` and `cell`
+//!
+//! Parentheses group. Comparisons and the boolean operators give 1 or 0, and any value but 0 is
+//! true, so `if cell 3` stops once cell 3 is non-zero.
+
+use std::fmt::{Display, Formatter};
+
+use crate::interpreter::Interpreter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+	Or,
+	And,
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+impl BinaryOp {
+	fn symbol(self) -> &'static str {
+		match self {
+			BinaryOp::Or => "||",
+			BinaryOp::And => "&&",
+			BinaryOp::Eq => "==",
+			BinaryOp::Ne => "!=",
+			BinaryOp::Lt => "<",
+			BinaryOp::Le => "<=",
+			BinaryOp::Gt => ">",
+			BinaryOp::Ge => ">=",
+		}
+	}
+
+	fn comparison(symbol: &str) -> Option<Self> {
+		match symbol {
+			"==" => Some(BinaryOp::Eq),
+			"!=" => Some(BinaryOp::Ne),
+			"<" => Some(BinaryOp::Lt),
+			"<=" => Some(BinaryOp::Le),
+			">" => Some(BinaryOp::Gt),
+			">=" => Some(BinaryOp::Ge),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+	Literal(i64),
+	DataPtr,
+	InstructionPtr,
+	Depth,
+	Cell(Box<Expr>),
+	Not(Box<Expr>),
+	Binary(Box<Expr>, BinaryOp, Box<Expr>),
+}
+
+impl Expr {
+	pub fn evaluate(&self, interpreter: &Interpreter) -> i64 {
+		match self {
+			Expr::Literal(value) => *value,
+			Expr::DataPtr => interpreter.get_data_ptr() as i64,
+			Expr::InstructionPtr => interpreter.get_instruction_ptr() as i64,
+			Expr::Depth => interpreter.loop_stack().len() as i64,
+			Expr::Cell(address) => usize::try_from(address.evaluate(interpreter)).ok()
+				.and_then(|address| interpreter.cell(address))
+				.map_or(0, i64::from),
+			Expr::Not(operand) => i64::from(operand.evaluate(interpreter) == 0),
+			Expr::Binary(left, BinaryOp::Or, right) => {
+				i64::from(left.evaluate(interpreter) != 0 || right.evaluate(interpreter) != 0)
+			}
+			Expr::Binary(left, BinaryOp::And, right) => {
+				i64::from(left.evaluate(interpreter) != 0 && right.evaluate(interpreter) != 0)
+			}
+			Expr::Binary(left, op, right) => {
+				let (left, right) = (left.evaluate(interpreter), right.evaluate(interpreter));
+				i64::from(match op {
+					BinaryOp::Eq => left == right,
+					BinaryOp::Ne => left != right,
+					BinaryOp::Lt => left < right,
+					BinaryOp::Le => left <= right,
+					BinaryOp::Gt => left > right,
+					BinaryOp::Ge => left >= right,
+					BinaryOp::Or | BinaryOp::And => unreachable!("handled above"),
+				})
+			}
+		}
+	}
+}
+
+impl Display for Expr {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Expr::Literal(value) => write!(f, "{value}"),
+			Expr::DataPtr => write!(f, "ptr"),
+			Expr::InstructionPtr => write!(f, "ip"),
+			Expr::Depth => write!(f, "depth"),
+			Expr::Cell(address) if matches!(**address, Expr::Binary(..)) => write!(f, "cell ({address})"),
+			Expr::Cell(address) => write!(f, "cell {address}"),
+			Expr::Not(operand) if matches!(**operand, Expr::Binary(..)) => write!(f, "!({operand})"),
+			Expr::Not(operand) => write!(f, "!{operand}"),
+			Expr::Binary(left, op, right) => {
+				write_operand(f, left, *op)?;
+				write!(f, " {} ", op.symbol())?;
+				write_operand(f, right, *op)
+			}
+		}
+	}
+}
+
+/// Writes `operand` of `op`, in parentheses when it binds looser or both are comparisons.
+fn write_operand(f: &mut Formatter<'_>, operand: &Expr, op: BinaryOp) -> std::fmt::Result {
+	match operand {
+		Expr::Binary(_, inner, _) if precedence(*inner) < precedence(op) || precedence(op) == COMPARISON => {
+			write!(f, "({operand})")
+		}
+		_ => write!(f, "{operand}"),
+	}
+}
+
+const COMPARISON: u8 = 2;
+
+fn precedence(op: BinaryOp) -> u8 {
+	match op {
+		BinaryOp::Or => 0,
+		BinaryOp::And => 1,
+		_ => COMPARISON,
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConditionError {
+	UnexpectedCharacter(char),
+	UnexpectedToken(String),
+	UnexpectedEnd,
+	InvalidNumber(String),
+	/// `a < b < c`, which would compare a boolean with `c`.
+	ChainedComparison,
+}
+
+impl Display for ConditionError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ConditionError::UnexpectedCharacter(character) => write!(f, "unexpected character {character:?}"),
+			ConditionError::UnexpectedToken(token) => write!(f, "unexpected `{token}`"),
+			ConditionError::UnexpectedEnd => write!(f, "the condition ends too early"),
+			ConditionError::InvalidNumber(number) => write!(f, "`{number}` is too large"),
+			ConditionError::ChainedComparison => write!(f, "comparisons can't be chained, use `&&`"),
+		}
+	}
+}
+
+/// A parsed breakpoint condition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+	expr: Expr,
+}
+
+impl Condition {
+	pub fn parse(source: &str) -> Result<Self, ConditionError> {
+		let tokens = tokenize(source)?;
+		let mut parser = Parser { tokens, position: 0 };
+		let expr = parser.or()?;
+		match parser.next() {
+			None => Ok(Condition { expr }),
+			Some(token) => Err(ConditionError::UnexpectedToken(token)),
+		}
+	}
+
+	pub fn expr(&self) -> &Expr {
+		&self.expr
+	}
+
+	pub fn holds(&self, interpreter: &Interpreter) -> bool {
+		self.expr.evaluate(interpreter) != 0
+	}
+}
+
+impl Display for Condition {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.expr)
+	}
+}
+
+const OPERATORS: [&str; 11] = ["||", "&&", "==", "!=", "<=", ">=", "<", ">", "!", "(", ")"];
+
+fn tokenize(source: &str) -> Result<Vec<String>, ConditionError> {
+	let mut tokens = Vec::new();
+	let mut rest = source.trim_start();
+	while let Some(character) = rest.chars().next() {
+		let length = if character.is_ascii_alphanumeric() {
+			rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len())
+		} else {
+			OPERATORS.iter()
+				.find(|operator| rest.starts_with(*operator))
+				.ok_or(ConditionError::UnexpectedCharacter(character))?
+				.len()
+		};
+		tokens.push(rest[..length].to_string());
+		rest = rest[length..].trim_start();
+	}
+	Ok(tokens)
+}
+
+/// Recursive descent over the tokens, one method per precedence level.
+struct Parser {
+	tokens: Vec<String>,
+	position: usize,
+}
+
+impl Parser {
+	fn peek(&self) -> Option<&str> {
+		self.tokens.get(self.position).map(String::as_str)
+	}
+
+	fn next(&mut self) -> Option<String> {
+		let token = self.tokens.get(self.position).cloned();
+		self.position += 1;
+		token
+	}
+
+	fn or(&mut self) -> Result<Expr, ConditionError> {
+		let mut left = self.and()?;
+		while self.peek() == Some("||") {
+			self.position += 1;
+			left = Expr::Binary(Box::new(left), BinaryOp::Or, Box::new(self.and()?));
+		}
+		Ok(left)
+	}
+
+	fn and(&mut self) -> Result<Expr, ConditionError> {
+		let mut left = self.comparison()?;
+		while self.peek() == Some("&&") {
+			self.position += 1;
+			left = Expr::Binary(Box::new(left), BinaryOp::And, Box::new(self.comparison()?));
+		}
+		Ok(left)
+	}
+
+	fn comparison(&mut self) -> Result<Expr, ConditionError> {
+		let left = self.unary()?;
+		let Some(op) = self.peek().and_then(BinaryOp::comparison) else {
+			return Ok(left);
+		};
+		self.position += 1;
+		let right = self.unary()?;
+		if self.peek().and_then(BinaryOp::comparison).is_some() {
+			return Err(ConditionError::ChainedComparison);
+		}
+		Ok(Expr::Binary(Box::new(left), op, Box::new(right)))
+	}
+
+	fn unary(&mut self) -> Result<Expr, ConditionError> {
+		let token = self.next().ok_or(ConditionError::UnexpectedEnd)?;
+		match token.as_str() {
+			"!" => Ok(Expr::Not(Box::new(self.unary()?))),
+			"cell" => Ok(Expr::Cell(Box::new(self.unary()?))),
+			"ptr" => Ok(Expr::DataPtr),
+			"ip" => Ok(Expr::InstructionPtr),
+			"depth" => Ok(Expr::Depth),
+			"(" => {
+				let expr = self.or()?;
+				match self.next() {
+					Some(token) if token == ")" => Ok(expr),
+					Some(token) => Err(ConditionError::UnexpectedToken(token)),
+					None => Err(ConditionError::UnexpectedEnd),
+				}
+			}
+			number if number.starts_with(|c: char| c.is_ascii_digit()) => {
+				if !number.bytes().all(|byte| byte.is_ascii_digit()) {
+					return Err(ConditionError::UnexpectedToken(token));
+				}
+				number.parse().map(Expr::Literal).map_err(|_| ConditionError::InvalidNumber(token.clone()))
+			}
+			_ => Err(ConditionError::UnexpectedToken(token)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(source: &str) -> Expr {
+		Condition::parse(source).unwrap().expr
+	}
+
+	fn binary(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+		Expr::Binary(Box::new(left), op, Box::new(right))
+	}
+
+	#[test]
+	fn operators_bind_by_precedence() {
+		assert_eq!(
+			parse("ptr == 1 || cell 2 > 3 && !ip"),
+			binary(
+				binary(Expr::DataPtr, BinaryOp::Eq, Expr::Literal(1)),
+				BinaryOp::Or,
+				binary(
+					binary(Expr::Cell(Box::new(Expr::Literal(2))), BinaryOp::Gt, Expr::Literal(3)),
+					BinaryOp::And,
+					Expr::Not(Box::new(Expr::InstructionPtr)),
+				),
+			)
+		);
+		assert_eq!(
+			parse("(depth || ptr) && cell (ptr) >= 0"),
+			binary(
+				binary(Expr::Depth, BinaryOp::Or, Expr::DataPtr),
+				BinaryOp::And,
+				binary(Expr::Cell(Box::new(Expr::DataPtr)), BinaryOp::Ge, Expr::Literal(0)),
+			)
+		);
+	}
+
+	#[test]
+	fn conditions_display_as_they_parse() {
+		for source in ["ptr == 1 || cell 2 > 3 && !ip", "(depth || ptr) && cell ptr >= 0", "!(ip < 4)", "cell (ptr == 1)"] {
+			let shown = Condition::parse(source).unwrap().to_string();
+			assert_eq!(Condition::parse(&shown).unwrap(), Condition::parse(source).unwrap(), "{shown}");
+		}
+		assert_eq!(Condition::parse("cell 3>100&&ptr==3").unwrap().to_string(), "cell 3 > 100 && ptr == 3");
+	}
+
+	#[test]
+	fn syntax_errors_are_reported() {
+		let error = |source: &str| Condition::parse(source).unwrap_err();
+		assert_eq!(error("ptr < 1 < 2"), ConditionError::ChainedComparison);
+		assert_eq!(error("cell"), ConditionError::UnexpectedEnd);
+		assert_eq!(error("(ptr == 1"), ConditionError::UnexpectedEnd);
+		assert_eq!(error("ptr $ 1"), ConditionError::UnexpectedCharacter('$'));
+		assert_eq!(error("ptr )"), ConditionError::UnexpectedToken(")".to_string()));
+		assert_eq!(error("3x"), ConditionError::UnexpectedToken("3x".to_string()));
+		assert_eq!(error("99999999999999999999"), ConditionError::InvalidNumber("99999999999999999999".to_string()));
+	}
+
+	#[test]
+	fn conditions_read_the_machine() {
+		let interpreter = Interpreter::new().with_initial_tape(&[0, 7, 200]).unwrap();
+		let holds = |source: &str| Condition::parse(source).unwrap().holds(&interpreter);
+		assert!(holds("cell 2 > 100 && ptr == 0"));
+		assert!(holds("cell cell 0 == 0 && cell 1"));
+		assert!(!holds("cell 3 || depth"));
+		assert!(holds("cell 40000 == 0"));
+	}
+}
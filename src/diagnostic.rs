@@ -0,0 +1,64 @@
+//! Compiler style error messages: the offending source line with a caret under the instruction,
+//! printed to stderr and colored when it is a terminal.
+
+use std::env;
+use std::io::{self, IsTerminal};
+
+use brainfuck::ir::Span;
+
+use crate::sources::SourceMap;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether to color diagnostics, which go to stderr: only for terminals, and never with `NO_COLOR` set.
+pub fn use_color() -> bool {
+	io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+fn paint(text: &str, color: &str, colored: bool) -> String {
+	if colored {
+		format!("{color}{text}{RESET}")
+	} else {
+		text.to_string()
+	}
+}
+
+/// `Error!` followed by `message`, and the source line holding `characters[index]` with a caret
+/// under it if the index is in the program.
+pub fn render(message: &str, characters: &[char], index: Option<usize>, colored: bool) -> String {
+	render_named(message, characters, index, None, colored)
+}
+
+/// [`render`] for a program that may be concatenated from several files, showing the line in the
+/// file it's from, named as `file:line:col`.
+pub fn render_mapped(message: &str, characters: &[char], index: Option<usize>, sources: &SourceMap, colored: bool) -> String {
+	match index.and_then(|index| sources.locate(characters, index)) {
+		Some((name, file, index)) => render_named(message, file, Some(index), Some(name), colored),
+		None => render(message, characters, index, colored),
+	}
+}
+
+fn render_named(message: &str, characters: &[char], index: Option<usize>, name: Option<&str>, colored: bool) -> String {
+	let mut rendered = format!("{} {message}\n", paint("Error!", RED, colored));
+	let Some(span) = index.and_then(|index| Span::table(characters).get(index).copied()) else {
+		return rendered;
+	};
+
+	let line: String = characters[span.offset + 1 - span.column..].iter()
+		.take_while(|&&c| c != '\n')
+		.collect();
+	let gutter = span.line.to_string();
+	let padding = " ".repeat(gutter.len());
+	let bar = paint("|", BLUE, colored);
+	let arrow = paint("-->", BLUE, colored);
+	match name {
+		Some(name) => rendered.push_str(&format!("{padding}{arrow} {name}:{}:{}\n", span.line, span.column)),
+		None => rendered.push_str(&format!("{padding}{arrow} line {}, col {}\n", span.line, span.column)),
+	}
+	rendered.push_str(&format!("{padding} {bar}\n"));
+	rendered.push_str(&format!("{} {bar} {line}\n", paint(&gutter, BLUE, colored)));
+	rendered.push_str(&format!("{padding} {bar} {}{}\n", " ".repeat(span.column - 1), paint("^", RED, colored)));
+	rendered
+}
@@ -0,0 +1,390 @@
+//! Languages that are brainfuck spelled differently, run by translating them into brainfuck.
+//!
+//! A translation keeps every offset: each instruction lands on the first character of what it was
+//! written as and the rest turns into spaces, newlines staying, so errors, coverage and profiles
+//! point into the original source.
+
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use crate::symbol::InterpreterInstruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+	#[default]
+	Brainfuck,
+	/// Ook!, every instruction a pair of `Ook.`, `Ook?` and `Ook!` tokens.
+	Ook,
+}
+
+/// A source that doesn't translate, at the offset of the offending token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialectError {
+	/// An `Ook` followed by none of `.`, `?` and `!`.
+	MissingPunctuation(usize),
+	/// The last token, left without a second one to pair with.
+	UnpairedToken(usize),
+	/// `Ook? Ook?`, the one pair that isn't an instruction.
+	UnknownPair(usize),
+}
+
+impl DialectError {
+	pub fn offset(self) -> usize {
+		match self {
+			DialectError::MissingPunctuation(offset)
+			| DialectError::UnpairedToken(offset)
+			| DialectError::UnknownPair(offset) => offset,
+		}
+	}
+}
+
+impl Display for DialectError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			DialectError::MissingPunctuation(_) => write!(f, "Ook without its punctuation"),
+			DialectError::UnpairedToken(_) => write!(f, "Ook token without a second one to pair with"),
+			DialectError::UnknownPair(_) => write!(f, "Ook? Ook? is no instruction"),
+		}
+	}
+}
+
+const OOK: [char; 3] = ['O', 'o', 'k'];
+
+impl Dialect {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"bf" | "brainfuck" => Some(Dialect::Brainfuck),
+			"ook" => Some(Dialect::Ook),
+			_ => None,
+		}
+	}
+
+	/// The dialect of a file by its extension, `.ook` being Ook! and anything else brainfuck.
+	pub fn of_file(path: &Path) -> Self {
+		match path.extension() {
+			Some(extension) if extension.eq_ignore_ascii_case("ook") => Dialect::Ook,
+			_ => Dialect::Brainfuck,
+		}
+	}
+
+	/// Offset of the first `!` of `characters` that isn't part of a token, where `--bang-input`
+	/// splits the program from its input.
+	pub fn bang_offset(self, characters: &[char]) -> Option<usize> {
+		match self {
+			Dialect::Brainfuck => characters.iter().position(|&character| character == '!'),
+			Dialect::Ook => {
+				let mut idx = 0;
+				while idx < characters.len() {
+					let starts_word = idx == 0 || !characters[idx - 1].is_alphanumeric();
+					let token = starts_word
+						&& characters[idx..].starts_with(&OOK)
+						&& matches!(characters.get(idx + OOK.len()), Some('.' | '?' | '!'));
+					if token {
+						idx += OOK.len() + 1;
+						continue;
+					}
+					if characters[idx] == '!' {
+						return Some(idx);
+					}
+					idx += 1;
+				}
+				None
+			}
+		}
+	}
+
+	/// `characters` as brainfuck, offset for offset.
+	pub fn translate(self, characters: &[char]) -> Result<Vec<char>, DialectError> {
+		match self {
+			Dialect::Brainfuck => Ok(characters.to_vec()),
+			Dialect::Ook => translate_ook(characters),
+		}
+	}
+}
+
+/// Every `Ook` token, at its offset, with its punctuation. One inside a word such as `Look.` isn't.
+fn ook_tokens(characters: &[char]) -> Result<Vec<(usize, char)>, DialectError> {
+	let mut tokens = Vec::new();
+	let mut idx = 0;
+	while idx < characters.len() {
+		let starts_word = idx == 0 || !characters[idx - 1].is_alphanumeric();
+		if !starts_word || !characters[idx..].starts_with(&OOK) {
+			idx += 1;
+			continue;
+		}
+		match characters.get(idx + OOK.len()) {
+			Some(&punctuation @ ('.' | '?' | '!')) => tokens.push((idx, punctuation)),
+			_ => return Err(DialectError::MissingPunctuation(idx)),
+		}
+		idx += OOK.len() + 1;
+	}
+	Ok(tokens)
+}
+
+fn translate_ook(characters: &[char]) -> Result<Vec<char>, DialectError> {
+	let mut translated: Vec<char> = characters.iter()
+		.map(|&character| if character == '\n' { '\n' } else { ' ' })
+		.collect();
+	let tokens = ook_tokens(characters)?;
+	for pair in tokens.chunks(2) {
+		let &[(offset, first), (_, second)] = pair else {
+			return Err(DialectError::UnpairedToken(pair[0].0));
+		};
+		let instruction = match (first, second) {
+			('.', '?') => InterpreterInstruction::MovePtrRight,
+			('?', '.') => InterpreterInstruction::MovePtrLeft,
+			('.', '.') => InterpreterInstruction::IncrementPtr,
+			('!', '!') => InterpreterInstruction::DecrementPtr,
+			('!', '.') => InterpreterInstruction::PrintPtr,
+			('.', '!') => InterpreterInstruction::ReadPtr,
+			('!', '?') => InterpreterInstruction::LoopStart,
+			('?', '!') => InterpreterInstruction::LoopEnd,
+			_ => return Err(DialectError::UnknownPair(offset)),
+		};
+		translated[offset] = instruction.to_char();
+	}
+	Ok(translated)
+}
+
+/// A brainfuck that only renames the eight instructions, each to a token of any length, as a
+/// `--symbol-map` file lists them: an instruction, whitespace and its token on every line, with
+/// blank lines and lines starting with `#` left out.
+///
+/// No token may be part of another, as with `+ inc` and `> incr` a program couldn't say `+` and
+/// then a comment starting with `r`, so wherever a token starts it's the only one there. Everything
+/// that isn't a token is a comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolMap {
+	tokens: Vec<(Vec<char>, InterpreterInstruction)>,
+}
+
+/// Why a symbol map doesn't load, on its line counted from 1 where there is one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolMapError {
+	/// The line doesn't start with one of the eight instructions.
+	UnknownInstruction { line: usize },
+	/// The instruction has no token after it.
+	EmptyToken { line: usize },
+	/// The instruction was already given a token.
+	DuplicateInstruction { line: usize, instruction: char },
+	/// The token already stands for another instruction, so it couldn't be told apart.
+	DuplicateToken { line: usize, token: String },
+	/// One token is part of the other, the two ambiguous wherever the longer one is written.
+	OverlappingTokens { line: usize, shorter: String, longer: String },
+	/// The instruction has no token at all.
+	MissingInstruction(char),
+}
+
+impl Display for SymbolMapError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SymbolMapError::UnknownInstruction { line } => {
+				write!(f, "line {line}: expected one of the eight instructions, then its token")
+			}
+			SymbolMapError::EmptyToken { line } => write!(f, "line {line}: the instruction has no token"),
+			SymbolMapError::DuplicateInstruction { line, instruction } => {
+				write!(f, "line {line}: {instruction} already has a token")
+			}
+			SymbolMapError::DuplicateToken { line, token } => {
+				write!(f, "line {line}: {token} already stands for another instruction")
+			}
+			SymbolMapError::OverlappingTokens { line, shorter, longer } => {
+				write!(f, "line {line}: {shorter} is part of {longer}, so they can't be told apart")
+			}
+			SymbolMapError::MissingInstruction(instruction) => write!(f, "no token for {instruction}"),
+		}
+	}
+}
+
+impl SymbolMap {
+	pub fn parse(text: &str) -> Result<Self, SymbolMapError> {
+		let mut tokens: Vec<(Vec<char>, InterpreterInstruction)> = Vec::new();
+		for (idx, text_line) in text.lines().enumerate() {
+			let line = idx + 1;
+			let text_line = text_line.trim();
+			if text_line.is_empty() || text_line.starts_with('#') {
+				continue;
+			}
+			let mut characters = text_line.chars();
+			let instruction = characters.next()
+				.and_then(|character| InterpreterInstruction::ALL.into_iter().find(|instruction| instruction.to_char() == character))
+				.ok_or(SymbolMapError::UnknownInstruction { line })?;
+			let rest = characters.as_str();
+			if !rest.starts_with(char::is_whitespace) && !rest.is_empty() {
+				return Err(SymbolMapError::UnknownInstruction { line });
+			}
+			let token: Vec<char> = rest.trim().chars().collect();
+			if token.is_empty() {
+				return Err(SymbolMapError::EmptyToken { line });
+			}
+			if tokens.iter().any(|(_, known)| *known == instruction) {
+				return Err(SymbolMapError::DuplicateInstruction { line, instruction: instruction.to_char() });
+			}
+			if tokens.iter().any(|(known, _)| *known == token) {
+				return Err(SymbolMapError::DuplicateToken { line, token: token.into_iter().collect() });
+			}
+			let contains = |longer: &[char], shorter: &[char]| longer.windows(shorter.len()).any(|part| part == shorter);
+			if let Some((known, _)) = tokens.iter().find(|(known, _)| contains(known, &token) || contains(&token, known)) {
+				let (shorter, longer) = if known.len() < token.len() { (known, &token) } else { (&token, known) };
+				return Err(SymbolMapError::OverlappingTokens {
+					line,
+					shorter: shorter.iter().collect(),
+					longer: longer.iter().collect(),
+				});
+			}
+			tokens.push((token, instruction));
+		}
+		if let Some(missing) = InterpreterInstruction::ALL.into_iter()
+			.find(|instruction| tokens.iter().all(|(_, known)| known != instruction))
+		{
+			return Err(SymbolMapError::MissingInstruction(missing.to_char()));
+		}
+		Ok(SymbolMap { tokens })
+	}
+
+	/// `characters` as brainfuck, offset for offset like a [`Dialect`].
+	pub fn translate(&self, characters: &[char]) -> Vec<char> {
+		let mut translated: Vec<char> = characters.iter()
+			.map(|&character| if character == '\n' { '\n' } else { ' ' })
+			.collect();
+		for (offset, instruction) in self.tokens(characters) {
+			translated[offset] = instruction.to_char();
+		}
+		translated
+	}
+
+	/// Like [`Dialect::bang_offset`], a `!` inside a token not counting.
+	pub fn bang_offset(&self, characters: &[char]) -> Option<usize> {
+		let mut idx = 0;
+		while idx < characters.len() {
+			match self.token_at(characters, idx) {
+				Some((token, _)) => idx += token.len(),
+				None if characters[idx] == '!' => return Some(idx),
+				None => idx += 1,
+			}
+		}
+		None
+	}
+
+	/// Every token of `characters`, at its offset, with what it stands for.
+	fn tokens(&self, characters: &[char]) -> Vec<(usize, InterpreterInstruction)> {
+		let mut tokens = Vec::new();
+		let mut idx = 0;
+		while idx < characters.len() {
+			match self.token_at(characters, idx) {
+				Some((token, instruction)) => {
+					tokens.push((idx, *instruction));
+					idx += token.len();
+				}
+				None => idx += 1,
+			}
+		}
+		tokens
+	}
+
+	fn token_at(&self, characters: &[char], idx: usize) -> Option<&(Vec<char>, InterpreterInstruction)> {
+		self.tokens.iter().find(|(token, _)| characters[idx..].starts_with(token))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn chars(text: &str) -> Vec<char> {
+		text.chars().collect()
+	}
+
+	fn text(characters: Vec<char>) -> String {
+		characters.into_iter().collect()
+	}
+
+	const ALPHUCK: &str = "# Alphuck\n> a\n< c\n\n+ e\n- i\n. j\n, o\n[ p\n] s\n";
+
+	#[test]
+	fn dialect_by_name_and_extension() {
+		assert_eq!(Dialect::from_name("ook"), Some(Dialect::Ook));
+		assert_eq!(Dialect::from_name("bf"), Some(Dialect::Brainfuck));
+		assert_eq!(Dialect::from_name("cobol"), None);
+		assert_eq!(Dialect::of_file(Path::new("hello.OOK")), Dialect::Ook);
+		assert_eq!(Dialect::of_file(Path::new("hello.bf")), Dialect::Brainfuck);
+		assert_eq!(Dialect::of_file(Path::new("ook")), Dialect::Brainfuck);
+	}
+
+	#[test]
+	fn ook_translates_in_place() {
+		let translated = Dialect::Ook.translate(&chars("Ook. Ook. Look.\nOok! Ook.")).unwrap();
+		assert_eq!(text(translated), "+              \n.        ");
+	}
+
+	#[test]
+	fn ook_rejects_malformed_tokens() {
+		assert_eq!(Dialect::Ook.translate(&chars("Ook. Ook")), Err(DialectError::MissingPunctuation(5)));
+		assert_eq!(Dialect::Ook.translate(&chars("Ook. Ook. Ook!")), Err(DialectError::UnpairedToken(10)));
+		assert_eq!(Dialect::Ook.translate(&chars("Ook? Ook?")), Err(DialectError::UnknownPair(0)));
+	}
+
+	#[test]
+	fn bang_input_skips_dialect_tokens() {
+		assert_eq!(Dialect::Brainfuck.bang_offset(&chars(",.!x")), Some(2));
+		assert_eq!(Dialect::Ook.bang_offset(&chars("Ook! Ook. Ook. Ook!")), None);
+		// Only an `Ook` starting a word is a token
+		assert_eq!(Dialect::Ook.bang_offset(&chars("Ook. Ook! Look!")), Some(14));
+		let map = SymbolMap::parse(include_str!("../maps/ook.map")).unwrap();
+		assert_eq!(map.bang_offset(&chars("Ook! Ook? Ook? Ook!!")), Some(19));
+	}
+
+	#[test]
+	fn ook_runs_with_bang_input() {
+		// `,.`, then its input
+		let source = chars("Ook. Ook! Ook! Ook.\n!hi");
+		let (program, input) = crate::symbol::split_input_at(&source, Dialect::Ook.bang_offset(&source));
+		assert_eq!(input, Some(b"hi".to_vec()));
+		let program = text(Dialect::Ook.translate(program).unwrap());
+		let (output, result) = crate::interpreter::Interpreter::run_with_input(&program, &input.unwrap(), None);
+		assert!(result.is_ok());
+		assert_eq!(output, b"h");
+	}
+
+	#[test]
+	fn symbol_map_translates_in_place() {
+		let map = SymbolMap::parse(ALPHUCK).unwrap();
+		assert_eq!(text(map.translate(&chars("ee xpa\ns"))), "++  [>\n]");
+	}
+
+	#[test]
+	fn symbol_map_rejects_overlapping_tokens() {
+		assert_eq!(
+			SymbolMap::parse("+ inc\n> incr\n< l\n- d\n. p\n, r\n[ b\n] e"),
+			Err(SymbolMapError::OverlappingTokens { line: 2, shorter: "inc".to_string(), longer: "incr".to_string() })
+		);
+		assert_eq!(
+			SymbolMap::parse("+ xay\n> a"),
+			Err(SymbolMapError::OverlappingTokens { line: 2, shorter: "a".to_string(), longer: "xay".to_string() })
+		);
+		// Tokens may hold spaces, as Ook!'s pairs do, and one may end how another starts
+		let map = SymbolMap::parse(include_str!("../maps/ook.map")).unwrap();
+		assert_eq!(text(map.translate(&chars("Ook. Ook. Ook. Ook?"))), "+         >        ");
+		assert!(SymbolMap::parse(include_str!("../maps/alphuck.map")).is_ok());
+	}
+
+	#[test]
+	fn symbol_map_rejects_bad_lines() {
+		let without = |instruction: char| ALPHUCK.lines()
+			.filter(|line| !line.starts_with(instruction))
+			.collect::<Vec<_>>()
+			.join("\n");
+		assert_eq!(SymbolMap::parse(&without(']')), Err(SymbolMapError::MissingInstruction(']')));
+		assert_eq!(SymbolMap::parse(&format!("{ALPHUCK}x y")), Err(SymbolMapError::UnknownInstruction { line: 11 }));
+		assert_eq!(SymbolMap::parse(&format!("{ALPHUCK}+x")), Err(SymbolMapError::UnknownInstruction { line: 11 }));
+		assert_eq!(SymbolMap::parse(&format!("{ALPHUCK}+")), Err(SymbolMapError::EmptyToken { line: 11 }));
+		assert_eq!(
+			SymbolMap::parse(&format!("{ALPHUCK}+ u")),
+			Err(SymbolMapError::DuplicateInstruction { line: 11, instruction: '+' })
+		);
+		assert_eq!(
+			SymbolMap::parse(&format!("> x\n< x\n{ALPHUCK}")),
+			Err(SymbolMapError::DuplicateToken { line: 2, token: "x".to_string() })
+		);
+	}
+}
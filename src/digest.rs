@@ -0,0 +1,106 @@
+//! Fingerprints of runs, telling whether two of them did exactly the same thing.
+
+use std::fmt::{Display, Formatter};
+
+use crate::interpreter::error::InterpreterError;
+use crate::interpreter::io::CapturedOutput;
+use crate::interpreter::Interpreter;
+
+/// 64-bit FNV-1a, fed a piece at a time.
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv1a {
+	hash: u64,
+}
+
+impl Fnv1a {
+	pub fn new() -> Self {
+		Fnv1a { hash: 0xcbf2_9ce4_8422_2325 }
+	}
+
+	pub fn write(&mut self, bytes: &[u8]) {
+		for &byte in bytes {
+			self.hash = (self.hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+		}
+	}
+
+	pub fn finish(self) -> u64 {
+		self.hash
+	}
+
+	pub fn hash(bytes: &[u8]) -> u64 {
+		let mut hasher = Self::new();
+		hasher.write(bytes);
+		hasher.finish()
+	}
+}
+
+impl Default for Fnv1a {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// What a run printed, left on the tape and how many instructions it took, hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunDigest {
+	pub output_hash: u64,
+	/// Of the written part of the tape without its trailing zeros, so cells written back to zero
+	/// don't matter.
+	pub tape_hash: u64,
+	pub steps: u64,
+}
+
+impl RunDigest {
+	pub fn new(output: &[u8], tape: &[u8], steps: u64) -> Self {
+		RunDigest { output_hash: Fnv1a::hash(output), tape_hash: Fnv1a::hash(tape), steps }
+	}
+}
+
+impl Display for RunDigest {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "output {:016x}, tape {:016x}, steps {}", self.output_hash, self.tape_hash, self.steps)
+	}
+}
+
+/// Runs `characters` on `input` alone, with nothing depending on the clock or the terminal, and
+/// digests the run. Failing runs are digested as far as they got.
+pub fn run_deterministic(characters: &[char], input: &[u8]) -> (RunDigest, Result<(), InterpreterError>) {
+	let output = CapturedOutput::new();
+	let mut interpreter = Interpreter::with_output(output.clone()).with_stats();
+	interpreter.feed_input(input);
+	let result = interpreter.run(characters);
+	let steps = interpreter.stats().map_or(0, |stats| stats.steps);
+	(RunDigest::new(&output.contents(), &interpreter.snapshot().tape, steps), result)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn digest(program: &str, input: &[u8]) -> RunDigest {
+		let (digest, result) = run_deterministic(&program.chars().collect::<Vec<_>>(), input);
+		result.unwrap();
+		digest
+	}
+
+	#[test]
+	fn same_runs_digest_the_same() {
+		const ECHO: &str = ",[.>+<,]";
+		assert_eq!(digest(ECHO, b"abc\0"), digest(ECHO, b"abc\0"));
+		assert_eq!(
+			digest(ECHO, b"abc\0").to_string(),
+			format!("output {:016x}, tape {:016x}, steps 23", Fnv1a::hash(b"abc"), Fnv1a::hash(&[0, 3])),
+		);
+
+		let other_input = digest(ECHO, b"abd\0");
+		assert_ne!(other_input, digest(ECHO, b"abc\0"));
+		// Only the output differs
+		assert_eq!((other_input.tape_hash, other_input.steps), (digest(ECHO, b"abc\0").tape_hash, 23));
+	}
+
+	#[test]
+	fn cells_written_back_to_zero_digest_as_untouched() {
+		assert_eq!(digest(">>+-<<", b"").tape_hash, digest("", b"").tape_hash);
+		assert_ne!(digest(">>+<<", b"").tape_hash, digest("", b"").tape_hash);
+	}
+}
@@ -0,0 +1,5 @@
+//! Backends turning a compiled [`Ir`](crate::ir::Ir) into other target formats.
+
+pub mod c;
+pub mod llvm;
+pub mod wasm;
@@ -0,0 +1,95 @@
+//! C source emitter.
+//!
+//! The generated program keeps the interpreter's checked semantics: leaving the tape, overflowing
+//! a cell, printing a non-ASCII byte or reading past EOF print the error reason to stderr and exit
+//! with status 1.
+
+use std::fmt::Write;
+
+use crate::interpreter::MEM_SIZE;
+use crate::ir::{Ir, Op};
+
+const PRELUDE: &str = r#"#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static unsigned char tape[TAPE_SIZE];
+
+static void fail(const char *reason) {
+	fflush(stdout);
+	fprintf(stderr, "Error! Reason: %s\n", reason);
+	exit(1);
+}
+
+int main(void) {
+	size_t ptr = 0;
+	int tmp;
+	void *tmp_ptr;
+"#;
+
+pub fn emit(ir: &Ir) -> String {
+	let mut source = format!("#define TAPE_SIZE {MEM_SIZE}\n");
+	source.push_str(PRELUDE);
+
+	let mut depth = 1usize;
+	for op in ir.ops() {
+		if let Op::JumpIfNonZero(_) = op {
+			depth -= 1;
+		}
+		let indent = "\t".repeat(depth);
+
+		// Writing into a String can't fail
+		let _ = match *op {
+			Op::Move(delta) if delta >= 0 => writeln!(
+				source,
+				"{indent}if (ptr + {delta} >= TAPE_SIZE) fail(\"PtrOutOfBounds\");\n{indent}ptr += {delta};"
+			),
+			Op::Move(delta) => writeln!(
+				source,
+				"{indent}if (ptr < {0}) fail(\"PtrOutOfBounds\");\n{indent}ptr -= {0};",
+				delta.unsigned_abs()
+			),
+			Op::Add(delta) => writeln!(
+				source,
+				"{indent}tmp = tape[ptr] + {delta};\n\
+				{indent}if (tmp < 0 || tmp > 255) fail(\"ValOutOfBounds\");\n\
+				{indent}tape[ptr] = (unsigned char) tmp;"
+			),
+			// The loop after it does the same, and C compilers turn such loops into arithmetic
+			Op::MulAdd(_) => Ok(()),
+			Op::SetZero => writeln!(source, "{indent}tape[ptr] = 0;"),
+			Op::ScanRight => writeln!(
+				source,
+				"{indent}tmp_ptr = memchr(tape + ptr, 0, TAPE_SIZE - ptr);\n\
+				{indent}if (!tmp_ptr) fail(\"PtrOutOfBounds\");\n\
+				{indent}ptr = (unsigned char *) tmp_ptr - tape;"
+			),
+			Op::ScanLeft => writeln!(
+				source,
+				"{indent}while (tape[ptr]) {{\n\
+				{indent}\tif (ptr == 0) fail(\"PtrOutOfBounds\");\n\
+				{indent}\tptr--;\n\
+				{indent}}}"
+			),
+			Op::Print => writeln!(
+				source,
+				"{indent}if (tape[ptr] > 127) fail(\"UnprintableByte\");\n{indent}putchar(tape[ptr]);"
+			),
+			Op::Read => writeln!(
+				source,
+				"{indent}tmp = getchar();\n\
+				{indent}if (tmp == EOF) fail(\"InvalidChar\");\n\
+				{indent}tape[ptr] = (unsigned char) tmp;"
+			),
+			Op::JumpIfZero(_) => writeln!(source, "{indent}while (tape[ptr]) {{"),
+			Op::JumpIfNonZero(_) => writeln!(source, "{indent}}}"),
+		};
+
+		if let Op::JumpIfZero(_) = op {
+			depth += 1;
+		}
+	}
+
+	source.push_str("\treturn 0;\n}\n");
+	source
+}
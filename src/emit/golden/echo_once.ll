@@ -0,0 +1,99 @@
+; Generated by brainfuck
+
+@tape = internal global [16 x i16] zeroinitializer
+@reason.ptr_out_of_bounds = private unnamed_addr constant [15 x i8] c"PtrOutOfBounds\00"
+@reason.val_out_of_bounds = private unnamed_addr constant [15 x i8] c"ValOutOfBounds\00"
+@reason.unprintable_byte = private unnamed_addr constant [16 x i8] c"UnprintableByte\00"
+@reason.invalid_char = private unnamed_addr constant [12 x i8] c"InvalidChar\00"
+@format = private unnamed_addr constant [19 x i8] c"Error! Reason: %s\0A\00"
+
+declare i32 @getchar()
+declare i32 @putchar(i32)
+declare i32 @fflush(ptr)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @exit(i32) noreturn
+
+define internal void @fail(ptr %reason) noreturn {
+	call i32 @fflush(ptr null)
+	call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @format, ptr %reason)
+	call void @exit(i32 1)
+	unreachable
+}
+
+define i32 @main() {
+entry:
+	%ptr = alloca i64
+	store i64 0, ptr %ptr
+	%t1 = load i64, ptr %ptr
+	%t2 = getelementptr inbounds [16 x i16], ptr @tape, i64 0, i64 %t1
+	%t3 = load i16, ptr %t2
+	%t4 = zext i16 %t3 to i64
+	%t5 = add i64 %t4, 1
+	%t6 = icmp ult i64 %t5, 65536
+	br i1 %t6, label %ok8, label %val_out_of_bounds
+ok8:
+	%t7 = trunc i64 %t5 to i16
+	store i16 %t7, ptr %t2
+	br label %loop1
+loop1:
+	%t9 = load i64, ptr %ptr
+	%t10 = getelementptr inbounds [16 x i16], ptr @tape, i64 0, i64 %t9
+	%t11 = load i16, ptr %t10
+	%t12 = icmp eq i16 %t11, 0
+	br i1 %t12, label %end1, label %body1
+body1:
+	%t13 = load i64, ptr %ptr
+	%t14 = add i64 %t13, 1
+	%t15 = icmp ult i64 %t14, 16
+	br i1 %t15, label %ok16, label %ptr_out_of_bounds
+ok16:
+	store i64 %t14, ptr %ptr
+	%t17 = load i64, ptr %ptr
+	%t18 = getelementptr inbounds [16 x i16], ptr @tape, i64 0, i64 %t17
+	%t19 = call i32 @getchar()
+	%t20 = icmp sge i32 %t19, 0
+	br i1 %t20, label %ok21, label %invalid_char
+ok21:
+	%t22 = trunc i32 %t19 to i16
+	store i16 %t22, ptr %t18
+	%t23 = load i64, ptr %ptr
+	%t24 = getelementptr inbounds [16 x i16], ptr @tape, i64 0, i64 %t23
+	%t25 = load i16, ptr %t24
+	%t26 = zext i16 %t25 to i64
+	%t27 = icmp ult i64 %t26, 128
+	br i1 %t27, label %ok29, label %unprintable_byte
+ok29:
+	%t28 = trunc i64 %t26 to i32
+	call i32 @putchar(i32 %t28)
+	%t30 = load i64, ptr %ptr
+	%t31 = add i64 %t30, -1
+	%t32 = icmp ult i64 %t31, 16
+	br i1 %t32, label %ok33, label %ptr_out_of_bounds
+ok33:
+	store i64 %t31, ptr %ptr
+	%t34 = load i64, ptr %ptr
+	%t35 = getelementptr inbounds [16 x i16], ptr @tape, i64 0, i64 %t34
+	%t36 = load i16, ptr %t35
+	%t37 = zext i16 %t36 to i64
+	%t38 = add i64 %t37, -1
+	%t39 = icmp ult i64 %t38, 65536
+	br i1 %t39, label %ok41, label %val_out_of_bounds
+ok41:
+	%t40 = trunc i64 %t38 to i16
+	store i16 %t40, ptr %t35
+	br label %loop1
+end1:
+	ret i32 0
+ptr_out_of_bounds:
+	call void @fail(ptr @reason.ptr_out_of_bounds)
+	unreachable
+val_out_of_bounds:
+	call void @fail(ptr @reason.val_out_of_bounds)
+	unreachable
+unprintable_byte:
+	call void @fail(ptr @reason.unprintable_byte)
+	unreachable
+invalid_char:
+	call void @fail(ptr @reason.invalid_char)
+	unreachable
+}
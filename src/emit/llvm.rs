@@ -0,0 +1,269 @@
+//! Textual LLVM IR emitter, for feeding `opt`/`llc`/`clang` directly.
+//!
+//! The program becomes a `main` function over a global tape array, with `getchar`/`putchar` for
+//! I/O. Like the C emitter it keeps the interpreter's checked semantics, failing through a shared
+//! block per error reason that prints the reason to stderr and exits with status 1.
+//!
+//! The output uses opaque pointers, the default since LLVM 15 (LLVM 14 needs `-opaque-pointers`).
+
+use std::fmt::Write;
+
+use crate::interpreter::MEM_SIZE;
+use crate::ir::{Ir, Op};
+
+pub struct LlvmOptions {
+	/// Width of a tape cell in bits, up to 32.
+	pub cell_bits: u32,
+	pub tape_size: usize,
+}
+
+impl Default for LlvmOptions {
+	fn default() -> Self {
+		LlvmOptions { cell_bits: 8, tape_size: MEM_SIZE }
+	}
+}
+
+const ERRORS: [(&str, &str); 4] = [
+	("ptr_out_of_bounds", "PtrOutOfBounds"),
+	("val_out_of_bounds", "ValOutOfBounds"),
+	("unprintable_byte", "UnprintableByte"),
+	("invalid_char", "InvalidChar"),
+];
+
+struct Emitter {
+	code: String,
+	cell: String,
+	tape: String,
+	next_tmp: usize,
+}
+
+impl Emitter {
+	fn tmp(&mut self) -> String {
+		self.next_tmp += 1;
+		format!("%t{}", self.next_tmp)
+	}
+
+	fn line(&mut self, line: &str) {
+		self.code.push('\t');
+		self.code.push_str(line);
+		self.code.push('\n');
+	}
+
+	fn label(&mut self, label: &str) {
+		// Writing into a String can't fail
+		let _ = writeln!(self.code, "{label}:");
+	}
+
+	/// Branches to the `error` block unless `condition` holds, continuing in a fresh block.
+	fn check(&mut self, condition: &str, error: &str) {
+		self.next_tmp += 1;
+		let ok = format!("ok{}", self.next_tmp);
+		self.line(&format!("br i1 {condition}, label %{ok}, label %{error}"));
+		self.label(&ok);
+	}
+
+	/// Pointer to the current cell.
+	fn cell_ptr(&mut self) -> String {
+		let ptr = self.tmp();
+		let cell_ptr = self.tmp();
+		self.line(&format!("{ptr} = load i64, ptr %ptr"));
+		self.line(&format!(
+			"{cell_ptr} = getelementptr inbounds {}, ptr @tape, i64 0, i64 {ptr}", self.tape
+		));
+		cell_ptr
+	}
+
+	fn load_cell(&mut self, cell_ptr: &str) -> String {
+		let value = self.tmp();
+		self.line(&format!("{value} = load {}, ptr {cell_ptr}", self.cell));
+		value
+	}
+
+	fn move_ptr(&mut self, delta: isize, tape_size: usize) {
+		let ptr = self.tmp();
+		let moved = self.tmp();
+		let in_bounds = self.tmp();
+		self.line(&format!("{ptr} = load i64, ptr %ptr"));
+		self.line(&format!("{moved} = add i64 {ptr}, {delta}"));
+		// A negative pointer wraps around to a huge unsigned value
+		self.line(&format!("{in_bounds} = icmp ult i64 {moved}, {tape_size}"));
+		self.check(&in_bounds, "ptr_out_of_bounds");
+		self.line(&format!("store i64 {moved}, ptr %ptr"));
+	}
+
+	/// Branches to `zero` if the current cell is zero, continuing in `non_zero` otherwise.
+	fn branch_on_cell(&mut self, zero: &str, non_zero: &str) {
+		let cell_ptr = self.cell_ptr();
+		let value = self.load_cell(&cell_ptr);
+		let is_zero = self.tmp();
+		self.line(&format!("{is_zero} = icmp eq {} {value}, 0", self.cell));
+		self.line(&format!("br i1 {is_zero}, label %{zero}, label %{non_zero}"));
+		self.label(non_zero);
+	}
+}
+
+pub fn emit(ir: &Ir, options: &LlvmOptions) -> String {
+	let cell = format!("i{}", options.cell_bits);
+	let tape = format!("[{} x {cell}]", options.tape_size);
+	let mut emitter = Emitter { code: String::new(), cell: cell.clone(), tape, next_tmp: 0 };
+
+	for (idx, op) in ir.ops().iter().enumerate() {
+		match *op {
+			Op::Move(delta) => emitter.move_ptr(delta, options.tape_size),
+			// Emitting the loop after it is enough, `opt` folds it into arithmetic
+			Op::MulAdd(_) => {}
+			Op::Add(delta) => {
+				let cell_ptr = emitter.cell_ptr();
+				let value = emitter.load_cell(&cell_ptr);
+				let wide = emitter.tmp();
+				let sum = emitter.tmp();
+				let in_range = emitter.tmp();
+				let narrow = emitter.tmp();
+				emitter.line(&format!("{wide} = zext {cell} {value} to i64"));
+				emitter.line(&format!("{sum} = add i64 {wide}, {delta}"));
+				emitter.line(&format!("{in_range} = icmp ult i64 {sum}, {}", 1u64 << options.cell_bits));
+				emitter.check(&in_range, "val_out_of_bounds");
+				emitter.line(&format!("{narrow} = trunc i64 {sum} to {cell}"));
+				emitter.line(&format!("store {cell} {narrow}, ptr {cell_ptr}"));
+			}
+			Op::SetZero => {
+				let cell_ptr = emitter.cell_ptr();
+				emitter.line(&format!("store {cell} 0, ptr {cell_ptr}"));
+			}
+			Op::ScanRight | Op::ScanLeft => {
+				emitter.line(&format!("br label %scan{idx}"));
+				emitter.label(&format!("scan{idx}"));
+				emitter.branch_on_cell(&format!("scan_end{idx}"), &format!("scan_step{idx}"));
+				emitter.move_ptr(if *op == Op::ScanRight { 1 } else { -1 }, options.tape_size);
+				emitter.line(&format!("br label %scan{idx}"));
+				emitter.label(&format!("scan_end{idx}"));
+			}
+			Op::Print => {
+				let cell_ptr = emitter.cell_ptr();
+				let value = emitter.load_cell(&cell_ptr);
+				let wide = emitter.tmp();
+				let printable = emitter.tmp();
+				let char = emitter.tmp();
+				emitter.line(&format!("{wide} = zext {cell} {value} to i64"));
+				emitter.line(&format!("{printable} = icmp ult i64 {wide}, 128"));
+				emitter.check(&printable, "unprintable_byte");
+				emitter.line(&format!("{char} = trunc i64 {wide} to i32"));
+				emitter.line(&format!("call i32 @putchar(i32 {char})"));
+			}
+			Op::Read => {
+				let cell_ptr = emitter.cell_ptr();
+				let char = emitter.tmp();
+				let not_eof = emitter.tmp();
+				emitter.line(&format!("{char} = call i32 @getchar()"));
+				emitter.line(&format!("{not_eof} = icmp sge i32 {char}, 0"));
+				emitter.check(&not_eof, "invalid_char");
+				let value = if options.cell_bits < 32 {
+					let value = emitter.tmp();
+					emitter.line(&format!("{value} = trunc i32 {char} to {cell}"));
+					value
+				} else {
+					char
+				};
+				emitter.line(&format!("store {cell} {value}, ptr {cell_ptr}"));
+			}
+			Op::JumpIfZero(_) => {
+				emitter.line(&format!("br label %loop{idx}"));
+				emitter.label(&format!("loop{idx}"));
+				emitter.branch_on_cell(&format!("end{idx}"), &format!("body{idx}"));
+			}
+			Op::JumpIfNonZero(start) => {
+				emitter.line(&format!("br label %loop{start}"));
+				emitter.label(&format!("end{start}"));
+			}
+		}
+	}
+
+	let mut module = String::from("; Generated by brainfuck\n\n");
+	let _ = writeln!(module, "@tape = internal global {} zeroinitializer", emitter.tape);
+	for (name, reason) in ERRORS {
+		let _ = writeln!(
+			module,
+			"@reason.{name} = private unnamed_addr constant [{} x i8] c\"{reason}\\00\"",
+			reason.len() + 1
+		);
+	}
+	module.push_str("@format = private unnamed_addr constant [19 x i8] c\"Error! Reason: %s\\0A\\00\"\n\n");
+	module.push_str("declare i32 @getchar()\ndeclare i32 @putchar(i32)\ndeclare i32 @fflush(ptr)\n");
+	module.push_str("declare i32 @dprintf(i32, ptr, ...)\ndeclare void @exit(i32) noreturn\n\n");
+	module.push_str("define internal void @fail(ptr %reason) noreturn {\n");
+	module.push_str("\tcall i32 @fflush(ptr null)\n");
+	module.push_str("\tcall i32 (i32, ptr, ...) @dprintf(i32 2, ptr @format, ptr %reason)\n");
+	module.push_str("\tcall void @exit(i32 1)\n\tunreachable\n}\n\n");
+
+	module.push_str("define i32 @main() {\nentry:\n\t%ptr = alloca i64\n\tstore i64 0, ptr %ptr\n");
+	module.push_str(&emitter.code);
+	module.push_str("\tret i32 0\n");
+	for (name, _) in ERRORS {
+		let _ = writeln!(module, "{name}:\n\tcall void @fail(ptr @reason.{name})\n\tunreachable");
+	}
+	module.push_str("}\n");
+	module
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write as _;
+	use std::process::{Command, Stdio};
+
+	use super::*;
+	use crate::ir::DEFAULT_OPT_LEVEL;
+
+	fn emit_source(source: &str, opt_level: u8, options: &LlvmOptions) -> String {
+		let characters: Vec<char> = source.chars().collect();
+		emit(&Ir::compile(&characters, opt_level).unwrap(), options)
+	}
+
+	/// Whether `llvm-as` takes `module`, `None` without one to ask.
+	fn assembles(module: &str) -> Option<bool> {
+		let version = Command::new("llvm-as").arg("--version").output().ok()?;
+		let version = String::from_utf8_lossy(&version.stdout);
+		let major: u32 = version.split("version ").nth(1)?.split('.').next()?.parse().ok()?;
+		let mut command = Command::new("llvm-as");
+		// Opaque pointers became the default in 15
+		if major < 15 {
+			command.arg("-opaque-pointers");
+		}
+		let mut child = command.args(["-", "-o", "/dev/null"])
+			.stdin(Stdio::piped())
+			.stderr(Stdio::null())
+			.spawn()
+			.ok()?;
+		child.stdin.take()?.write_all(module.as_bytes()).ok()?;
+		Some(child.wait().ok()?.success())
+	}
+
+	#[test]
+	fn tape_and_cells_follow_the_options() {
+		let module = emit_source("+>.", DEFAULT_OPT_LEVEL, &LlvmOptions { cell_bits: 32, tape_size: 100 });
+		assert!(module.contains("@tape = internal global [100 x i32] zeroinitializer"));
+		// Bounds of the pointer and of a cell
+		assert!(module.contains(", 100\n"));
+		assert!(module.contains(&format!(", {}\n", 1u64 << 32)));
+	}
+
+	#[test]
+	fn small_programs_match_their_golden_file() {
+		let module = emit_source("+[>,.<-]", 0, &LlvmOptions { cell_bits: 16, tape_size: 16 });
+		assert_eq!(module, include_str!("golden/echo_once.ll"));
+	}
+
+	#[test]
+	fn output_passes_llvm_as() {
+		let hello = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+		for cell_bits in [8, 16, 32] {
+			let options = LlvmOptions { cell_bits, tape_size: MEM_SIZE };
+			for source in [hello, ",[.,]", "[>]<[<]+[->+<]"] {
+				match assembles(&emit_source(source, DEFAULT_OPT_LEVEL, &options)) {
+					Some(valid) => assert!(valid, "{source} with {cell_bits}-bit cells"),
+					// Nothing to check it with
+					None => return,
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,441 @@
+//! WebAssembly binary emitter.
+//!
+//! The generated module imports `env.read_byte: () -> i32` (returning a negative value on EOF) and
+//! `env.write_byte: (i32) -> ()`, and exports its linear memory as `memory` together with a `run`
+//! function executing the program, the tape living at the start of the memory. Runtime errors the
+//! interpreter would report (pointer or value out of bounds, unprintable bytes, reading past EOF)
+//! trap with `unreachable`.
+
+use crate::interpreter::MEM_SIZE;
+use crate::ir::{Ir, Op};
+
+const WASM_MAGIC: [u8; 4] = *b"\0asm";
+const WASM_VERSION: [u8; 4] = [1, 0, 0, 0];
+const PAGE_SIZE: usize = 65_536;
+
+const SECTION_TYPE: u8 = 1;
+const SECTION_IMPORT: u8 = 2;
+const SECTION_FUNCTION: u8 = 3;
+const SECTION_MEMORY: u8 = 5;
+const SECTION_EXPORT: u8 = 7;
+const SECTION_CODE: u8 = 10;
+
+const TYPE_FUNC: u8 = 0x60;
+const TYPE_I32: u8 = 0x7F;
+const BLOCK_EMPTY: u8 = 0x40;
+const EXTERNAL_FUNC: u8 = 0x00;
+const EXTERNAL_MEMORY: u8 = 0x02;
+
+const OP_UNREACHABLE: u8 = 0x00;
+const OP_BLOCK: u8 = 0x02;
+const OP_LOOP: u8 = 0x03;
+const OP_IF: u8 = 0x04;
+const OP_END: u8 = 0x0B;
+const OP_BR: u8 = 0x0C;
+const OP_BR_IF: u8 = 0x0D;
+const OP_CALL: u8 = 0x10;
+const OP_LOCAL_GET: u8 = 0x20;
+const OP_LOCAL_TEE: u8 = 0x22;
+const OP_I32_LOAD8_U: u8 = 0x2D;
+const OP_I32_STORE8: u8 = 0x3A;
+const OP_I32_CONST: u8 = 0x41;
+const OP_I32_EQZ: u8 = 0x45;
+const OP_I32_LT_S: u8 = 0x48;
+const OP_I32_GT_U: u8 = 0x4B;
+const OP_I32_GE_U: u8 = 0x4F;
+const OP_I32_ADD: u8 = 0x6A;
+
+const FUNC_READ_BYTE: u32 = 0;
+const FUNC_WRITE_BYTE: u32 = 1;
+const FUNC_RUN: u32 = 2;
+
+const LOCAL_PTR: u32 = 0;
+const LOCAL_TMP: u32 = 1;
+
+/// Minimal Node.js host for emitted modules: `node shim.js program.wasm < input`.
+pub const JS_SHIM: &str = r#"const fs = require("fs");
+
+const input = fs.readFileSync(0);
+let inputPos = 0;
+const output = [];
+
+const flush = () => process.stdout.write(Buffer.from(output.splice(0)));
+const imports = {
+	env: {
+		read_byte: () => (inputPos < input.length ? input[inputPos++] : -1),
+		write_byte: (byte) => {
+			output.push(byte);
+			if (byte === 10) flush();
+		},
+	},
+};
+
+WebAssembly.instantiate(fs.readFileSync(process.argv[2]), imports).then(({ instance }) => {
+	try {
+		instance.exports.run();
+	} finally {
+		flush();
+	}
+});
+"#;
+
+/// Encodes `ir` as a complete WebAssembly module.
+pub fn emit(ir: &Ir) -> Vec<u8> {
+	let mut module = Vec::from(WASM_MAGIC);
+	module.extend(WASM_VERSION);
+
+	let mut types = Vec::new();
+	write_u32(&mut types, 3);
+	types.extend([TYPE_FUNC, 0, 1, TYPE_I32]);
+	types.extend([TYPE_FUNC, 1, TYPE_I32, 0]);
+	types.extend([TYPE_FUNC, 0, 0]);
+	write_section(&mut module, SECTION_TYPE, &types);
+
+	let mut imports = Vec::new();
+	write_u32(&mut imports, 2);
+	write_name(&mut imports, "env");
+	write_name(&mut imports, "read_byte");
+	imports.push(EXTERNAL_FUNC);
+	write_u32(&mut imports, 0);
+	write_name(&mut imports, "env");
+	write_name(&mut imports, "write_byte");
+	imports.push(EXTERNAL_FUNC);
+	write_u32(&mut imports, 1);
+	write_section(&mut module, SECTION_IMPORT, &imports);
+
+	let mut functions = Vec::new();
+	write_u32(&mut functions, 1);
+	write_u32(&mut functions, 2);
+	write_section(&mut module, SECTION_FUNCTION, &functions);
+
+	let mut memory = Vec::new();
+	write_u32(&mut memory, 1);
+	memory.push(0);
+	write_u32(&mut memory, MEM_SIZE.div_ceil(PAGE_SIZE) as u32);
+	write_section(&mut module, SECTION_MEMORY, &memory);
+
+	let mut exports = Vec::new();
+	write_u32(&mut exports, 2);
+	write_name(&mut exports, "run");
+	exports.push(EXTERNAL_FUNC);
+	write_u32(&mut exports, FUNC_RUN);
+	write_name(&mut exports, "memory");
+	exports.push(EXTERNAL_MEMORY);
+	write_u32(&mut exports, 0);
+	write_section(&mut module, SECTION_EXPORT, &exports);
+
+	let body = emit_body(ir);
+	let mut code = Vec::new();
+	write_u32(&mut code, 1);
+	write_u32(&mut code, body.len() as u32);
+	code.extend(body);
+	write_section(&mut module, SECTION_CODE, &code);
+
+	module
+}
+
+fn emit_body(ir: &Ir) -> Vec<u8> {
+	let mut body = Vec::new();
+	// One local group of two i32s: the data pointer and a scratch value
+	write_u32(&mut body, 1);
+	write_u32(&mut body, 2);
+	body.push(TYPE_I32);
+
+	for op in ir.ops() {
+		match *op {
+			Op::Move(delta) => move_ptr(&mut body, delta),
+			// Left to the loop that follows; engines optimize the module themselves
+			Op::MulAdd(_) => {}
+			Op::Add(delta) => {
+				local_get(&mut body, LOCAL_PTR);
+				load_cell(&mut body);
+				i32_const(&mut body, delta as i32);
+				body.push(OP_I32_ADD);
+				body.push(OP_LOCAL_TEE);
+				write_u32(&mut body, LOCAL_TMP);
+				i32_const(&mut body, u8::MAX as i32);
+				body.push(OP_I32_GT_U);
+				trap_if(&mut body);
+				store_tmp(&mut body);
+			}
+			Op::SetZero => {
+				local_get(&mut body, LOCAL_PTR);
+				i32_const(&mut body, 0);
+				body.push(OP_I32_STORE8);
+				body.extend([0, 0]);
+			}
+			Op::ScanRight | Op::ScanLeft => {
+				body.extend([OP_BLOCK, BLOCK_EMPTY, OP_LOOP, BLOCK_EMPTY]);
+				load_cell(&mut body);
+				body.push(OP_I32_EQZ);
+				body.push(OP_BR_IF);
+				write_u32(&mut body, 1);
+				move_ptr(&mut body, if *op == Op::ScanRight { 1 } else { -1 });
+				body.push(OP_BR);
+				write_u32(&mut body, 0);
+				body.extend([OP_END, OP_END]);
+			}
+			Op::Print => {
+				load_cell(&mut body);
+				body.push(OP_LOCAL_TEE);
+				write_u32(&mut body, LOCAL_TMP);
+				i32_const(&mut body, 0x7F);
+				body.push(OP_I32_GT_U);
+				trap_if(&mut body);
+				local_get(&mut body, LOCAL_TMP);
+				body.push(OP_CALL);
+				write_u32(&mut body, FUNC_WRITE_BYTE);
+			}
+			Op::Read => {
+				local_get(&mut body, LOCAL_PTR);
+				body.push(OP_CALL);
+				write_u32(&mut body, FUNC_READ_BYTE);
+				body.push(OP_LOCAL_TEE);
+				write_u32(&mut body, LOCAL_TMP);
+				i32_const(&mut body, 0);
+				body.push(OP_I32_LT_S);
+				trap_if(&mut body);
+				store_tmp(&mut body);
+			}
+			Op::JumpIfZero(_) => {
+				body.extend([OP_BLOCK, BLOCK_EMPTY, OP_LOOP, BLOCK_EMPTY]);
+				load_cell(&mut body);
+				body.push(OP_I32_EQZ);
+				body.push(OP_BR_IF);
+				write_u32(&mut body, 1);
+			}
+			Op::JumpIfNonZero(_) => {
+				body.push(OP_BR);
+				write_u32(&mut body, 0);
+				body.extend([OP_END, OP_END]);
+			}
+		}
+	}
+
+	body.push(OP_END);
+	body
+}
+
+/// Moves the data pointer, trapping if it leaves the tape.
+fn move_ptr(body: &mut Vec<u8>, delta: isize) {
+	local_get(body, LOCAL_PTR);
+	i32_const(body, delta as i32);
+	body.push(OP_I32_ADD);
+	body.push(OP_LOCAL_TEE);
+	write_u32(body, LOCAL_PTR);
+	i32_const(body, MEM_SIZE as i32);
+	body.push(OP_I32_GE_U);
+	trap_if(body);
+}
+
+/// Pushes the current cell's value, given the data pointer.
+fn load_cell(body: &mut Vec<u8>) {
+	local_get(body, LOCAL_PTR);
+	body.push(OP_I32_LOAD8_U);
+	// Alignment and offset immediates
+	body.extend([0, 0]);
+}
+
+/// Stores the scratch local into the cell whose address is on the stack.
+fn store_tmp(body: &mut Vec<u8>) {
+	local_get(body, LOCAL_TMP);
+	body.push(OP_I32_STORE8);
+	body.extend([0, 0]);
+}
+
+fn trap_if(body: &mut Vec<u8>) {
+	body.extend([OP_IF, BLOCK_EMPTY, OP_UNREACHABLE, OP_END]);
+}
+
+fn local_get(body: &mut Vec<u8>, local: u32) {
+	body.push(OP_LOCAL_GET);
+	write_u32(body, local);
+}
+
+fn i32_const(body: &mut Vec<u8>, value: i32) {
+	body.push(OP_I32_CONST);
+	write_i32(body, value);
+}
+
+fn write_section(module: &mut Vec<u8>, id: u8, contents: &[u8]) {
+	module.push(id);
+	write_u32(module, contents.len() as u32);
+	module.extend_from_slice(contents);
+}
+
+fn write_name(buffer: &mut Vec<u8>, name: &str) {
+	write_u32(buffer, name.len() as u32);
+	buffer.extend_from_slice(name.as_bytes());
+}
+
+fn write_u32(buffer: &mut Vec<u8>, mut value: u32) {
+	loop {
+		let byte = (value & 0x7F) as u8;
+		value >>= 7;
+		if value == 0 {
+			buffer.push(byte);
+			break;
+		}
+		buffer.push(byte | 0x80);
+	}
+}
+
+fn write_i32(buffer: &mut Vec<u8>, mut value: i32) {
+	loop {
+		let byte = (value & 0x7F) as u8;
+		value >>= 7;
+		let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+		if done {
+			buffer.push(byte);
+			break;
+		}
+		buffer.push(byte | 0x80);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::process::{Command, Stdio};
+	use std::io::Write;
+
+	use super::*;
+	use crate::interpreter::Interpreter;
+	use crate::interpreter::io::CapturedOutput;
+	use crate::ir::DEFAULT_OPT_LEVEL;
+
+	const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+	fn emit_source(source: &str) -> Vec<u8> {
+		let characters: Vec<char> = source.chars().collect();
+		emit(&Ir::compile(&characters, DEFAULT_OPT_LEVEL).unwrap())
+	}
+
+	/// Reads the module back the way an engine's decoder would, returning the ids of its sections
+	/// and the names it exports.
+	fn parse(module: &[u8]) -> (Vec<u8>, Vec<String>) {
+		let mut reader = Reader { bytes: module, position: 8 };
+		assert_eq!(module[..8], [b"\0asm".as_slice(), &[1, 0, 0, 0]].concat());
+
+		let (mut ids, mut exports) = (Vec::new(), Vec::new());
+		while reader.position < module.len() {
+			let id = reader.byte();
+			let end = reader.u32() as usize + reader.position;
+			match id {
+				SECTION_EXPORT => {
+					for _ in 0..reader.u32() {
+						let name_end = reader.u32() as usize + reader.position;
+						exports.push(String::from_utf8(module[reader.position..name_end].to_vec()).unwrap());
+						reader.position = name_end;
+						reader.byte();
+						reader.u32();
+					}
+				}
+				SECTION_CODE => {
+					assert_eq!(reader.u32(), 1);
+					let body_end = reader.u32() as usize + reader.position;
+					assert_eq!(body_end, end);
+					reader.u32();
+					reader.u32();
+					assert_eq!(reader.byte(), TYPE_I32);
+					reader.instructions(body_end);
+				}
+				_ => reader.position = end,
+			}
+			assert_eq!(reader.position, end, "section {id}");
+			ids.push(id);
+		}
+		(ids, exports)
+	}
+
+	struct Reader<'a> {
+		bytes: &'a [u8],
+		position: usize,
+	}
+
+	impl Reader<'_> {
+		fn byte(&mut self) -> u8 {
+			self.position += 1;
+			self.bytes[self.position - 1]
+		}
+
+		fn u32(&mut self) -> u32 {
+			let (mut value, mut shift) = (0, 0);
+			loop {
+				let byte = self.byte();
+				value |= u32::from(byte & 0x7F) << shift;
+				if byte & 0x80 == 0 {
+					return value;
+				}
+				shift += 7;
+			}
+		}
+
+		/// Walks a function body up to its final `end`, checking blocks nest.
+		fn instructions(&mut self, end: usize) {
+			let mut depth = 1;
+			while depth > 0 {
+				match self.byte() {
+					OP_BLOCK | OP_LOOP | OP_IF => {
+						assert_eq!(self.byte(), BLOCK_EMPTY);
+						depth += 1;
+					}
+					OP_END => depth -= 1,
+					OP_BR | OP_BR_IF => assert!(self.u32() < depth),
+					OP_CALL => assert!(self.u32() <= FUNC_WRITE_BYTE),
+					OP_LOCAL_GET | OP_LOCAL_TEE => assert!(self.u32() <= LOCAL_TMP),
+					// Signed, but the continuation bits are the same
+					OP_I32_CONST => {
+						self.u32();
+					}
+					OP_I32_LOAD8_U | OP_I32_STORE8 => assert_eq!((self.u32(), self.u32()), (0, 0)),
+					OP_UNREACHABLE | OP_I32_EQZ | OP_I32_LT_S | OP_I32_GT_U | OP_I32_GE_U | OP_I32_ADD => {}
+					other => panic!("unknown opcode {other:#04x} at {}", self.position - 1),
+				}
+			}
+			assert_eq!(self.position, end);
+		}
+	}
+
+	#[test]
+	fn modules_parse_back() {
+		for source in ["", HELLO, ",[.,]", "+[>+<-[<]>]"] {
+			let (ids, exports) = parse(&emit_source(source));
+			assert_eq!(ids, [SECTION_TYPE, SECTION_IMPORT, SECTION_FUNCTION, SECTION_MEMORY, SECTION_EXPORT, SECTION_CODE]);
+			assert_eq!(exports, ["run", "memory"]);
+		}
+	}
+
+	#[test]
+	fn modules_run_like_the_interpreter() {
+		if Command::new("node").arg("--version").output().is_err() {
+			eprintln!("node not found, skipping");
+			return;
+		}
+		let dir = std::env::temp_dir().join(format!("brainfuck-{}-wasm", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		std::fs::write(dir.join("shim.js"), JS_SHIM).unwrap();
+
+		for (source, input) in [(HELLO, ""), (",[.,]", "echo\n"), (",[->+>++<<]>.>.", "\u{21}")] {
+			let characters: Vec<char> = source.chars().collect();
+			let output = CapturedOutput::new();
+			let mut interpreter = Interpreter::with_output(output.clone());
+			interpreter.feed_input(input.as_bytes());
+			// `,` at the end of the input is an error in both
+			let _ = interpreter.run(&characters);
+
+			std::fs::write(dir.join("program.wasm"), emit_source(source)).unwrap();
+			let mut node = Command::new("node")
+				.arg(dir.join("shim.js"))
+				.arg(dir.join("program.wasm"))
+				.stdin(Stdio::piped())
+				.stdout(Stdio::piped())
+				.stderr(Stdio::null())
+				.spawn()
+				.unwrap();
+			node.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+			let run = node.wait_with_output().unwrap();
+			assert_eq!(String::from_utf8_lossy(&run.stdout), String::from_utf8_lossy(&output.contents()), "{source}");
+		}
+		let _ = std::fs::remove_dir_all(dir);
+	}
+}
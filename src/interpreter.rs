@@ -1,27 +1,175 @@
+pub mod cancel;
+pub mod config;
+pub mod coredump;
+mod deadline;
 pub mod error;
-mod io;
+pub mod events;
+mod exec;
+pub mod history;
+pub mod hot_loop;
+pub mod io;
+mod loop_cap;
 mod math_utils;
 mod memory;
+pub mod profile;
+pub mod report;
+mod stall;
+pub mod stats;
+pub mod watch;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{Debug, Formatter};
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use error::InterpreterError;
+use events::Events;
+use history::{History, RevertedStep, UndoRecord};
+use cancel::CancellationToken;
+use config::InterpreterConfig;
+use deadline::Deadline;
+use hot_loop::{HotLoop, HotLoopDetector};
+use io::{CapturedOutput, Io};
+use report::ExecutionReport;
+use loop_cap::IterationCap;
 use crate::InterpreterSymbol;
+use crate::ir::MulAdd;
 use crate::symbol::InterpreterInstruction;
 use memory::InterpreterMemory;
+use profile::LoopProfile;
+use stall::StallDetector;
+use stats::RunStats;
+use watch::{WatchHit, Watchpoint};
 
-const MEM_SIZE: usize = 30_000usize;
+pub const MEM_SIZE: usize = 30_000usize;
+/// Character dumping the machine for [`Interpreter::with_debug_char`] when none is chosen.
+pub const DEFAULT_DEBUG_CHAR: char = '!';
+/// Loops that can be nested unless configured otherwise.
+pub const DEFAULT_MAX_LOOP_DEPTH: usize = 10_000;
+/// Steps [`Interpreter::run_fuzz`] gives a program.
+pub const FUZZ_STEPS: u64 = 1_000_000;
+/// Bytes [`Interpreter::run_fuzz`] lets a program print.
+pub const FUZZ_MAX_OUTPUT: u64 = 1 << 16;
 
-#[derive(Debug)]
+/// Clones share the input reader and output sink, but get their own copy of everything else: the
+/// tape, pointers, loop stack and queued input.
+#[derive(Clone)]
 pub struct Interpreter {
 	memory: InterpreterMemory,
 	data_ptr: usize,
 	instruction_ptr: usize,
 	stack: Vec<usize>,
+	max_stack_depth: usize,
+	max_loop_depth: usize,
+	/// Set by `]`, so the `[` it goes back to knows it isn't reached from before the loop.
+	looping_back: bool,
 	state: InterpreterState,
+	/// The `[` the current or last skip started at, unmatched if the program ends while skipping.
+	skip_start: usize,
+	io: Io,
+	input_queue: VecDeque<u8>,
+	input_recorder: Option<Rc<RefCell<dyn Write>>>,
+	/// Write every byte `,` reads to the output too.
+	echo_input: bool,
+	/// Bytes printed so far, and how many may be.
+	printed: u64,
+	output_limit: Option<u64>,
+	eof_policy: EofPolicy,
+	/// The output sink, when it's the one [`Interpreter::with_captured_output`] set.
+	captured: Option<CapturedOutput>,
+	/// Executions per instruction pointer, `None` where no instruction was reached.
+	coverage: Option<Vec<Option<u64>>>,
+	history: Option<History>,
+	watchpoints: Vec<Watchpoint>,
+	watch_hit: Option<WatchHit>,
+	/// Set from elsewhere, typically a signal handler, to stop the run before the next symbol.
+	interrupt: Option<Arc<AtomicBool>>,
+	deadline: Option<Deadline>,
+	stats: Option<RunStats>,
+	loop_profile: Option<LoopProfile>,
+	hot_loops: Option<HotLoopDetector>,
+	stalls: Option<StallDetector>,
+	iteration_cap: Option<IterationCap>,
+	debug_char: Option<char>,
+	/// Time spent blocked reading input, when timed.
+	input_wait: Option<Duration>,
+	/// Op of the IR to resume from, once [`Interpreter::run_with_fuel`] ran out.
+	ir_pc: usize,
+	/// The program given to [`Interpreter::load`] or [`Interpreter::load_bytes`], shared between
+	/// clones.
+	program: LoadedProgram,
 }
 
 pub type InterpreterResult = Result<(), InterpreterError>;
 
-#[derive(Debug)]
+/// What `,` does once there is no more input to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EofPolicy {
+	/// Fail the run with an `InvalidChar` error.
+	#[default]
+	Error,
+	/// Store a zero in the current cell.
+	Zero,
+	/// Leave the current cell untouched.
+	Unchanged,
+}
+
+impl EofPolicy {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"error" => Some(EofPolicy::Error),
+			"zero" => Some(EofPolicy::Zero),
+			"unchanged" => Some(EofPolicy::Unchanged),
+			_ => None,
+		}
+	}
+}
+
+/// A program kept for [`Interpreter::run_for`]. Loops jump back, so all of it stays in memory either
+/// way, the bytes just taking an eighth of the room the symbols do.
+#[derive(Clone)]
+enum LoadedProgram {
+	Symbols(Rc<[InterpreterSymbol]>),
+	/// Source bytes, classified every time they're reached.
+	Bytes(Rc<[u8]>),
+}
+
+impl LoadedProgram {
+	fn at(&self, index: usize) -> InterpreterSymbol {
+		match self {
+			LoadedProgram::Symbols(symbols) => InterpreterSymbol::at(symbols, index),
+			LoadedProgram::Bytes(bytes) => InterpreterSymbol::from_byte(bytes.get(index)),
+		}
+	}
+}
+
+/// How a [`Interpreter::run_for`] or [`Interpreter::run_with_fuel`] slice ended, when it didn't
+/// fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceOutcome {
+	Halted,
+	/// The step budget or fuel ran out, the next call picks up from here.
+	OutOfSteps,
+}
+
+/// Everything telling two machines apart, for comparing runs with `==`. Taken with
+/// [`Interpreter::snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterpreterSnapshot {
+	/// The written part of the tape, trailing zeros left out.
+	pub tape: Vec<u8>,
+	pub data_ptr: usize,
+	pub instruction_ptr: usize,
+	pub stack: Vec<usize>,
+	pub state: InterpreterState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InterpreterState {
 	Running,
 	Skipping(usize),
@@ -30,12 +178,338 @@ pub enum InterpreterState {
 
 impl Interpreter {
 	pub fn new() -> Self {
+		Self::with_streams(Io::default())
+	}
+
+	/// An interpreter on stdin and stdout set up as `config` says.
+	pub fn with_config(config: InterpreterConfig) -> Result<Self, InterpreterError> {
+		Self::with_streams(Io::default()).configured(config)
+	}
+
+	/// Applies every setting of `config`, for interpreters made with other constructors. Fails if
+	/// the pointer would start off the tape.
+	pub fn configured(mut self, config: InterpreterConfig) -> Result<Self, InterpreterError> {
+		self.memory.restrict(config.tape_cells);
+		if config.start_ptr >= self.memory.len() {
+			return Err(InterpreterError::ptr_out_of_bounds(config.start_ptr));
+		}
+		self.data_ptr = config.start_ptr;
+		self.eof_policy = config.eof_policy;
+		self.max_loop_depth = config.max_loop_depth;
+		self.iteration_cap = config.max_loop_iterations.map(IterationCap::new);
+		self.output_limit = config.max_output;
+		self.deadline = config.timeout.map(|timeout| Deadline::new(timeout, config.timeout_includes_input));
+		self.echo_input = config.echo_input;
+		self.debug_char = config.debug_char;
+		self.stalls = config.detect_infinite_loops.then(StallDetector::default);
+		Ok(self)
+	}
+
+	pub fn with_io(input: impl Read + 'static, output: impl Write + 'static) -> Self {
+		Self::with_streams(Io::new(input, output))
+	}
+
+	/// Creates an interpreter with no input reader: `,` only consumes bytes given through
+	/// [`Interpreter::feed_input`], applying the EOF policy once they run out.
+	pub fn with_output(output: impl Write + 'static) -> Self {
+		Self::with_streams(Io::output_only(output))
+	}
+
+	/// Creates an interpreter reading and printing through `io`.
+	pub fn with_streams(io: Io) -> Self {
 		Interpreter {
 			memory: InterpreterMemory::new(),
 			data_ptr: 0usize,
 			instruction_ptr: 0usize,
 			stack: Vec::new(),
+			max_stack_depth: 0,
+			max_loop_depth: DEFAULT_MAX_LOOP_DEPTH,
+			looping_back: false,
 			state: InterpreterState::Running,
+			skip_start: 0,
+			io,
+			input_queue: VecDeque::new(),
+			input_recorder: None,
+			echo_input: false,
+			printed: 0,
+			output_limit: None,
+			eof_policy: EofPolicy::default(),
+			captured: None,
+			coverage: None,
+			history: None,
+			watchpoints: Vec::new(),
+			watch_hit: None,
+			interrupt: None,
+			deadline: None,
+			stats: None,
+			loop_profile: None,
+			hot_loops: None,
+			stalls: None,
+			iteration_cap: None,
+			debug_char: None,
+			input_wait: None,
+			ir_pc: 0,
+			program: LoadedProgram::Symbols(Rc::from([])),
+		}
+	}
+
+	/// Replaces the output sink.
+	pub fn with_output_sink(mut self, output: impl Write + 'static) -> Self {
+		self.io.set_output(output);
+		self.captured = None;
+		self
+	}
+
+	/// Keeps the output in memory instead, handed back by [`Interpreter::execute`].
+	pub fn with_captured_output(self) -> Self {
+		let captured = CapturedOutput::new();
+		let mut interpreter = self.with_output_sink(captured.clone());
+		interpreter.captured = Some(captured);
+		interpreter
+	}
+
+	pub fn with_eof_policy(mut self, eof_policy: EofPolicy) -> Self {
+		self.eof_policy = eof_policy;
+		self
+	}
+
+	/// Makes `character`, otherwise a comment, print the written tape and the data pointer to
+	/// stderr when reached, changing nothing. Only seen stepping through the characters, the IR
+	/// drops comments.
+	pub fn with_debug_char(mut self, character: char) -> Self {
+		self.debug_char = Some(character);
+		self
+	}
+
+	/// Fails the run with a `LoopIterationLimit` error when a loop would go around more than
+	/// `limit` times in a row, counted from reaching its `[`. Loops the IR folds into a single op
+	/// aren't counted, and the closures don't check.
+	pub fn with_max_loop_iterations(mut self, limit: u64) -> Self {
+		self.iteration_cap = Some(IterationCap::new(limit));
+		self
+	}
+
+	/// Keeps only the first `cells` of the tape, at least one: moving past them fails with a
+	/// `TapeLimitExceeded` error instead of `PtrOutOfBounds`, and the tape takes no more memory
+	/// than that. Meant to come before [`Interpreter::with_initial_tape`], which then fails to seed
+	/// more bytes than there are cells.
+	pub fn with_max_tape_cells(mut self, cells: usize) -> Self {
+		self.memory.restrict(cells);
+		self
+	}
+
+	/// Fails the run with a `LoopDepthExceeded` error, at the `[`, when entering a loop would nest
+	/// more than `depth` of them, [`DEFAULT_MAX_LOOP_DEPTH`] unless set. Only the loop stack of
+	/// stepping through the characters is bounded, compiled runs don't keep one.
+	pub fn with_max_loop_depth(mut self, depth: usize) -> Self {
+		self.max_loop_depth = depth;
+		self
+	}
+
+	/// Fails the run with an `OutputLimitExceeded` error when `.` would print more than `limit`
+	/// bytes, whichever way the program is run. What was printed before is kept.
+	pub fn with_output_limit(mut self, limit: u64) -> Self {
+		self.output_limit = Some(limit);
+		self
+	}
+
+	/// Writes every byte `,` consumes to the output sink, so a transcript of the run shows what
+	/// was typed.
+	pub fn with_input_echo(mut self) -> Self {
+		self.echo_input = true;
+		self
+	}
+
+	/// Copies every byte `,` stores into a cell to `recorder`, EOF policy zeros included, so
+	/// feeding the recording back through [`Interpreter::feed_input`] replays the run faithfully.
+	pub fn with_input_recorder(mut self, recorder: impl Write + 'static) -> Self {
+		self.input_recorder = Some(Rc::new(RefCell::new(recorder)));
+		self
+	}
+
+	/// Starts the tape with `bytes` in the cells from 0 on, for programs that take their data
+	/// preloaded. Fails if there are more bytes than cells.
+	pub fn with_initial_tape(mut self, bytes: &[u8]) -> Result<Self, InterpreterError> {
+		let counting = self.memory.access_counts().is_some();
+		let cells = self.memory.len();
+		if bytes.len() > cells {
+			return Err(InterpreterError::seed_too_large(bytes.len()));
+		}
+		self.memory = InterpreterMemory::with_initial(bytes)
+			.map_err(|_| InterpreterError::seed_too_large(bytes.len()))?;
+		self.memory.restrict(cells);
+		if counting {
+			self.memory.track_access_counts();
+		}
+		Ok(self)
+	}
+
+	/// Starts the data pointer on `cell` instead of 0, such as right past data preloaded with
+	/// [`Interpreter::with_initial_tape`]. Fails if the tape has no such cell, so it's meant to come
+	/// after [`Interpreter::with_max_tape_cells`].
+	pub fn with_start_ptr(mut self, cell: usize) -> Result<Self, InterpreterError> {
+		if cell >= self.memory.len() {
+			return Err(InterpreterError::ptr_out_of_bounds(cell));
+		}
+		self.data_ptr = cell;
+		Ok(self)
+	}
+
+	/// Stops the run with an `Interrupted` error once `flag` is set. The interpreter is left as it
+	/// was before the symbol it didn't run, so it can be inspected or resumed after clearing `flag`.
+	pub fn with_interrupt_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+		self.interrupt = Some(flag);
+		self
+	}
+
+	/// Stops the run with an `Interrupted` error once `token` is cancelled, as
+	/// [`Interpreter::with_interrupt_flag`] does, replacing any flag given before.
+	pub fn with_cancellation(self, token: &CancellationToken) -> Self {
+		self.with_interrupt_flag(token.flag())
+	}
+
+	/// Stops the run with a `TimedOut` error once `timeout` has passed from now, checking every few
+	/// thousand steps. Time blocked reading input only counts if `includes_input`.
+	pub fn with_timeout(mut self, timeout: Duration, includes_input: bool) -> Self {
+		self.deadline = Some(Deadline::new(timeout, includes_input));
+		self
+	}
+
+	/// Counts executed instructions, loop iterations and I/O for [`Interpreter::stats`].
+	pub fn with_stats(mut self) -> Self {
+		self.stats = Some(RunStats::default());
+		self
+	}
+
+	/// Like [`Interpreter::with_stats`], also counting the reads and writes of every cell into
+	/// [`RunStats::cells`].
+	pub fn with_memory_profile(mut self) -> Self {
+		self.stats = Some(RunStats::with_cells());
+		self
+	}
+
+	/// Measures how long `,` waits on the input reader, for [`Interpreter::input_wait`].
+	pub fn with_input_timing(mut self) -> Self {
+		self.input_wait = Some(Duration::ZERO);
+		self
+	}
+
+	/// Calls `on_hot_loop` once for every loop running `threshold` iterations in a row without any
+	/// I/O, which may never end. See [`hot_loop::DEFAULT_HOT_LOOP_ITERATIONS`].
+	pub fn with_hot_loop_warning(mut self, threshold: u64, on_hot_loop: impl Fn(HotLoop) + 'static) -> Self {
+		self.hot_loops = Some(HotLoopDetector::new(threshold, Rc::new(on_hot_loop)));
+		self
+	}
+
+	/// Fails the run with an `InfiniteLoopDetected` error once a loop goes around without writing a
+	/// cell, moving the pointer or doing I/O, which it would then do forever. Best effort: loops
+	/// that change cells and change them back still run until stopped.
+	pub fn with_infinite_loop_detection(mut self) -> Self {
+		self.stalls = Some(StallDetector::default());
+		self
+	}
+
+	/// Counts entries and iterations of every loop for [`Interpreter::loop_profile`].
+	pub fn with_loop_profile(mut self) -> Self {
+		self.loop_profile = Some(LoopProfile::default());
+		self
+	}
+
+	/// Counts writes to every cell so [`Interpreter::access_counts`] can report the hot ones.
+	pub fn with_access_counts(mut self) -> Self {
+		self.memory.track_access_counts();
+		self
+	}
+
+	/// Records which instructions run and how often, see [`Interpreter::unexecuted_indices`] and
+	/// [`Interpreter::execution_counts`].
+	pub fn with_coverage(mut self) -> Self {
+		self.coverage = Some(Vec::new());
+		self
+	}
+
+	/// Keeps undo records for the last `capacity` steps so they can be reverted with
+	/// [`Interpreter::step_back`]. Costs a small record per step, so it's off by default.
+	pub fn with_history(mut self, capacity: usize) -> Self {
+		self.history = Some(History::new(capacity));
+		self
+	}
+
+	/// Makes [`Interpreter::run_until_break`] pause once `watchpoint` fires. Checking them only
+	/// costs anything while some are set.
+	pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+		if !self.watchpoints.contains(&watchpoint) {
+			self.watchpoints.push(watchpoint);
+		}
+	}
+
+	/// Shorthand for a watchpoint pausing as soon as a write turns the cell at `address` into
+	/// `value`.
+	pub fn set_cell_breakpoint(&mut self, address: usize, value: u8) {
+		self.add_watchpoint(Watchpoint::becomes(address, value));
+	}
+
+	/// Removes the watchpoint, returning whether it was set.
+	pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+		let len = self.watchpoints.len();
+		self.watchpoints.retain(|set| *set != watchpoint);
+		self.watchpoints.len() != len
+	}
+
+	pub fn clear_watchpoints(&mut self) {
+		self.watchpoints.clear();
+	}
+
+	pub fn watchpoints(&self) -> &[Watchpoint] {
+		&self.watchpoints
+	}
+
+	/// The watchpoint that fired since the last call, if any, for drivers stepping on their own.
+	pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+		self.watch_hit.take()
+	}
+
+	/// Queues bytes for `,` to consume before falling back to the input reader, if any.
+	pub fn feed_input(&mut self, bytes: &[u8]) {
+		self.input_queue.extend(bytes);
+	}
+
+	/// Runs `program` with no input reader, returning everything it printed instead of writing to
+	/// stdout.
+	pub fn run_capturing(program: &str) -> Result<Vec<u8>, InterpreterError> {
+		let characters: Vec<char> = program.chars().collect();
+		let output = CapturedOutput::new();
+		let mut interpreter = Self::with_output(output.clone());
+		interpreter.run(&characters)?;
+		Ok(output.contents())
+	}
+
+	/// Runs `program` with `input` queued, returning what it printed along with how the run ended,
+	/// so the output up to a failure isn't lost. Printing more than `max_output` bytes, when given,
+	/// fails the run.
+	pub fn run_with_input(program: &str, input: &[u8], max_output: Option<u64>) -> (Vec<u8>, InterpreterResult) {
+		let characters: Vec<char> = program.chars().collect();
+		let output = CapturedOutput::new();
+		let mut interpreter = Self::with_output(output.clone());
+		if let Some(limit) = max_output {
+			interpreter = interpreter.with_output_limit(limit);
+		}
+		interpreter.feed_input(input);
+		let result = interpreter.run(&characters);
+		(output.contents(), result)
+	}
+
+	/// Runs any bytes as a program on `input`, for fuzzing: every byte is a character of its own,
+	/// so invalid UTF-8 is just comments, and the run is bounded by [`FUZZ_STEPS`] and
+	/// [`FUZZ_MAX_OUTPUT`]. Never touches stdin or stdout, and never panics, that being a bug.
+	pub fn run_fuzz(program: &[u8], input: &[u8]) -> Result<Vec<u8>, InterpreterError> {
+		let output = CapturedOutput::new();
+		let mut interpreter = Self::with_output(output.clone()).with_output_limit(FUZZ_MAX_OUTPUT);
+		interpreter.feed_input(input);
+		interpreter.load_bytes(program);
+		match interpreter.run_for(FUZZ_STEPS)? {
+			SliceOutcome::Halted => Ok(output.contents()),
+			SliceOutcome::OutOfSteps => Err(InterpreterError::step_limit_exceeded(FUZZ_STEPS)),
 		}
 	}
 
@@ -43,11 +517,245 @@ impl Interpreter {
 		self.instruction_ptr
 	}
 
+	/// Per-cell write counts over the written range, if enabled with
+	/// [`Interpreter::with_access_counts`].
+	pub fn access_counts(&self) -> Option<&[u64]> {
+		self.memory.access_counts()
+	}
+
+	/// Instruction pointers of the instructions that were reached but never executed, such as the
+	/// body of a loop entered on a zero cell. Empty unless enabled with
+	/// [`Interpreter::with_coverage`].
+	pub fn unexecuted_indices(&self) -> Vec<usize> {
+		self.coverage.iter().flatten().enumerate()
+			.filter(|(_, executed)| **executed == Some(0))
+			.map(|(idx, _)| idx)
+			.collect()
+	}
+
+	/// Times the instruction at every instruction pointer ran, `None` for characters that were
+	/// never reached or aren't instructions. Only as long as the furthest reached one, and `None`
+	/// unless enabled with [`Interpreter::with_coverage`].
+	pub fn execution_counts(&self) -> Option<&[Option<u64>]> {
+		self.coverage.as_deref()
+	}
+
+	/// What the run did so far, if counting was enabled with [`Interpreter::with_stats`]. Only
+	/// stepping through the characters counts, not [`Interpreter::run_ir`].
+	pub fn stats(&self) -> Option<RunStats> {
+		let mut stats = self.stats.clone()?;
+		stats.highest_written = self.highest_written().unwrap_or(0);
+		stats.dirty_len = self.dirty_len();
+		stats.peak_loop_depth = self.max_stack_depth;
+		Some(stats)
+	}
+
+	/// Time spent waiting on the input reader, if enabled with [`Interpreter::with_input_timing`].
+	/// Queued input is free.
+	pub fn input_wait(&self) -> Option<Duration> {
+		self.input_wait
+	}
+
+	/// How much every loop ran, if enabled with [`Interpreter::with_loop_profile`].
+	pub fn loop_profile(&self) -> Option<&LoopProfile> {
+		self.loop_profile.as_ref()
+	}
+
+	/// Times each loop's body ran, keyed by the offset of its `[`, if enabled with
+	/// [`Interpreter::with_loop_profile`].
+	pub fn loop_iteration_counts(&self) -> Option<BTreeMap<usize, u64>> {
+		self.loop_profile.as_ref().map(LoopProfile::iteration_counts)
+	}
+
+	/// The tape up to the highest written cell.
+	pub fn tape(&self) -> &[u8] {
+		self.memory.written()
+	}
+
+	/// The highest cell written by the program or the initial tape, `None` if there was none.
+	pub fn highest_written(&self) -> Option<usize> {
+		self.memory.highest_written()
+	}
+
+	/// Length of the part of the tape in use, from cell 0 to the highest written one.
+	pub fn dirty_len(&self) -> usize {
+		self.highest_written().map_or(0, |highest| highest + 1)
+	}
+
+	/// The cells that aren't zero, with their addresses, in order.
+	pub fn nonzero_cells(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+		self.tape().iter().copied().enumerate().filter(|&(_, value)| value != 0)
+	}
+
+	pub fn get_data_ptr(&self) -> usize {
+		self.data_ptr
+	}
+
+	/// Hex dump of the written part of the tape, empty when nothing was written.
+	pub fn hex_dump(&self) -> String {
+		self.memory.hex_dump(0..self.highest_written().map_or(0, |last| last + 1))
+	}
+
+	/// Hex dump of the whole tape, untouched cells included.
+	pub fn hex_dump_full(&self) -> String {
+		self.memory.hex_dump(self.memory.full_range())
+	}
+
+	/// Hex dump of the cells in `range`, clamped to the tape.
+	pub fn hex_dump_range(&self, range: Range<usize>) -> String {
+		self.memory.hex_dump(range)
+	}
+
+	/// Writes the hex dump of the cells in `range`, clamped to the tape, to `out`.
+	pub fn dump_memory(&self, range: Range<usize>, out: &mut impl Write) -> std::io::Result<()> {
+		self.memory.dump(range, out)
+	}
+
+	/// Value of the cell at `address`, `None` past the end of the tape.
+	pub fn cell(&self, address: usize) -> Option<u8> {
+		self.memory.read(address).ok()
+	}
+
+	pub fn get_state(&self) -> InterpreterState {
+		self.state
+	}
+
+	/// Instruction pointers of the `[` of every loop currently being run, innermost last.
+	pub fn loop_stack(&self) -> &[usize] {
+		&self.stack
+	}
+
+	pub fn snapshot(&self) -> InterpreterSnapshot {
+		InterpreterSnapshot {
+			tape: self.memory.significant().to_vec(),
+			data_ptr: self.data_ptr,
+			instruction_ptr: self.instruction_ptr,
+			stack: self.stack.clone(),
+			state: self.state,
+		}
+	}
+
+	/// Most loops the loop stack held at once. Only stepping through the characters uses the stack.
+	pub fn max_stack_depth(&self) -> usize {
+		self.max_stack_depth
+	}
+
 	pub fn is_halted(&self) -> bool {
 		matches!(self.state, InterpreterState::Halted)
 	}
 
-	fn read_memory(&self) -> Result<u8, InterpreterError> {
+	/// Runs `characters` to the end. A program without instructions, empty, whitespace or comments
+	/// only, halts successfully without touching the tape.
+	pub fn run(&mut self, characters: &[char]) -> InterpreterResult {
+		self.run_observed(characters, |_| {})
+	}
+
+	/// Like [`Interpreter::run`], calling `on_step` after every executed instruction.
+	pub fn run_observed(&mut self, characters: &[char], mut on_step: impl FnMut(&Self)) -> InterpreterResult {
+		let symbols = InterpreterSymbol::tokenize(characters);
+		while !self.is_halted() {
+			let symbol = InterpreterSymbol::at(&symbols, self.instruction_ptr);
+			let executes = matches!(
+				(&self.state, &symbol),
+				(InterpreterState::Running, InterpreterSymbol::Instruction(_))
+			);
+			self.interpret_symbol(&symbol)?;
+			if executes {
+				on_step(self);
+			}
+		}
+		self.io.flush().ok();
+		Ok(())
+	}
+
+	/// Keeps `characters` for [`Interpreter::run_for`], rewinding to the first instruction. The
+	/// tape and data pointer are left as they are.
+	pub fn load(&mut self, characters: &[char]) {
+		self.reload(LoadedProgram::Symbols(InterpreterSymbol::tokenize(characters).into()));
+	}
+
+	/// Like [`Interpreter::load`], keeping the source bytes as they are instead of decoding them,
+	/// for programs too big to copy. Every byte is a character of its own, so offsets are byte
+	/// offsets, and bytes that aren't ASCII are comments.
+	pub fn load_bytes(&mut self, source: impl Into<Rc<[u8]>>) {
+		self.reload(LoadedProgram::Bytes(source.into()));
+	}
+
+	fn reload(&mut self, program: LoadedProgram) {
+		self.program = program;
+		self.instruction_ptr = 0;
+		self.stack.clear();
+		self.looping_back = false;
+		self.state = InterpreterState::Running;
+	}
+
+	/// Executes up to `steps` instructions of the [`Interpreter::load`]ed program, resuming where
+	/// the previous call stopped. Without a loaded program the machine halts right away.
+	pub fn run_for(&mut self, steps: u64) -> Result<SliceOutcome, InterpreterError> {
+		self.run_counted(steps).0
+	}
+
+	/// Runs `characters` to the end, summing up how it went instead of only telling whether it
+	/// failed.
+	pub fn execute(&mut self, characters: &[char]) -> ExecutionReport {
+		self.load(characters);
+		let (result, steps) = self.run_counted(u64::MAX);
+		ExecutionReport {
+			state: self.state,
+			steps,
+			error: result.err(),
+			output: self.captured.as_ref().map(CapturedOutput::contents),
+		}
+	}
+
+	/// [`Interpreter::run_for`], along with the instructions it executed.
+	fn run_counted(&mut self, steps: u64) -> (Result<SliceOutcome, InterpreterError>, u64) {
+		let program = self.program.clone();
+		let mut executed = 0u64;
+		while !self.is_halted() {
+			let symbol = program.at(self.instruction_ptr);
+			let executes = matches!(
+				(&self.state, &symbol),
+				(InterpreterState::Running, InterpreterSymbol::Instruction(_))
+			);
+			if executes {
+				if executed == steps {
+					self.flush_output();
+					return (Ok(SliceOutcome::OutOfSteps), executed);
+				}
+				executed += 1;
+			}
+			if let Err(err) = self.interpret_symbol(&symbol) {
+				self.flush_output();
+				return (Err(err), executed);
+			}
+		}
+		self.flush_output();
+		(Ok(SliceOutcome::Halted), executed)
+	}
+
+	/// Steps through `characters` as an iterator yielding an event per executed instruction, ending
+	/// once the program halts or right after yielding an error.
+	pub fn events<'a>(&'a mut self, characters: &'a [char]) -> Events<'a> {
+		Events::new(self, characters)
+	}
+
+	/// Runs until the program halts, returning `None`, or until a watchpoint fires, returning the
+	/// hit. The run resumes where it stopped when called again.
+	pub fn run_until_break(&mut self, characters: &[char]) -> Result<Option<WatchHit>, InterpreterError> {
+		self.watch_hit = None;
+		while !self.is_halted() {
+			self.step(characters)?;
+			if let Some(hit) = self.watch_hit.take() {
+				self.io.flush().ok();
+				return Ok(Some(hit));
+			}
+		}
+		self.io.flush().ok();
+		Ok(None)
+	}
+
+	pub(crate) fn read_memory(&self) -> Result<u8, InterpreterError> {
 		if let Ok(value) = self.memory.read(self.data_ptr) {
 			Ok(value)
 		} else {
@@ -55,26 +763,228 @@ impl Interpreter {
 		}
 	}
 
-	fn write_memory(&mut self, value: u8) -> Result<(), InterpreterError> {
-		self.memory.write(self.data_ptr, value).map_err(
+	pub(crate) fn write_memory(&mut self, value: u8) -> Result<(), InterpreterError> {
+		let address = self.data_ptr;
+		let old_value = self.memory.read(address).map_err(
+			|()| InterpreterError::ptr_out_of_bounds_from_interpreter(self)
+		)?;
+		if let Some(record) = self.pending_undo() {
+			record.cell.get_or_insert((address, old_value));
+		}
+		if let Some(stats) = &mut self.stats {
+			stats.count_cell_write(address);
+		}
+		if let Some(stalls) = &mut self.stalls {
+			stalls.effect();
+		}
+		if !self.watchpoints.is_empty() {
+			let fired = self.watchpoints.iter().find(|watchpoint| watchpoint.fires_on_write(address, old_value, value));
+			if let Some(&watchpoint) = fired {
+				self.watch_hit = Some(WatchHit {
+					watchpoint,
+					instruction_ptr: self.instruction_ptr,
+					old_value,
+					new_value: value,
+				});
+			}
+		}
+		self.memory.write(address, value).map_err(
 			|()| InterpreterError::ptr_out_of_bounds_from_interpreter(self)
 		)
 	}
 
+	/// Checks the read watchpoints for a `.` or `[` that just read `value` from the current cell,
+	/// and counts the read.
+	fn watch_read(&mut self, value: u8) {
+		if let Some(stats) = &mut self.stats {
+			stats.count_cell_read(self.data_ptr);
+		}
+		if self.watchpoints.is_empty() {
+			return;
+		}
+		let address = self.data_ptr;
+		if let Some(&watchpoint) = self.watchpoints.iter().find(|watchpoint| watchpoint.fires_on_read(address)) {
+			self.watch_hit = Some(WatchHit {
+				watchpoint,
+				instruction_ptr: self.instruction_ptr,
+				old_value: value,
+				new_value: value,
+			});
+		}
+	}
+
+	/// Moves the data pointer by `delta` cells at once, failing if that leaves the tape. The
+	/// pointer is then left at the edge, where moving one cell at a time would have failed.
+	pub(crate) fn move_ptr(&mut self, delta: isize) -> InterpreterResult {
+		match self.data_ptr.checked_add_signed(delta) {
+			Some(data_ptr) if data_ptr < self.memory.len() => {
+				self.data_ptr = data_ptr;
+				Ok(())
+			}
+			requested if delta > 0 => self.past_right_edge(requested.unwrap_or(usize::MAX)),
+			_ => {
+				self.data_ptr = 0;
+				InterpreterError::ptr_out_of_bounds_from_interpreter(self).into_result()
+			}
+		}
+	}
+
+	/// Runs `[>]` when `right`, `[<]` otherwise, in one search over the tape. Running off the tape
+	/// fails with the pointer at the edge, as stepping one cell at a time would.
+	pub(crate) fn scan(&mut self, right: bool) -> InterpreterResult {
+		match self.memory.find_zero(self.data_ptr, right) {
+			Some(address) => {
+				self.data_ptr = address;
+				Ok(())
+			}
+			None if right => self.past_right_edge(self.memory.len()),
+			None => {
+				self.data_ptr = 0;
+				InterpreterError::ptr_out_of_bounds_from_interpreter(self).into_result()
+			}
+		}
+	}
+
+	/// Fails a move to `requested`, past the last cell, leaving the pointer there. A tape cut short
+	/// by [`Interpreter::with_max_tape_cells`] tells it was the limit.
+	fn past_right_edge(&mut self, requested: usize) -> InterpreterResult {
+		let limit = self.memory.len();
+		self.data_ptr = limit - 1;
+		if limit < MEM_SIZE {
+			InterpreterError::tape_limit_exceeded(requested, limit).into_result()
+		} else {
+			InterpreterError::ptr_out_of_bounds_from_interpreter(self).into_result()
+		}
+	}
+
+	/// Runs the loop `mul_add` stands for in one go, see [`crate::ir::Op::MulAdd`]. Does nothing
+	/// if a cell would overflow or the loop would leave the tape, so the loop runs and fails as
+	/// written.
+	pub(crate) fn mul_add(&mut self, mul_add: &MulAdd) -> InterpreterResult {
+		let count = self.read_memory()?;
+		if count == 0 {
+			return Ok(());
+		}
+		let (data_ptr, tape_len) = (self.data_ptr, self.memory.len());
+		let address = |offset: isize| data_ptr.checked_add_signed(offset).filter(|&address| address < tape_len);
+		if address(mul_add.reach.0).is_none() || address(mul_add.reach.1).is_none() {
+			return Ok(());
+		}
+		let result = |memory: &InterpreterMemory, &(offset, factor): &(isize, i8)| {
+			let address = address(offset)?;
+			let value = i32::from(memory.read(address).ok()?) + i32::from(count) * i32::from(factor);
+			Some((address, u8::try_from(value).ok()?))
+		};
+		if !mul_add.targets.iter().all(|target| result(&self.memory, target).is_some()) {
+			return Ok(());
+		}
+
+		for target in &mul_add.targets {
+			if let Some((address, value)) = result(&self.memory, target) {
+				let _ = self.memory.write(address, value);
+			}
+		}
+		self.write_memory(0)
+	}
+
+	pub(crate) fn check_interrupt(&mut self) -> InterpreterResult {
+		if let Some(deadline) = &mut self.deadline {
+			if deadline.expired() {
+				return InterpreterError::timed_out().into_result();
+			}
+		}
+		match &self.interrupt {
+			Some(flag) if flag.load(Ordering::Relaxed) => InterpreterError::interrupted().into_result(),
+			_ => Ok(()),
+		}
+	}
+
+	pub(crate) fn flush_output(&mut self) {
+		self.io.flush().ok();
+	}
+
 	fn move_right(&mut self) -> InterpreterResult {
-		if self.data_ptr + 1 < MEM_SIZE {
+		if self.data_ptr + 1 < self.memory.len() {
 			self.data_ptr += 1;
 			Ok(())
 		} else {
-			InterpreterError::ptr_out_of_bounds_from_interpreter(self).to_result()
+			self.past_right_edge(self.data_ptr + 1)
+		}
+	}
+
+	/// Interprets the symbol at the current instruction pointer.
+	pub fn step(&mut self, characters: &[char]) -> InterpreterResult {
+		let symbol = InterpreterSymbol::from_char(characters.get(self.instruction_ptr));
+		self.interpret_symbol(&symbol)
+	}
+
+	/// Reverts the most recent recorded step, returning what it undid or `None` if there was
+	/// nothing left to revert.
+	///
+	/// Cells, pointers, the loop stack and consumed input are restored; output already written
+	/// can't be taken back.
+	pub fn step_back(&mut self) -> Option<RevertedStep> {
+		let record = self.history.as_mut().and_then(History::pop)?;
+		let cell = record.cell.map(|(address, value)| {
+			(address, value, self.memory.read(address).unwrap_or_default())
+		});
+
+		self.instruction_ptr = record.instruction_ptr;
+		self.data_ptr = record.data_ptr;
+		self.state = record.state;
+		self.stack.truncate(record.stack_len);
+		if let Some(popped) = record.popped {
+			self.stack.push(popped);
+		}
+		if let Some((address, value)) = record.cell {
+			// The address was written before, so it's in bounds
+			let _ = self.memory.write(address, value);
+		}
+		if let Some(byte) = record.read {
+			self.input_queue.push_front(byte);
 		}
+		Some(RevertedStep { instruction_ptr: record.instruction_ptr, cell, output: record.output })
+	}
+
+	/// Number of steps that can currently be reverted.
+	pub fn history_len(&self) -> usize {
+		self.history.as_ref().map_or(0, History::len)
 	}
 
 	pub fn interpret_symbol(&mut self, symbol: &InterpreterSymbol) -> InterpreterResult {
+		self.check_interrupt()?;
+		if let InterpreterSymbol::Instruction(_) = symbol {
+			self.mark_coverage(false);
+		}
+
+		if let Some(history) = &mut self.history {
+			history.begin(UndoRecord {
+				instruction_ptr: self.instruction_ptr,
+				data_ptr: self.data_ptr,
+				state: self.state,
+				stack_len: self.stack.len(),
+				popped: None,
+				cell: None,
+				read: None,
+				output: None,
+			});
+		}
+		let result = self.dispatch_symbol(symbol);
+		if let Some(history) = &mut self.history {
+			history.commit();
+		}
+		result
+	}
+
+	fn pending_undo(&mut self) -> Option<&mut UndoRecord> {
+		self.history.as_mut()?.pending()
+	}
+
+	fn dispatch_symbol(&mut self, symbol: &InterpreterSymbol) -> InterpreterResult {
 		let state = &self.state;
 
 		match (state, symbol) {
-			(InterpreterState::Halted, _) => InterpreterError::halted_machine().to_result(),
+			(InterpreterState::Halted, _) => InterpreterError::halted_machine().into_result(),
 			(InterpreterState::Skipping(skip), InterpreterSymbol::Instruction(InterpreterInstruction::LoopEnd)) => {
 				let skip = skip - 1;
 				if skip > 0 {
@@ -85,8 +995,8 @@ impl Interpreter {
 				self.next_instruction();
 				Ok(())
 			}
-			(InterpreterState::Skipping(_), InterpreterSymbol::EOF) => {
-				InterpreterError::mismatched_brackets(self).to_result()
+			(InterpreterState::Skipping(_), InterpreterSymbol::Eof) => {
+				InterpreterError::mismatched_brackets(self).into_result()
 			}
 			(InterpreterState::Skipping(skip), InterpreterSymbol::Instruction(InterpreterInstruction::LoopStart)) => {
 				self.state = InterpreterState::Skipping(skip + 1);
@@ -97,7 +1007,17 @@ impl Interpreter {
 				self.next_instruction();
 				Ok(())
 			}
-			(InterpreterState::Running, InterpreterSymbol::EOF) => {
+			(InterpreterState::Running, InterpreterSymbol::Other(character)) if self.debug_char == Some(*character) => {
+				self.flush_output();
+				eprintln!("\n{character} at {}: data pointer {}, tape {}", self.instruction_ptr, self.data_ptr, self.memory);
+				self.next_instruction();
+				Ok(())
+			}
+			// Running off the end inside a loop, which never got its `]`
+			(InterpreterState::Running, InterpreterSymbol::Eof) if !self.stack.is_empty() => {
+				InterpreterError::mismatched_brackets(self).into_result()
+			}
+			(InterpreterState::Running, InterpreterSymbol::Eof) => {
 				self.halt();
 				Ok(())
 			}
@@ -117,17 +1037,24 @@ impl Interpreter {
 			self.data_ptr -= 1;
 			Ok(())
 		} else {
-			InterpreterError::ptr_out_of_bounds_from_interpreter(self).to_result()
+			InterpreterError::ptr_out_of_bounds_from_interpreter(self).into_result()
 		}
 	}
 
-	fn delta_data_cell(&mut self, delta: i8) -> InterpreterResult {
+	/// Adds `delta` to the current cell. A folded run of `+` or `-` that overflows leaves the cell
+	/// at the edge and reports a single step, as running them one at a time would.
+	pub(crate) fn delta_data_cell(&mut self, delta: i8) -> InterpreterResult {
 		let val = self.read_memory()?;
-		let new_val = math_utils::safe_delta_u8(val, delta).map_err(
-			|delta_error|
-				InterpreterError::val_out_of_bounds(self.data_ptr, delta_error.right)
-		)?;
-		self.write_memory(new_val)
+		match math_utils::safe_delta_u8(val, delta) {
+			Ok(new_val) => self.write_memory(new_val),
+			Err(delta_error) => {
+				let edge = if delta > 0 { u8::MAX } else { u8::MIN };
+				if val != edge {
+					self.write_memory(edge)?;
+				}
+				InterpreterError::val_out_of_bounds(self.data_ptr, delta_error.right.signum()).into_result()
+			}
+		}
 	}
 
 
@@ -140,47 +1067,156 @@ impl Interpreter {
 	}
 
 
-	fn print_ptr(&mut self) -> InterpreterResult {
+	/// Prints the current cell. The pointer can't be off the tape, moves stop at its edges with an
+	/// error, so the edge cells print like any other; the read is still checked rather than trusted.
+	pub(crate) fn print_ptr(&mut self) -> InterpreterResult {
+		if let Some(limit) = self.output_limit.filter(|&limit| self.printed >= limit) {
+			self.flush_output();
+			return InterpreterError::output_limit_exceeded(limit).into_result();
+		}
 		if let Ok(val) = self.read_memory() {
-			if let Some(_printed_string) = error::print_char(val) {
+			self.watch_read(val);
+			let printed = self.io.print_char(val);
+			if let Some(_printed_string) = printed {
+				self.printed = self.printed.saturating_add(1);
+				if let Some(stats) = &mut self.stats {
+					stats.count_written();
+				}
+				if let Some(detector) = &mut self.hot_loops {
+					detector.io();
+				}
+				if let Some(stalls) = &mut self.stalls {
+					stalls.effect();
+				}
+				if let Some(record) = self.pending_undo() {
+					record.output = Some(val);
+				}
 				Ok(())
 			} else {
-				InterpreterError::unprintable_byte(val).to_result()
+				InterpreterError::unprintable_byte(val).into_result()
 			}
 		} else {
-			InterpreterError::ptr_out_of_bounds_from_interpreter(self).to_result()
+			InterpreterError::ptr_out_of_bounds_from_interpreter(self).into_result()
+		}
+	}
+
+	fn next_input_byte(&mut self) -> Option<u8> {
+		if let Some(byte) = self.input_queue.pop_front() {
+			return Some(byte);
+		}
+		if !self.io.has_input() {
+			return None;
+		}
+		// A prompt printed before the read has to show up before the program blocks on it
+		self.io.flush().ok();
+		if self.input_wait.is_none() && self.deadline.is_none() {
+			return self.io.read_byte();
+		}
+		let start = Instant::now();
+		let byte = self.io.read_byte();
+		let wait = start.elapsed();
+		if let Some(input_wait) = &mut self.input_wait {
+			*input_wait += wait;
+		}
+		if let Some(deadline) = &mut self.deadline {
+			deadline.waited(wait);
 		}
+		byte
 	}
 
-	fn read_ptr(&mut self) -> InterpreterResult {
-		if let Some(byte) = error::read_byte() {
-			self.write_memory(byte)
+	pub(crate) fn read_ptr(&mut self) -> InterpreterResult {
+		let byte = if let Some(byte) = self.next_input_byte() {
+			if let Some(stats) = &mut self.stats {
+				stats.count_read();
+			}
+			if let Some(detector) = &mut self.hot_loops {
+				detector.io();
+			}
+			if let Some(stalls) = &mut self.stalls {
+				stalls.effect();
+			}
+			if let Some(record) = self.pending_undo() {
+				record.read = Some(byte);
+			}
+			if self.echo_input {
+				self.io.write_all(&[byte]).ok();
+			}
+			byte
 		} else {
-			InterpreterError::invalid_char().to_result()
+			match self.eof_policy {
+				EofPolicy::Error => return InterpreterError::invalid_char().into_result(),
+				EofPolicy::Zero => 0,
+				EofPolicy::Unchanged => return Ok(()),
+			}
+		};
+
+		if let Some(recorder) = &self.input_recorder {
+			recorder.borrow_mut().write_all(&[byte]).ok();
 		}
+		self.write_memory(byte)
 	}
 
 	fn enter_loop(&mut self) -> InterpreterResult {
 		if let Ok(val) = self.read_memory() {
+			self.watch_read(val);
+			let looping_back = std::mem::take(&mut self.looping_back);
+			if let Some(profile) = &mut self.loop_profile {
+				profile.reach(self.instruction_ptr, val != 0, looping_back);
+			}
+			if let Some(detector) = &mut self.hot_loops {
+				if val != 0 {
+					detector.iterate(self.instruction_ptr, looping_back);
+				} else if looping_back {
+					detector.exit(self.instruction_ptr);
+				}
+			}
+			if let Some(cap) = &mut self.iteration_cap {
+				let capped = if val != 0 {
+					cap.iterate(self.instruction_ptr, looping_back)
+				} else {
+					cap.exit(self.instruction_ptr);
+					Ok(())
+				};
+				if let Err(iterations) = capped {
+					return InterpreterError::loop_iteration_limit(self.instruction_ptr, iterations).into_result();
+				}
+			}
+			if let Some(stalls) = &mut self.stalls {
+				if val != 0 && stalls.iterate(self.stack.len(), self.instruction_ptr, self.data_ptr, looping_back) {
+					return InterpreterError::infinite_loop_detected(self.instruction_ptr).into_result();
+				}
+			}
+			if val != 0 && self.stack.len() >= self.max_loop_depth {
+				return InterpreterError::loop_depth_exceeded(self.max_loop_depth).into_result();
+			}
 			let next_state = if val != 0 {
 				self.stack.push(self.instruction_ptr);
+				self.max_stack_depth = self.max_stack_depth.max(self.stack.len());
+				if let Some(stats) = &mut self.stats {
+					stats.count_loop_iteration();
+				}
 				InterpreterState::Running
 			} else {
+				self.skip_start = self.instruction_ptr;
 				InterpreterState::Skipping(1)
 			};
 			self.state = next_state;
 			Ok(())
 		} else {
-			InterpreterError::ptr_out_of_bounds_from_interpreter(self).to_result()
+			InterpreterError::ptr_out_of_bounds_from_interpreter(self).into_result()
 		}
 	}
 
 	fn exit_loop(&mut self) -> InterpreterResult {
 		if let Some(loop_ptr) = self.stack.pop() {
+			if let Some(record) = self.pending_undo() {
+				record.popped = Some(loop_ptr);
+			}
+			self.looping_back = true;
 			self.instruction_ptr = loop_ptr;
 			Ok(())
 		} else {
-			InterpreterError::stack_underflow().to_result()
+			InterpreterError::stack_underflow().into_result()
 		}
 	}
 
@@ -188,11 +1224,22 @@ impl Interpreter {
 		self.instruction_ptr += 1;
 	}
 
-	fn halt(&mut self) {
+	pub(crate) fn halt(&mut self) {
 		self.state = InterpreterState::Halted;
 	}
 
+	fn mark_coverage(&mut self, executed: bool) {
+		if let Some(coverage) = &mut self.coverage {
+			if coverage.len() <= self.instruction_ptr {
+				coverage.resize(self.instruction_ptr + 1, None);
+			}
+			let mark = &mut coverage[self.instruction_ptr];
+			*mark = Some(mark.unwrap_or(0).saturating_add(u64::from(executed)));
+		}
+	}
+
 	fn run_instruction(&mut self, instruction: &InterpreterInstruction) -> InterpreterResult {
+		self.mark_coverage(true);
 		let (advance, result) = match instruction {
 			InterpreterInstruction::MovePtrRight => (true, self.move_right()),
 			InterpreterInstruction::MovePtrLeft => (true, self.move_left()),
@@ -203,9 +1250,351 @@ impl Interpreter {
 			InterpreterInstruction::LoopStart => (true, self.enter_loop()),
 			InterpreterInstruction::LoopEnd => (false, self.exit_loop())
 		};
+		if let Some(stats) = &mut self.stats {
+			stats.count(*instruction, self.data_ptr);
+		}
 		if advance && result.is_ok() {
 			self.next_instruction();
 		}
 		result
 	}
-}
\ No newline at end of file
+}
+
+impl Default for Interpreter {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Debug for Interpreter {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Interpreter")
+			.field("memory", &self.memory)
+			.field("data_ptr", &self.data_ptr)
+			.field("instruction_ptr", &self.instruction_ptr)
+			.field("stack", &self.stack)
+			.field("state", &self.state)
+			.field("eof_policy", &self.eof_policy)
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use error::InterpreterErrorReason;
+
+	/// A machine printing into a buffer, which `,` reads nothing from.
+	fn interpreter() -> (Interpreter, CapturedOutput) {
+		let output = CapturedOutput::new();
+		(Interpreter::with_output(output.clone()), output)
+	}
+
+	fn chars(program: &str) -> Vec<char> {
+		program.chars().collect()
+	}
+
+	#[test]
+	fn programs_without_instructions_halt() {
+		for program in ["", " \n\t\n", "a comment and no instructions\n"] {
+			let (mut bf_interpreter, output) = interpreter();
+			assert!(bf_interpreter.run(&chars(program)).is_ok(), "{program:?}");
+			assert!(bf_interpreter.is_halted(), "{program:?}");
+			let snapshot = bf_interpreter.snapshot();
+			assert!(snapshot.tape.is_empty() && snapshot.data_ptr == 0 && snapshot.stack.is_empty(), "{program:?}");
+			assert!(output.contents().is_empty());
+		}
+	}
+
+	#[test]
+	fn empty_program_halts_when_stepped() {
+		let (mut bf_interpreter, _) = interpreter();
+		bf_interpreter.load_bytes(&b""[..]);
+		assert!(matches!(bf_interpreter.run_for(10), Ok(SliceOutcome::Halted)));
+		let (mut bf_interpreter, _) = interpreter();
+		assert!(bf_interpreter.step(&[]).is_ok());
+		assert!(bf_interpreter.is_halted());
+	}
+
+	#[test]
+	fn runs_to_the_same_machine_compare_equal() {
+		let (mut first, _) = interpreter();
+		let (mut second, _) = interpreter();
+		first.run(&chars("++>+++<[->+<]>")).unwrap();
+		second.run(&chars(">+++++")).unwrap();
+		assert_ne!(first.snapshot(), second.snapshot());
+		// Only the instruction pointer tells them apart
+		assert_eq!(first.snapshot().tape, second.snapshot().tape);
+		assert_eq!(first.memory, second.memory);
+	}
+
+	#[test]
+	fn cells_written_back_to_zero_compare_equal() {
+		let (mut written, _) = interpreter();
+		written.run(&chars(">>+-<<")).unwrap();
+		let (untouched, _) = interpreter();
+		assert_eq!(written.memory, untouched.memory);
+		assert_eq!(written.snapshot().tape, untouched.snapshot().tape);
+
+		let (mut different, _) = interpreter();
+		different.run(&chars(">>+<<")).unwrap();
+		assert_ne!(written.memory, different.memory);
+	}
+
+	#[test]
+	fn print_at_the_tape_edges() {
+		let tapes = [
+			("full tape", Interpreter::with_output(CapturedOutput::new()), MEM_SIZE),
+			("restricted", Interpreter::with_output(CapturedOutput::new()).with_max_tape_cells(16), 16),
+			("configured", Interpreter::with_output(CapturedOutput::new()).configured(InterpreterConfig {
+				tape_cells: 1,
+				..InterpreterConfig::default()
+			}).unwrap(), 1),
+		];
+		for (name, bf_interpreter, cells) in tapes {
+			// Cell 0 holds 'A', the last one 'B'
+			let output = CapturedOutput::new();
+			let mut bf_interpreter = bf_interpreter.with_output_sink(output.clone())
+				.with_initial_tape(b"A").unwrap();
+			let mut program = ".".to_string();
+			if cells > 1 {
+				program += &">".repeat(cells - 1);
+				program += "+".repeat(usize::from(b'B')).as_str();
+			} else {
+				program += &"+".repeat(usize::from(b'B' - b'A'));
+			}
+			// Past the last cell the move fails rather than `.` printing anything
+			program += ".>.";
+			assert!(bf_interpreter.run(&chars(&program)).is_err(), "{name}");
+			assert_eq!(output.contents(), b"AB", "{name}");
+			assert_eq!(bf_interpreter.get_data_ptr(), cells - 1, "{name}");
+		}
+	}
+
+	#[test]
+	fn unclosed_loops_fail_at_the_end() {
+		// Skipped because the cell is zero, and entered because it isn't
+		for (program, loop_start) in [("[[]", 0), ("+>+[", 3)] {
+			let (mut bf_interpreter, _) = interpreter();
+			let err = bf_interpreter.run(&chars(program)).unwrap_err();
+			match err.reason {
+				InterpreterErrorReason::MismatchedBrackets(brackets) => assert_eq!(brackets.unmatched_loop_start, loop_start, "{program}"),
+				reason => panic!("{program}: expected mismatched brackets, got {reason:?}"),
+			}
+		}
+	}
+
+	#[test]
+	fn mismatched_brackets_outside_a_loop_is_an_error() {
+		let (bf_interpreter, _) = interpreter();
+		let err = InterpreterError::mismatched_brackets(&bf_interpreter);
+		assert!(matches!(err.reason, InterpreterErrorReason::NoOpenLoop));
+	}
+
+	#[test]
+	fn configured_start_is_where_moves_count_from() {
+		let config = InterpreterConfig { tape_cells: 8, start_ptr: 5, ..InterpreterConfig::default() };
+		let (bf_interpreter, _) = interpreter();
+		let mut bf_interpreter = bf_interpreter.configured(config).unwrap();
+		bf_interpreter.run(&chars(">+<<+")).unwrap();
+		assert_eq!(bf_interpreter.get_data_ptr(), 4);
+		assert_eq!(bf_interpreter.tape()[4..7], [1, 0, 1]);
+	}
+
+	#[test]
+	fn configured_start_off_the_tape_is_rejected() {
+		for (tape_cells, start_ptr) in [(8, 8), (MEM_SIZE, MEM_SIZE), (1, 1)] {
+			let config = InterpreterConfig { tape_cells, start_ptr, ..InterpreterConfig::default() };
+			let err = Interpreter::with_output(CapturedOutput::new()).configured(config).unwrap_err();
+			assert!(matches!(err.reason, InterpreterErrorReason::PtrOutOfBounds(ptr) if ptr == start_ptr));
+		}
+	}
+
+	#[test]
+	fn stepping_back_restores_every_earlier_state() {
+		let program = chars(",[->+>+<<]>>[-<+>]<.");
+		let (bf_interpreter, output) = interpreter();
+		let mut bf_interpreter = bf_interpreter.with_history(1000);
+		bf_interpreter.feed_input(&[3]);
+
+		let mut snapshots = vec![bf_interpreter.snapshot()];
+		while !bf_interpreter.is_halted() {
+			bf_interpreter.step(&program).unwrap();
+			snapshots.push(bf_interpreter.snapshot());
+		}
+		assert_eq!(output.contents(), [6]);
+
+		let last = snapshots.pop().unwrap();
+		let mut un_output = Vec::new();
+		while let Some(snapshot) = snapshots.pop() {
+			let reverted = bf_interpreter.step_back().unwrap();
+			assert_eq!(bf_interpreter.snapshot(), snapshot);
+			un_output.extend(reverted.output);
+		}
+		assert!(bf_interpreter.step_back().is_none());
+		assert_eq!(un_output, [6]);
+
+		// The input byte went back to be read again
+		bf_interpreter.run(&program).unwrap();
+		assert_eq!(bf_interpreter.snapshot(), last);
+	}
+
+	#[test]
+	fn hex_dumps_end_at_the_highest_written_cell() {
+		let (mut bf_interpreter, _) = interpreter();
+		assert_eq!(bf_interpreter.hex_dump(), "");
+		bf_interpreter.run(&chars("+")).unwrap();
+		assert_eq!(bf_interpreter.hex_dump(), "00000000: 01                                       .\n");
+
+		let (mut bf_interpreter, _) = interpreter();
+		let program = format!("{}>{}+", "+".repeat(72), ">".repeat(16));
+		bf_interpreter.run(&chars(&program)).unwrap();
+		assert_eq!(
+			bf_interpreter.hex_dump(),
+			concat!(
+				"00000000: 4800 0000 0000 0000 0000 0000 0000 0000  H...............\n",
+				"00000010: 0001                                     ..\n",
+			)
+		);
+		assert_eq!(
+			bf_interpreter.hex_dump_range(15..40),
+			concat!(
+				"0000000f: 0000 0100 0000 0000 0000 0000 0000 0000  ................\n",
+				"0000001f: 0000 0000 0000 0000 00                   .........\n",
+			)
+		);
+		assert_eq!(bf_interpreter.hex_dump_range(30_000..30_016), "");
+	}
+
+	#[test]
+	fn clones_run_on_independently() {
+		let (mut original, output) = interpreter();
+		original.feed_input(b"\x15b");
+		original.load(&chars(",[>+++<-]>.<,."));
+		assert!(matches!(original.run_for(6), Ok(SliceOutcome::OutOfSteps)));
+
+		let mut copy = original.clone();
+		let halfway = original.snapshot();
+		assert_eq!(copy.snapshot(), halfway);
+		assert_eq!(copy.run_for(u64::MAX).unwrap(), SliceOutcome::Halted);
+		assert_eq!(original.snapshot(), halfway, "the copy's run left the original alone");
+
+		// Each took its own copy of the queued input
+		assert!(matches!(original.run_for(4), Ok(SliceOutcome::OutOfSteps)));
+		assert_ne!(original.snapshot(), copy.snapshot());
+		assert_eq!(original.run_for(u64::MAX).unwrap(), SliceOutcome::Halted);
+		assert_eq!(original.snapshot(), copy.snapshot());
+		assert_eq!(output.contents(), b"?b?b");
+	}
+
+	#[test]
+	fn tape_lives_outside_the_struct() {
+		// Inline, the tape alone would be MEM_SIZE bytes
+		assert!(std::mem::size_of::<Interpreter>() < MEM_SIZE / 10, "{}", std::mem::size_of::<Interpreter>());
+		let (mut bf_interpreter, _) = interpreter();
+		bf_interpreter.run(&chars("+")).unwrap();
+		let moved = Box::new(bf_interpreter);
+		assert_eq!(moved.tape(), [1]);
+	}
+
+	#[test]
+	fn used_tape_reaches_the_highest_written_cell() {
+		let (mut bf_interpreter, _) = interpreter();
+		let program = format!("+>>{}+++>>", ">".repeat(309));
+		bf_interpreter.run(&chars(&program)).unwrap();
+		assert_eq!(bf_interpreter.highest_written(), Some(311));
+		assert_eq!(bf_interpreter.dirty_len(), 312);
+		assert_eq!(bf_interpreter.nonzero_cells().collect::<Vec<_>>(), [(0, 1), (311, 3)]);
+		// The pointer went past the last cell written
+		assert_eq!(bf_interpreter.get_data_ptr(), 313);
+		assert!(bf_interpreter.memory.to_string().ends_with(".00.00.03.]"));
+
+		let (mut bf_interpreter, _) = interpreter();
+		bf_interpreter.run(&chars("+>++")).unwrap();
+		assert_eq!(bf_interpreter.memory.to_string(), "[01.02.]");
+	}
+
+	#[test]
+	fn untouched_tapes_use_no_cells() {
+		let (mut bf_interpreter, _) = interpreter();
+		bf_interpreter.run(&chars(">>><")).unwrap();
+		assert_eq!(bf_interpreter.highest_written(), None);
+		assert_eq!(bf_interpreter.dirty_len(), 0);
+		assert_eq!(bf_interpreter.nonzero_cells().count(), 0);
+		assert_eq!(bf_interpreter.memory.to_string(), "[]");
+
+		let mut bf_interpreter = Interpreter::with_output(CapturedOutput::new()).with_stats();
+		bf_interpreter.run(&chars(">")).unwrap();
+		let stats = bf_interpreter.stats().unwrap();
+		assert_eq!((stats.highest_written, stats.dirty_len, stats.peak_data_ptr), (0, 0, 1));
+	}
+
+	#[test]
+	fn slices_resume_where_the_last_one_stopped() {
+		let (mut bf_interpreter, output) = interpreter();
+		bf_interpreter.load(&chars("+++[>++<-]>."));
+		let mut slices = 0;
+		while bf_interpreter.run_for(4).unwrap() == SliceOutcome::OutOfSteps {
+			slices += 1;
+			assert_eq!(output.contents(), b"", "printed before the end, after {slices} slices");
+		}
+		// 24 instructions: 4 before the loop, 6 per iteration but the last, 7 for it and 2 after
+		assert_eq!(slices, 6);
+		assert_eq!(output.contents(), [6]);
+		assert_eq!(bf_interpreter.run_for(4).unwrap(), SliceOutcome::Halted);
+
+		// Stopped inside the loop, with its `[` on the stack
+		let (mut bf_interpreter, _) = interpreter();
+		bf_interpreter.load(&chars("+++[>++<-]>."));
+		assert_eq!(bf_interpreter.run_for(6).unwrap(), SliceOutcome::OutOfSteps);
+		let snapshot = bf_interpreter.snapshot();
+		assert_eq!((snapshot.data_ptr, snapshot.instruction_ptr, &snapshot.stack[..]), (1, 6, &[3][..]));
+		assert_eq!(snapshot.tape, [3, 1]);
+	}
+
+	#[test]
+	fn failing_slices_return_the_error() {
+		let (mut bf_interpreter, _) = interpreter();
+		bf_interpreter.load(&chars("++<"));
+		assert_eq!(bf_interpreter.run_for(2).unwrap(), SliceOutcome::OutOfSteps);
+		let err = bf_interpreter.run_for(2).unwrap_err();
+		assert!(matches!(err.reason, InterpreterErrorReason::PtrOutOfBounds(_)), "{err:?}");
+	}
+
+	#[test]
+	fn endless_printers_stop_at_the_output_limit() {
+		let (output, result) = Interpreter::run_with_input("+[.]", b"", Some(100));
+		assert_eq!(output, [1; 100]);
+		assert!(matches!(result.unwrap_err().reason, InterpreterErrorReason::OutputLimitExceeded { limit: 100 }));
+
+		// Up to the limit is fine
+		let (output, result) = Interpreter::run_with_input("+....", b"", Some(4));
+		assert_eq!((output.len(), result.is_ok()), (4, true));
+
+		for backend in [crate::backend::Backend::Ir, crate::backend::Backend::Closures] {
+			let (bf_interpreter, output) = interpreter();
+			let mut bf_interpreter = bf_interpreter.with_output_limit(7);
+			let err = backend.run(&mut bf_interpreter, &chars("+[.]")).unwrap_err();
+			assert!(matches!(err.reason, InterpreterErrorReason::OutputLimitExceeded { limit: 7 }), "{backend:?}: {err:?}");
+			assert_eq!(output.contents(), [1; 7], "{backend:?}");
+		}
+	}
+
+	#[test]
+	fn nesting_past_the_loop_depth_fails_at_its_bracket() {
+		let program = chars(&format!("+{}-{}", "[".repeat(20), "]".repeat(20)));
+		assert_eq!(crate::ir::Ir::compile(&program, 0).unwrap().max_depth(), 20);
+
+		let (bf_interpreter, _) = interpreter();
+		let mut bf_interpreter = bf_interpreter.with_max_loop_depth(20);
+		bf_interpreter.run(&program).unwrap();
+
+		let (bf_interpreter, _) = interpreter();
+		let mut bf_interpreter = bf_interpreter.with_max_loop_depth(19);
+		let err = bf_interpreter.run(&program).unwrap_err();
+		assert!(matches!(err.reason, InterpreterErrorReason::LoopDepthExceeded { depth: 19 }), "{err:?}");
+		// The 20th `[`, with the 19 before it entered
+		assert_eq!(bf_interpreter.get_instruction_ptr(), 20);
+		assert_eq!(bf_interpreter.snapshot().stack.len(), 19);
+	}
+}
@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Stops a run from another thread, see [`Interpreter::with_cancellation`]. Clones share the same
+/// flag.
+///
+/// A `,` blocked on a reader isn't woken up, the run stops once the read returns. Input queued
+/// with [`Interpreter::feed_input`] never blocks.
+///
+/// [`Interpreter::with_cancellation`]: crate::interpreter::Interpreter::with_cancellation
+/// [`Interpreter::feed_input`]: crate::interpreter::Interpreter::feed_input
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+	flag: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn cancel(&self) {
+		self.flag.store(true, Ordering::Relaxed);
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		self.flag.load(Ordering::Relaxed)
+	}
+
+	/// Lets a cancelled run be resumed.
+	pub fn reset(&self) {
+		self.flag.store(false, Ordering::Relaxed);
+	}
+
+	pub(crate) fn flag(&self) -> Arc<AtomicBool> {
+		Arc::clone(&self.flag)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+	use super::*;
+	use crate::interpreter::error::InterpreterErrorReason;
+	use crate::interpreter::io::CapturedOutput;
+	use crate::interpreter::{Interpreter, SliceOutcome};
+
+	#[test]
+	fn runs_cancelled_from_another_thread_stop_and_resume() {
+		let token = CancellationToken::new();
+		let (started, running) = std::sync::mpsc::channel();
+		let runner = {
+			let token = token.clone();
+			std::thread::spawn(move || {
+				let output = CapturedOutput::new();
+				let mut interpreter = Interpreter::with_output(output.clone()).with_cancellation(&token);
+				interpreter.load(&"+.>++<[]".chars().collect::<Vec<_>>());
+				started.send(()).unwrap();
+				let err = interpreter.run_for(u64::MAX).unwrap_err();
+				let stopped = interpreter.snapshot();
+
+				// Going on from there once the token is reset
+				token.reset();
+				let resumed = interpreter.run_for(100).unwrap();
+				(err.reason, output.contents(), stopped, resumed, interpreter.snapshot())
+			})
+		};
+		running.recv().unwrap();
+		std::thread::sleep(Duration::from_millis(20));
+		let cancelled = Instant::now();
+		token.cancel();
+		let (reason, output, stopped, resumed, after) = runner.join().unwrap();
+		assert!(cancelled.elapsed() < Duration::from_secs(1), "{:?}", cancelled.elapsed());
+
+		assert!(matches!(reason, InterpreterErrorReason::Interrupted), "{reason:?}");
+		assert_eq!(output, [1]);
+		// Stopped in the loop, at its `[` or `]`
+		assert_eq!((&stopped.tape[..], stopped.data_ptr), (&[1, 2][..], 0));
+		assert!(matches!(stopped.instruction_ptr, 6 | 7), "{stopped:?}");
+		assert_eq!(resumed, SliceOutcome::OutOfSteps);
+		assert_eq!((&after.tape, after.data_ptr), (&stopped.tape, stopped.data_ptr));
+		assert!(matches!(after.instruction_ptr, 6 | 7), "{after:?}");
+	}
+}
@@ -0,0 +1,45 @@
+//! Every setting of an interpreter in one place, for [`Interpreter::with_config`] and
+//! [`Interpreter::configured`]. Each field does what the builder of the same name does.
+//!
+//! [`Interpreter::with_config`]: crate::interpreter::Interpreter::with_config
+//! [`Interpreter::configured`]: crate::interpreter::Interpreter::configured
+
+use std::time::Duration;
+
+use crate::interpreter::{EofPolicy, DEFAULT_MAX_LOOP_DEPTH, MEM_SIZE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterpreterConfig {
+	pub eof_policy: EofPolicy,
+	/// Cells of the tape, at most [`MEM_SIZE`].
+	pub tape_cells: usize,
+	/// Cell the data pointer starts on, which has to be within the `tape_cells`.
+	pub start_ptr: usize,
+	pub max_loop_depth: usize,
+	pub max_loop_iterations: Option<u64>,
+	pub max_output: Option<u64>,
+	pub timeout: Option<Duration>,
+	/// Whether time blocked reading input counts towards `timeout`.
+	pub timeout_includes_input: bool,
+	pub echo_input: bool,
+	pub debug_char: Option<char>,
+	pub detect_infinite_loops: bool,
+}
+
+impl Default for InterpreterConfig {
+	fn default() -> Self {
+		InterpreterConfig {
+			eof_policy: EofPolicy::default(),
+			tape_cells: MEM_SIZE,
+			start_ptr: 0,
+			max_loop_depth: DEFAULT_MAX_LOOP_DEPTH,
+			max_loop_iterations: None,
+			max_output: None,
+			timeout: None,
+			timeout_includes_input: false,
+			echo_input: false,
+			debug_char: None,
+			detect_infinite_loops: false,
+		}
+	}
+}
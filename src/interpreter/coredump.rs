@@ -0,0 +1,206 @@
+//! Post-mortem dumps of failed runs, written by `run --coredump` and opened read-only by
+//! `debug --core`.
+//!
+//! Layout: the [`MAGIC`] bytes and a format version byte, then varints: the program hash, the
+//! error as a length and UTF-8 bytes, the data pointer, the instruction pointer, the state (0
+//! running, 1 skipping followed by the depth, 2 halted), the loop stack as a length and entries,
+//! the written part of the tape as a length and raw bytes, and the recent steps as a count and,
+//! per step, the instruction character, instruction pointer, data pointer and cell.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::digest::Fnv1a;
+use crate::interpreter::error::InterpreterError;
+use crate::interpreter::events::StepEvent;
+use crate::interpreter::{Interpreter, InterpreterState, MEM_SIZE};
+use crate::ir::bytecode::{write_varint, ByteReader, LoadError};
+use crate::symbol::InterpreterSymbol;
+
+pub const MAGIC: [u8; 4] = *b"BFCR";
+pub const FORMAT_VERSION: u8 = 1;
+/// Steps kept for the dump unless configured otherwise.
+pub const DEFAULT_RECENT_STEPS: usize = 32;
+
+const STATE_RUNNING: u8 = 0;
+const STATE_SKIPPING: u8 = 1;
+const STATE_HALTED: u8 = 2;
+
+/// FNV-1a over the UTF-8 encoding of the program, telling whether a dump belongs to it.
+pub fn program_hash(characters: &[char]) -> u64 {
+	let mut hasher = Fnv1a::new();
+	let mut buffer = [0u8; 4];
+	for character in characters {
+		hasher.write(character.encode_utf8(&mut buffer).as_bytes());
+	}
+	hasher.finish()
+}
+
+/// Ring buffer of the most recently executed steps.
+pub struct RecentSteps {
+	capacity: usize,
+	steps: VecDeque<StepEvent>,
+}
+
+impl RecentSteps {
+	pub fn new(capacity: usize) -> Self {
+		RecentSteps { capacity, steps: VecDeque::with_capacity(capacity) }
+	}
+
+	pub fn push(&mut self, step: StepEvent) {
+		if self.steps.len() == self.capacity {
+			self.steps.pop_front();
+		}
+		if self.capacity > 0 {
+			self.steps.push_back(step);
+		}
+	}
+}
+
+/// The machine frozen where a run failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreDump {
+	/// [`program_hash`] of the program that ran.
+	pub program_hash: u64,
+	/// Debug rendering of the error reason.
+	pub error: String,
+	pub data_ptr: usize,
+	pub instruction_ptr: usize,
+	pub state: InterpreterState,
+	pub loop_stack: Vec<usize>,
+	/// The written part of the tape, the rest being zero.
+	pub tape: Vec<u8>,
+	/// The last executed steps, oldest first.
+	pub recent: Vec<StepEvent>,
+}
+
+impl CoreDump {
+	pub fn capture(interpreter: &Interpreter, characters: &[char], error: &InterpreterError, recent: &RecentSteps) -> Self {
+		CoreDump {
+			program_hash: program_hash(characters),
+			error: format!("{:?}", error.reason),
+			data_ptr: interpreter.data_ptr,
+			instruction_ptr: interpreter.instruction_ptr,
+			state: interpreter.state,
+			loop_stack: interpreter.stack.clone(),
+			tape: interpreter.tape().to_vec(),
+			recent: recent.steps.iter().copied().collect(),
+		}
+	}
+
+	/// An interpreter in the dumped state, printing to `output`.
+	pub fn restore(&self, output: impl Write + 'static) -> Interpreter {
+		let mut interpreter = Interpreter::with_output(output)
+			.with_initial_tape(&self.tape)
+			.expect("Loaded dumps fit the tape");
+		interpreter.data_ptr = self.data_ptr;
+		interpreter.instruction_ptr = self.instruction_ptr;
+		interpreter.state = self.state;
+		interpreter.stack = self.loop_stack.clone();
+		interpreter.max_stack_depth = self.loop_stack.len();
+		interpreter
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::from(MAGIC);
+		bytes.push(FORMAT_VERSION);
+		write_varint(&mut bytes, self.program_hash);
+		write_varint(&mut bytes, self.error.len() as u64);
+		bytes.extend_from_slice(self.error.as_bytes());
+		write_varint(&mut bytes, self.data_ptr as u64);
+		write_varint(&mut bytes, self.instruction_ptr as u64);
+		match self.state {
+			InterpreterState::Running => bytes.push(STATE_RUNNING),
+			InterpreterState::Skipping(depth) => {
+				bytes.push(STATE_SKIPPING);
+				write_varint(&mut bytes, depth as u64);
+			}
+			InterpreterState::Halted => bytes.push(STATE_HALTED),
+		}
+		write_varint(&mut bytes, self.loop_stack.len() as u64);
+		for &address in &self.loop_stack {
+			write_varint(&mut bytes, address as u64);
+		}
+		write_varint(&mut bytes, self.tape.len() as u64);
+		bytes.extend_from_slice(&self.tape);
+		write_varint(&mut bytes, self.recent.len() as u64);
+		for step in &self.recent {
+			bytes.push(step.instruction.to_char() as u8);
+			write_varint(&mut bytes, step.instruction_ptr as u64);
+			write_varint(&mut bytes, step.data_ptr as u64);
+			bytes.push(step.cell);
+		}
+		bytes
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoadError> {
+		let mut reader = ByteReader { bytes, position: 0 };
+		if reader.take(MAGIC.len())? != MAGIC {
+			return Err(LoadError::BadMagic);
+		}
+		let version = reader.byte()?;
+		if version != FORMAT_VERSION {
+			return Err(LoadError::UnsupportedVersion(version));
+		}
+
+		let program_hash = reader.varint()?;
+		let error_len = reader.unsigned_operand(reader.position)?;
+		let error_offset = reader.position;
+		let error = String::from_utf8(reader.take(error_len)?.to_vec())
+			.map_err(|_| LoadError::InvalidOperand { offset: error_offset })?;
+		let data_ptr = reader.unsigned_operand(reader.position)?;
+		let instruction_ptr = reader.unsigned_operand(reader.position)?;
+		let state_offset = reader.position;
+		let state = match reader.byte()? {
+			STATE_RUNNING => InterpreterState::Running,
+			STATE_SKIPPING => InterpreterState::Skipping(reader.unsigned_operand(state_offset)?),
+			STATE_HALTED => InterpreterState::Halted,
+			tag => return Err(LoadError::InvalidOp { offset: state_offset, tag }),
+		};
+
+		let stack_len: usize = reader.unsigned_operand(reader.position)?;
+		// Every entry takes at least a byte, which bounds the allocation for corrupt lengths
+		let mut loop_stack = Vec::with_capacity(stack_len.min(bytes.len()));
+		for _ in 0..stack_len {
+			loop_stack.push(reader.unsigned_operand(reader.position)?);
+		}
+		let tape_offset = reader.position;
+		let tape_len = reader.unsigned_operand(tape_offset)?;
+		if tape_len > MEM_SIZE || data_ptr >= MEM_SIZE {
+			return Err(LoadError::InvalidOperand { offset: tape_offset });
+		}
+		let tape = reader.take(tape_len)?.to_vec();
+
+		let recent_len: usize = reader.unsigned_operand(reader.position)?;
+		let mut recent = Vec::with_capacity(recent_len.min(bytes.len()));
+		for _ in 0..recent_len {
+			let step_offset = reader.position;
+			let tag = reader.byte()?;
+			let InterpreterSymbol::Instruction(instruction) = InterpreterSymbol::from_byte(Some(&tag)) else {
+				return Err(LoadError::InvalidOp { offset: step_offset, tag });
+			};
+			recent.push(StepEvent {
+				instruction,
+				instruction_ptr: reader.unsigned_operand(step_offset)?,
+				data_ptr: reader.unsigned_operand(step_offset)?,
+				cell: reader.byte()?,
+			});
+		}
+
+		if reader.position != bytes.len() {
+			return Err(LoadError::TrailingBytes);
+		}
+		Ok(CoreDump { program_hash, error, data_ptr, instruction_ptr, state, loop_stack, tape, recent })
+	}
+
+	pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+		fs::write(path, self.to_bytes())
+	}
+
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+		let bytes = fs::read(path).map_err(LoadError::Io)?;
+		Self::from_bytes(&bytes)
+	}
+}
@@ -0,0 +1,102 @@
+//! Wall-clock limit on a run, for [`Interpreter::with_timeout`].
+//!
+//! [`Interpreter::with_timeout`]: crate::interpreter::Interpreter::with_timeout
+
+use std::time::{Duration, Instant};
+
+/// Checks between looking at the clock, which is slow next to a step.
+const CHECK_EVERY: u32 = 4096;
+
+#[derive(Clone)]
+pub(crate) struct Deadline {
+	at: Instant,
+	/// Whether time blocked reading input counts, instead of pushing the deadline back.
+	includes_input: bool,
+	until_check: u32,
+}
+
+impl Deadline {
+	pub fn new(timeout: Duration, includes_input: bool) -> Self {
+		Deadline { at: Instant::now() + timeout, includes_input, until_check: CHECK_EVERY }
+	}
+
+	/// Whether the deadline passed, as of the last time the clock was looked at.
+	pub fn expired(&mut self) -> bool {
+		self.until_check -= 1;
+		if self.until_check > 0 {
+			return false;
+		}
+		self.until_check = CHECK_EVERY;
+		Instant::now() >= self.at
+	}
+
+	/// The run spent `wait` blocked reading input.
+	pub fn waited(&mut self, wait: Duration) {
+		if self.includes_input {
+			// The wait may well have used up the time, no point running thousands more steps
+			self.until_check = 1;
+		} else {
+			self.at += wait;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::backend::Backend;
+	use crate::interpreter::error::InterpreterErrorReason;
+	use crate::interpreter::io::CapturedOutput;
+	use crate::interpreter::{Interpreter, SliceOutcome};
+
+	const SPIN: &str = "++.[]";
+
+	#[test]
+	fn endless_loops_time_out() {
+		let program: Vec<char> = SPIN.chars().collect();
+		for backend in [Backend::Naive, Backend::Ir, Backend::Closures] {
+			let output = CapturedOutput::new();
+			let mut interpreter = Interpreter::with_output(output.clone())
+				.with_stats()
+				.with_timeout(Duration::from_millis(100), false);
+			let start = Instant::now();
+			let err = backend.run(&mut interpreter, &program).unwrap_err();
+			let elapsed = start.elapsed();
+			assert!(matches!(err.reason, InterpreterErrorReason::TimedOut), "{backend:?}: {err:?}");
+			assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_secs(5), "{backend:?}: {elapsed:?}");
+			// What was printed before is out, and the run got somewhere
+			assert_eq!(output.contents(), [2], "{backend:?}");
+			if backend == Backend::Naive {
+				assert!(interpreter.stats().unwrap().steps > u64::from(CHECK_EVERY));
+			}
+		}
+	}
+
+	#[test]
+	fn step_limits_hit_first_win() {
+		let program: Vec<char> = SPIN.chars().collect();
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new()).with_timeout(Duration::from_secs(10), false);
+		interpreter.load(&program);
+		let start = Instant::now();
+		assert_eq!(interpreter.run_for(1000).unwrap(), SliceOutcome::OutOfSteps);
+		assert!(start.elapsed() < Duration::from_secs(5));
+
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new()).with_timeout(Duration::from_millis(100), false);
+		interpreter.load(&program);
+		let err = interpreter.run_for(u64::MAX).unwrap_err();
+		assert!(matches!(err.reason, InterpreterErrorReason::TimedOut), "{err:?}");
+	}
+
+	#[test]
+	fn waiting_on_input_pushes_the_deadline_back() {
+		let mut deadline = Deadline::new(Duration::from_millis(100), false);
+		deadline.waited(Duration::from_secs(3600));
+		std::thread::sleep(Duration::from_millis(150));
+		assert!(!(0..CHECK_EVERY).any(|_| deadline.expired()));
+
+		let mut deadline = Deadline::new(Duration::from_millis(100), true);
+		std::thread::sleep(Duration::from_millis(150));
+		deadline.waited(Duration::from_millis(150));
+		assert!(deadline.expired(), "checked right after the wait");
+	}
+}
@@ -4,6 +4,8 @@ use crate::interpreter::{Interpreter, InterpreterResult, InterpreterState};
 pub struct InterpreterMismatchedBracketsError {
 	_instruction_ptr: usize,
 	_missing_brackets: usize,
+	/// Source offset of the outermost `[` left open.
+	pub unmatched_loop_start: usize,
 }
 
 #[derive(Debug)]
@@ -15,14 +17,37 @@ pub enum InterpreterErrorReason {
 	HaltedMachine,
 	MismatchedBrackets(InterpreterMismatchedBracketsError),
 	UnprintableByte(u8),
+	/// An initial tape of this many bytes is longer than the tape.
+	SeedTooLarge(usize),
+	/// The interrupt flag was set.
+	Interrupted,
+	/// The loop starting at `loop_start` was about to go around for the `iterations`th time in a
+	/// row, more than allowed.
+	LoopIterationLimit { loop_start: usize, iterations: u64 },
+	/// The pointer went to cell `requested` of a tape cut down to `limit` cells.
+	TapeLimitExceeded { requested: usize, limit: usize },
+	/// Entering a loop would have nested more than this many.
+	LoopDepthExceeded { depth: usize },
+	/// `.` would have printed more than this many bytes.
+	OutputLimitExceeded { limit: u64 },
+	/// The run went past the time it was given.
+	TimedOut,
+	/// The loop starting at this offset went around without changing anything, so it never ends.
+	InfiniteLoopDetected(usize),
+	/// The run was still going after the steps it was given.
+	StepLimitExceeded { limit: u64 },
+	/// Unmatched brackets blamed on a machine that was neither skipping a loop nor inside one, so
+	/// there's no bracket to point at.
+	NoOpenLoop,
 }
 
+#[derive(Debug)]
 pub struct InterpreterError {
 	pub reason: InterpreterErrorReason,
 }
 
 impl InterpreterError {
-	pub fn to_result(self) -> InterpreterResult {
+	pub fn into_result(self) -> InterpreterResult {
 		Err(self)
 	}
 
@@ -58,17 +83,21 @@ impl InterpreterError {
 		}
 	}
 
+	/// The end of the program reached while skipping a loop, or inside loops still open. On a
+	/// machine in neither, [`InterpreterErrorReason::NoOpenLoop`].
 	pub fn mismatched_brackets(interpreter: &Interpreter) -> Self {
 		let instruction_ptr = interpreter.instruction_ptr;
-		if let InterpreterState::Skipping(missing_brackets) = interpreter.state {
-			InterpreterError {
-				reason: InterpreterErrorReason::MismatchedBrackets(InterpreterMismatchedBracketsError {
-					_instruction_ptr: instruction_ptr,
-					_missing_brackets: missing_brackets,
-				}),
-			}
-		} else {
-			panic!("Not in a skipping state");
+		let (missing_brackets, unmatched_loop_start) = match (interpreter.state, interpreter.stack.first()) {
+			(InterpreterState::Skipping(missing_brackets), _) => (missing_brackets, interpreter.skip_start),
+			(InterpreterState::Running, Some(&loop_start)) => (interpreter.stack.len(), loop_start),
+			_ => return InterpreterError { reason: InterpreterErrorReason::NoOpenLoop },
+		};
+		InterpreterError {
+			reason: InterpreterErrorReason::MismatchedBrackets(InterpreterMismatchedBracketsError {
+				_instruction_ptr: instruction_ptr,
+				_missing_brackets: missing_brackets,
+				unmatched_loop_start,
+			}),
 		}
 	}
 
@@ -77,26 +106,59 @@ impl InterpreterError {
 			reason: InterpreterErrorReason::UnprintableByte(byte),
 		}
 	}
-}
 
+	pub fn interrupted() -> Self {
+		InterpreterError {
+			reason: InterpreterErrorReason::Interrupted,
+		}
+	}
 
-pub fn read_byte() -> Option<u8> {
-	let mut s = String::new();
-	std::io::stdin().read_line(&mut s).ok()?;
-	let first_char = s.chars().next()?;
+	pub fn loop_iteration_limit(loop_start: usize, iterations: u64) -> Self {
+		InterpreterError {
+			reason: InterpreterErrorReason::LoopIterationLimit { loop_start, iterations },
+		}
+	}
 
-	if !first_char.len_utf8() == 1 {
-		return None;
+	pub fn tape_limit_exceeded(requested: usize, limit: usize) -> Self {
+		InterpreterError {
+			reason: InterpreterErrorReason::TapeLimitExceeded { requested, limit },
+		}
 	}
 
-	let mut array = [0u8; 1];
-	first_char.encode_utf8(&mut array).bytes().next()
-}
+	pub fn loop_depth_exceeded(depth: usize) -> Self {
+		InterpreterError {
+			reason: InterpreterErrorReason::LoopDepthExceeded { depth },
+		}
+	}
+
+	pub fn output_limit_exceeded(limit: u64) -> Self {
+		InterpreterError {
+			reason: InterpreterErrorReason::OutputLimitExceeded { limit },
+		}
+	}
 
-pub fn print_char(byte: u8) -> Option<String> {
-	let byte_vec: Vec<u8> = vec![byte];
-	let string = String::from_utf8(byte_vec).ok()?;
+	pub fn timed_out() -> Self {
+		InterpreterError {
+			reason: InterpreterErrorReason::TimedOut,
+		}
+	}
+
+	pub fn infinite_loop_detected(loop_start: usize) -> Self {
+		InterpreterError {
+			reason: InterpreterErrorReason::InfiniteLoopDetected(loop_start),
+		}
+	}
 
-	print!("{string}");
-	Some(string)
+	pub fn step_limit_exceeded(limit: u64) -> Self {
+		InterpreterError {
+			reason: InterpreterErrorReason::StepLimitExceeded { limit },
+		}
+	}
+
+	pub fn seed_too_large(len: usize) -> Self {
+		InterpreterError {
+			reason: InterpreterErrorReason::SeedTooLarge(len),
+		}
+	}
 }
+
@@ -0,0 +1,64 @@
+use std::iter::FusedIterator;
+
+use crate::interpreter::error::InterpreterError;
+use crate::interpreter::{Interpreter, InterpreterState};
+use crate::symbol::InterpreterInstruction;
+use crate::InterpreterSymbol;
+
+/// An executed instruction and the machine right after it, as yielded by [`Interpreter::events`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepEvent {
+	pub instruction: InterpreterInstruction,
+	/// Where the instruction is in the source.
+	pub instruction_ptr: usize,
+	pub data_ptr: usize,
+	/// Value of the cell under the data pointer.
+	pub cell: u8,
+}
+
+pub struct Events<'a> {
+	interpreter: &'a mut Interpreter,
+	symbols: Vec<InterpreterSymbol>,
+	failed: bool,
+}
+
+impl<'a> Events<'a> {
+	pub(super) fn new(interpreter: &'a mut Interpreter, characters: &'a [char]) -> Self {
+		Events { interpreter, symbols: InterpreterSymbol::tokenize(characters), failed: false }
+	}
+}
+
+impl Iterator for Events<'_> {
+	type Item = Result<StepEvent, InterpreterError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while !self.failed && !self.interpreter.is_halted() {
+			let instruction_ptr = self.interpreter.get_instruction_ptr();
+			let symbol = InterpreterSymbol::at(&self.symbols, instruction_ptr);
+			let executed = match (self.interpreter.get_state(), &symbol) {
+				(InterpreterState::Running, InterpreterSymbol::Instruction(instruction)) => Some(*instruction),
+				_ => None,
+			};
+
+			if let Err(err) = self.interpreter.interpret_symbol(&symbol) {
+				self.failed = true;
+				self.interpreter.flush_output();
+				return Some(Err(err));
+			}
+			if let Some(instruction) = executed {
+				let data_ptr = self.interpreter.get_data_ptr();
+				return Some(Ok(StepEvent {
+					instruction,
+					instruction_ptr,
+					data_ptr,
+					cell: self.interpreter.cell(data_ptr).unwrap_or_default(),
+				}));
+			}
+		}
+
+		self.interpreter.flush_output();
+		None
+	}
+}
+
+impl FusedIterator for Events<'_> {}
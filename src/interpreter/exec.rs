@@ -0,0 +1,145 @@
+//! Execution of compiled programs: a flat list of ops with resolved jumps, run with a program
+//! counter instead of tokenizing characters and skipping over loop bodies.
+
+use crate::interpreter::error::InterpreterError;
+use crate::interpreter::{Interpreter, InterpreterResult, InterpreterState, SliceOutcome};
+use crate::ir::{Ir, Op};
+
+impl Interpreter {
+	/// Runs `ir` to the end with the same checked semantics as [`Interpreter::run`]. On failure the
+	/// instruction pointer is the source offset of the op that failed.
+	///
+	/// Only the tape, pointer, I/O, interrupt flag, loop profile and hot loop warning are involved:
+	/// coverage, history, watchpoints and the loop stack need [`Interpreter::run`] or
+	/// [`Interpreter::step`].
+	pub fn run_ir(&mut self, ir: &Ir) -> InterpreterResult {
+		self.run_with_fuel(ir, u64::MAX).map(|_| ())
+	}
+
+	/// Like [`Interpreter::run_ir`], executing at most `fuel` ops, every one costing a unit however
+	/// much it does. Running out leaves the machine where it stopped, even mid-loop, and calling
+	/// this or [`Interpreter::run_ir`] again with the same `ir` picks up from there.
+	pub fn run_with_fuel(&mut self, ir: &Ir, fuel: u64) -> Result<SliceOutcome, InterpreterError> {
+		if self.is_halted() {
+			return Err(InterpreterError::halted_machine());
+		}
+
+		let result = self.run_ops(ir, fuel);
+		let stopped_at = match &result {
+			Ok(None) => {
+				self.state = InterpreterState::Halted;
+				self.ir_pc = 0;
+				None
+			}
+			Ok(Some(pc)) | Err((pc, _)) => Some(*pc),
+		};
+		if let Some(span) = stopped_at.and_then(|pc| ir.spans().get(pc)) {
+			self.instruction_ptr = span.offset;
+		}
+		self.flush_output();
+		match result {
+			Ok(None) => Ok(SliceOutcome::Halted),
+			Ok(Some(_)) => Ok(SliceOutcome::OutOfSteps),
+			Err((_, err)) => Err(err),
+		}
+	}
+
+	/// Runs the ops of `ir` from where the last run stopped, returning the op to resume from if
+	/// `fuel` ran out, or failing with the index of the op that failed.
+	fn run_ops(&mut self, ir: &Ir, mut fuel: u64) -> Result<Option<usize>, (usize, InterpreterError)> {
+		let ops = ir.ops();
+		let mut pc = self.ir_pc;
+		while let Some(op) = ops.get(pc) {
+			if fuel == 0 {
+				self.ir_pc = pc;
+				return Ok(Some(pc));
+			}
+			fuel -= 1;
+			let result = match op {
+				&Op::Move(delta) => self.move_ptr(delta),
+				&Op::Add(delta) => self.delta_data_cell(delta),
+				Op::SetZero => {
+					self.profile_loop(ir, pc, |interpreter| interpreter.read_memory().map_or(0, u64::from));
+					self.write_memory(0)
+				}
+				Op::ScanRight | Op::ScanLeft => {
+					let start = self.data_ptr;
+					let result = self.scan(*op == Op::ScanRight);
+					self.profile_loop(ir, pc, |interpreter| interpreter.data_ptr.abs_diff(start) as u64);
+					result
+				}
+				Op::MulAdd(mul_add) => {
+					let count = self.read_memory().unwrap_or_default();
+					let result = self.mul_add(mul_add);
+					// The loop right after it is still entered, only finding its counter zeroed
+					let zeroed = matches!(self.read_memory(), Ok(0));
+					if let (Some(profile), true) = (&mut self.loop_profile, zeroed) {
+						profile.add_iterations(ir.spans()[pc].offset, u64::from(count));
+					}
+					result
+				}
+				Op::Print => self.print_ptr(),
+				Op::Read => self.read_ptr(),
+				&Op::JumpIfZero(end) => self.read_memory().and_then(|value| {
+					if let Some(profile) = &mut self.loop_profile {
+						profile.enter(ir.spans()[pc].offset, u64::from(value != 0));
+					}
+					if let (Some(detector), true) = (&mut self.hot_loops, value != 0) {
+						detector.iterate(ir.spans()[pc].offset, false);
+					}
+					if let (Some(cap), true) = (&mut self.iteration_cap, value != 0) {
+						let loop_start = ir.spans()[pc].offset;
+						cap.iterate(loop_start, false)
+							.map_err(|iterations| InterpreterError::loop_iteration_limit(loop_start, iterations))?;
+					}
+					if value == 0 {
+						pc = end;
+					}
+					Ok(())
+				}),
+				&Op::JumpIfNonZero(start) => self.check_interrupt().and_then(|()| self.read_memory()).and_then(|value| {
+					let loop_start = ir.spans()[start].offset;
+					if let Some(detector) = &mut self.hot_loops {
+						if value != 0 {
+							detector.iterate(loop_start, true);
+						} else {
+							detector.exit(loop_start);
+						}
+					}
+					if let Some(cap) = &mut self.iteration_cap {
+						if value != 0 {
+							cap.iterate(loop_start, true)
+								.map_err(|iterations| InterpreterError::loop_iteration_limit(loop_start, iterations))?;
+						} else {
+							cap.exit(loop_start);
+						}
+					}
+					if value != 0 {
+						if let Some(profile) = &mut self.loop_profile {
+							profile.add_iterations(loop_start, 1);
+						}
+						pc = start;
+					}
+					Ok(())
+				}),
+			};
+			if let Err(err) = result {
+				self.ir_pc = pc;
+				return Err((pc, err));
+			}
+			pc += 1;
+		}
+		Ok(None)
+	}
+
+	/// Counts the loop compiled into the op at `pc` as entered once, running the body the number of
+	/// times `iterations` tells.
+	fn profile_loop(&mut self, ir: &Ir, pc: usize, iterations: impl FnOnce(&Self) -> u64) {
+		if self.loop_profile.is_some() {
+			let iterations = iterations(self);
+			if let Some(profile) = &mut self.loop_profile {
+				profile.enter(ir.spans()[pc].offset, iterations);
+			}
+		}
+	}
+}
@@ -0,0 +1,77 @@
+use std::collections::VecDeque;
+
+use crate::interpreter::InterpreterState;
+
+/// Everything a single step changed, enough to put the machine back as it was before it.
+#[derive(Debug, Clone)]
+pub(super) struct UndoRecord {
+	pub instruction_ptr: usize,
+	pub data_ptr: usize,
+	pub state: InterpreterState,
+	pub stack_len: usize,
+	/// Loop return address popped by the step.
+	pub popped: Option<usize>,
+	/// Address and previous value of the cell written by the step.
+	pub cell: Option<(usize, u8)>,
+	/// Input byte consumed by the step, handed back to the input queue when undone.
+	pub read: Option<u8>,
+	/// Byte printed by the step, which stays printed.
+	pub output: Option<u8>,
+}
+
+/// What [`Interpreter::step_back`](crate::interpreter::Interpreter::step_back) undid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RevertedStep {
+	/// Where the machine is back at.
+	pub instruction_ptr: usize,
+	/// Address of the cell written by the step, the value put back and the value it replaced.
+	pub cell: Option<(usize, u8, u8)>,
+	/// Byte the step printed; output can't be taken back, so it's only reported.
+	pub output: Option<u8>,
+}
+
+/// Ring buffer with the undo records of the most recent steps.
+#[derive(Debug, Clone)]
+pub(super) struct History {
+	capacity: usize,
+	records: VecDeque<UndoRecord>,
+	pending: Option<UndoRecord>,
+}
+
+impl History {
+	pub fn new(capacity: usize) -> Self {
+		History {
+			capacity,
+			records: VecDeque::with_capacity(capacity),
+			pending: None,
+		}
+	}
+
+	pub fn begin(&mut self, record: UndoRecord) {
+		self.pending = Some(record);
+	}
+
+	pub fn pending(&mut self) -> Option<&mut UndoRecord> {
+		self.pending.as_mut()
+	}
+
+	pub fn commit(&mut self) {
+		if let Some(record) = self.pending.take() {
+			if self.capacity == 0 {
+				return;
+			}
+			if self.records.len() == self.capacity {
+				self.records.pop_front();
+			}
+			self.records.push_back(record);
+		}
+	}
+
+	pub fn pop(&mut self) -> Option<UndoRecord> {
+		self.records.pop_back()
+	}
+
+	pub fn len(&self) -> usize {
+		self.records.len()
+	}
+}
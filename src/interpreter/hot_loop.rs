@@ -0,0 +1,97 @@
+//! Spotting loops that may never end: a loop running many iterations in a row without reading or
+//! printing anything is reported once, while it's still running.
+
+use std::rc::Rc;
+
+/// Iterations without I/O after which a loop is reported unless configured otherwise.
+pub const DEFAULT_HOT_LOOP_ITERATIONS: u64 = 10_000_000;
+
+/// A loop that went over the threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HotLoop {
+	/// Source offset of the loop's `[`.
+	pub loop_start: usize,
+	pub iterations: u64,
+}
+
+#[derive(Clone)]
+pub(crate) struct HotLoopDetector {
+	threshold: u64,
+	on_hot_loop: Rc<dyn Fn(HotLoop)>,
+	/// The loops being run, innermost last, with their iterations since entering them or the last
+	/// I/O.
+	running: Vec<(usize, u64)>,
+	/// Loops already reported, which never are again.
+	reported: Vec<usize>,
+}
+
+impl HotLoopDetector {
+	pub fn new(threshold: u64, on_hot_loop: Rc<dyn Fn(HotLoop)>) -> Self {
+		HotLoopDetector { threshold, on_hot_loop, running: Vec::new(), reported: Vec::new() }
+	}
+
+	/// The body of the loop at `loop_start` runs, for the first time since reaching it if not
+	/// `looping_back`.
+	pub fn iterate(&mut self, loop_start: usize, looping_back: bool) {
+		if !looping_back || self.running.last().is_none_or(|&(running, _)| running != loop_start) {
+			self.running.push((loop_start, 0));
+		}
+		let Some((_, iterations)) = self.running.last_mut() else {
+			return;
+		};
+		*iterations += 1;
+		if *iterations == self.threshold && !self.reported.contains(&loop_start) {
+			self.reported.push(loop_start);
+			(self.on_hot_loop)(HotLoop { loop_start, iterations: *iterations });
+		}
+	}
+
+	/// The loop at `loop_start` found its cell zero and ended.
+	pub fn exit(&mut self, loop_start: usize) {
+		if let Some(idx) = self.running.iter().rposition(|&(running, _)| running == loop_start) {
+			self.running.truncate(idx);
+		}
+	}
+
+	/// Reading or printing shows every running loop is doing something.
+	pub fn io(&mut self) {
+		for (_, iterations) in &mut self.running {
+			*iterations = 0;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::RefCell;
+
+	use super::*;
+	use crate::interpreter::io::CapturedOutput;
+	use crate::interpreter::{Interpreter, SliceOutcome};
+
+	/// The hot loops `program` reports within `steps` steps, with a threshold of 1000.
+	fn warnings(program: &str, steps: u64) -> Vec<HotLoop> {
+		let warned = Rc::new(RefCell::new(Vec::new()));
+		let sink = Rc::clone(&warned);
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new())
+			.with_hot_loop_warning(1000, move |hot_loop| sink.borrow_mut().push(hot_loop));
+		interpreter.load(&program.chars().collect::<Vec<_>>());
+		let _: SliceOutcome = interpreter.run_for(steps).unwrap();
+		let warned = warned.borrow().clone();
+		warned
+	}
+
+	#[test]
+	fn endless_loops_are_reported_once() {
+		assert_eq!(warnings("+[]", 10_000), [HotLoop { loop_start: 1, iterations: 1000 }]);
+		assert_eq!(warnings("+[[]]", 10_000), [HotLoop { loop_start: 2, iterations: 1000 }]);
+	}
+
+	#[test]
+	fn finite_and_printing_loops_are_not() {
+		assert_eq!(warnings(&format!("{}[-]", "+".repeat(255)), 10_000), []);
+		assert_eq!(warnings("+[.]", 10_000), []);
+		// A thousand iterations in all, but only four each time it's entered
+		assert_eq!(warnings(&format!("{}[>++++[-]<-]", "+".repeat(250)), 100_000), []);
+	}
+}
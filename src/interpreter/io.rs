@@ -0,0 +1,93 @@
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+/// The streams `,` reads from and `.` prints to, stdin and stdout by default. Clones share them.
+#[derive(Clone)]
+pub struct Io {
+	input: Option<Rc<RefCell<dyn Read>>>,
+	output: Rc<RefCell<dyn Write>>,
+}
+
+impl Io {
+	pub fn new(input: impl Read + 'static, output: impl Write + 'static) -> Self {
+		Io { input: Some(Rc::new(RefCell::new(input))), output: Rc::new(RefCell::new(output)) }
+	}
+
+	/// Printing to `output`, with nothing to read from.
+	pub fn output_only(output: impl Write + 'static) -> Self {
+		Io { input: None, output: Rc::new(RefCell::new(output)) }
+	}
+
+	pub fn has_input(&self) -> bool {
+		self.input.is_some()
+	}
+
+	/// Replaces the output stream, keeping the input.
+	pub fn set_output(&mut self, output: impl Write + 'static) {
+		self.output = Rc::new(RefCell::new(output));
+	}
+
+	/// The next byte of the input, `None` at its end, on a failed read or without an input stream.
+	pub fn read_byte(&self) -> Option<u8> {
+		let mut array = [0u8; 1];
+		self.input.as_ref()?.borrow_mut().read_exact(&mut array).ok()?;
+
+		Some(array[0])
+	}
+
+	/// Prints `byte` as the character it is on its own, `None` if it isn't one, ASCII only, or
+	/// the write failed.
+	pub fn print_char(&self, byte: u8) -> Option<String> {
+		let byte_vec: Vec<u8> = vec![byte];
+		let string = String::from_utf8(byte_vec).ok()?;
+
+		write!(self.output.borrow_mut(), "{string}").ok()?;
+		Some(string)
+	}
+
+	/// Writes `bytes` to the output as they are.
+	pub fn write_all(&self, bytes: &[u8]) -> io::Result<()> {
+		self.output.borrow_mut().write_all(bytes)
+	}
+
+	pub fn flush(&self) -> io::Result<()> {
+		self.output.borrow_mut().flush()
+	}
+}
+
+impl Default for Io {
+	fn default() -> Self {
+		Io::new(io::stdin(), io::stdout())
+	}
+}
+
+/// Output sink that keeps every written byte in memory.
+///
+/// Clones share the same buffer, so one handle can be given to the interpreter while another is
+/// kept around to read the output back once the run is over.
+#[derive(Clone, Default, Debug)]
+pub struct CapturedOutput {
+	buffer: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CapturedOutput {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn contents(&self) -> Vec<u8> {
+		self.buffer.borrow().clone()
+	}
+}
+
+impl Write for CapturedOutput {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.buffer.borrow_mut().extend_from_slice(buf);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
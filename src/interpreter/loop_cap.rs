@@ -0,0 +1,78 @@
+//! Bounding how many times in a row a single loop may go around, for
+//! [`Interpreter::with_max_loop_iterations`].
+//!
+//! [`Interpreter::with_max_loop_iterations`]: crate::interpreter::Interpreter::with_max_loop_iterations
+
+#[derive(Clone)]
+pub(crate) struct IterationCap {
+	limit: u64,
+	/// The loops being run, innermost last, with their iterations since entering them.
+	running: Vec<(usize, u64)>,
+}
+
+impl IterationCap {
+	pub fn new(limit: u64) -> Self {
+		IterationCap { limit, running: Vec::new() }
+	}
+
+	/// The body of the loop at `loop_start` is about to run, for the first time since reaching it
+	/// if not `looping_back`. Fails with the iterations it would make if that's over the limit.
+	pub fn iterate(&mut self, loop_start: usize, looping_back: bool) -> Result<(), u64> {
+		if !looping_back || self.running.last().is_none_or(|&(running, _)| running != loop_start) {
+			self.running.push((loop_start, 0));
+		}
+		let Some((_, iterations)) = self.running.last_mut() else {
+			return Ok(());
+		};
+		*iterations += 1;
+		if *iterations > self.limit { Err(*iterations) } else { Ok(()) }
+	}
+
+	/// The loop at `loop_start` found its cell zero and ended.
+	pub fn exit(&mut self, loop_start: usize) {
+		if let Some(idx) = self.running.iter().rposition(|&(running, _)| running == loop_start) {
+			self.running.truncate(idx);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::interpreter::error::InterpreterErrorReason;
+	use crate::interpreter::io::CapturedOutput;
+	use crate::interpreter::Interpreter;
+	use crate::ir::Ir;
+
+	/// Runs `program` stepping through it, then as IR, with loops capped at `limit` iterations.
+	fn capped_runs(program: &str, limit: u64) -> [Result<(), InterpreterErrorReason>; 2] {
+		let characters: Vec<char> = program.chars().collect();
+		let ir = Ir::compile(&characters, crate::ir::DEFAULT_OPT_LEVEL).unwrap();
+		let machine = || Interpreter::with_output(CapturedOutput::new()).with_max_loop_iterations(limit);
+		[
+			machine().run(&characters).map_err(|err| err.reason),
+			machine().run_ir(&ir).map_err(|err| err.reason),
+		]
+	}
+
+	#[test]
+	fn endless_loops_are_caught_at_their_bracket() {
+		for result in capped_runs("+++[>+[]<-]", 100) {
+			assert!(
+				matches!(result, Err(InterpreterErrorReason::LoopIterationLimit { loop_start: 6, iterations: 101 })),
+				"{result:?}"
+			);
+		}
+	}
+
+	#[test]
+	fn loops_under_the_cap_are_not() {
+		// The outer loop goes around 50 times, the inner one 500 in all but 10 at a time
+		let program = format!("{}[>++++++++++[-]<-]", "+".repeat(50));
+		for result in capped_runs(&program, 50) {
+			assert!(result.is_ok(), "{result:?}");
+		}
+		for result in capped_runs(&program, 49) {
+			assert!(matches!(result, Err(InterpreterErrorReason::LoopIterationLimit { loop_start: 50, iterations: 50 })), "{result:?}");
+		}
+	}
+}
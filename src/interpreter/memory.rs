@@ -1,34 +1,93 @@
-use std::fmt::{Debug, Display, Formatter};
+use std::fmt::{Debug, Display, Formatter, Write};
+use std::io;
+use std::ops::Range;
 
 const MEMORY_SIZE: usize = 30_000;
+const HEX_DUMP_ROW: usize = 16;
 
+#[derive(Clone)]
 pub(super) struct InterpreterMemory {
-	memory: [u8; MEMORY_SIZE],
+	/// On the heap, so moving an interpreter doesn't copy the tape.
+	memory: Box<[u8]>,
 	highest_written: usize,
+	/// Whether any cell was written, `highest_written` being 0 either way for a fresh tape.
+	dirty: bool,
+	write_counts: Option<Vec<u64>>,
 }
 
 impl InterpreterMemory {
 	pub fn new() -> Self {
 		InterpreterMemory {
-			memory: [0u8; MEMORY_SIZE],
+			memory: vec![0u8; MEMORY_SIZE].into_boxed_slice(),
 			highest_written: 0,
+			dirty: false,
+			write_counts: None,
 		}
 	}
 
+	/// A tape holding `bytes` from cell 0 on, the rest zeroed. Fails if they don't fit.
+	pub fn with_initial(bytes: &[u8]) -> Result<Self, ()> {
+		let mut memory = Self::new();
+		memory.memory.get_mut(..bytes.len()).ok_or(())?.copy_from_slice(bytes);
+		memory.highest_written = bytes.len().saturating_sub(1);
+		memory.dirty = !bytes.is_empty();
+		Ok(memory)
+	}
+
+	/// Shrinks the tape to its first `cells`, at least one and at most the full tape, dropping
+	/// anything written past them.
+	pub fn restrict(&mut self, cells: usize) {
+		let cells = cells.max(1);
+		if cells >= self.memory.len() {
+			return;
+		}
+		self.memory = self.memory[..cells].into();
+		if let Some(write_counts) = &mut self.write_counts {
+			write_counts.truncate(cells);
+		}
+		self.highest_written = self.highest_written.min(cells - 1);
+	}
+
+	pub fn len(&self) -> usize {
+		self.memory.len()
+	}
+
+	/// Starts counting writes per cell, see [`InterpreterMemory::access_counts`].
+	pub fn track_access_counts(&mut self) {
+		if self.write_counts.is_none() {
+			self.write_counts = Some(vec![0u64; self.memory.len()]);
+		}
+	}
+
+	/// Number of writes to each cell up to the highest written one, if tracking is enabled.
+	pub fn access_counts(&self) -> Option<&[u64]> {
+		let write_counts = self.write_counts.as_ref()?;
+		Some(&write_counts[..=self.highest_written])
+	}
+
 	pub fn read(&self, address: usize) -> Result<u8, ()> {
-		if address < MEMORY_SIZE {
-			Ok(self.memory[address])
+		self.memory.get(address).copied().ok_or(())
+	}
+
+	/// The first zero cell from `address` on, going right or left.
+	pub fn find_zero(&self, address: usize, right: bool) -> Option<usize> {
+		if right {
+			self.memory.get(address..)?.iter().position(|&value| value == 0).map(|offset| address + offset)
 		} else {
-			Err(())
+			self.memory.get(..=address)?.iter().rposition(|&value| value == 0)
 		}
 	}
 
 	pub fn write(&mut self, address: usize, value: u8) -> Result<(), ()> {
-		if address < MEMORY_SIZE {
+		if address < self.memory.len() {
 			self.memory[address] = value;
+			if let Some(write_counts) = &mut self.write_counts {
+				write_counts[address] += 1;
+			}
 			if address > self.highest_written {
 				self.highest_written = address;
 			}
+			self.dirty = true;
 
 			Ok(())
 		} else {
@@ -37,17 +96,83 @@ impl InterpreterMemory {
 	}
 }
 
+impl InterpreterMemory {
+	/// The cells up to and including the highest written one, none for an untouched tape.
+	pub fn written_range(&self) -> Range<usize> {
+		0..self.highest_written().map_or(0, |highest| highest + 1)
+	}
+
+	/// The highest written cell, `None` for a tape nothing was written to.
+	pub fn highest_written(&self) -> Option<usize> {
+		self.dirty.then_some(self.highest_written)
+	}
+
+	/// The written part of the tape.
+	pub fn written(&self) -> &[u8] {
+		&self.memory[self.written_range()]
+	}
+
+	/// The written part of the tape without its trailing zeros, what tells tapes apart.
+	pub fn significant(&self) -> &[u8] {
+		let written = self.written();
+		&written[..written.iter().rposition(|&value| value != 0).map_or(0, |last| last + 1)]
+	}
+
+	pub fn full_range(&self) -> Range<usize> {
+		0..self.memory.len()
+	}
+
+	/// Writes an `xxd`-style dump of `range` to `out`: 16 cells per row, with the address on the
+	/// left and the printable ASCII characters on the right. Cells past the tape are left out.
+	pub fn dump(&self, range: Range<usize>, out: &mut impl io::Write) -> io::Result<()> {
+		let range = range.start.min(self.memory.len())..range.end.min(self.memory.len());
+
+		for row_start in range.clone().step_by(HEX_DUMP_ROW) {
+			let row = &self.memory[row_start..(row_start + HEX_DUMP_ROW).min(range.end)];
+
+			let mut hex = String::new();
+			for (idx, byte) in row.iter().enumerate() {
+				let separator = if idx % 2 == 1 { " " } else { "" };
+				// Writing into a String can't fail
+				let _ = write!(hex, "{byte:02x}{separator}");
+			}
+			let ascii: String = row.iter()
+				.map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+				.collect();
+
+			writeln!(out, "{row_start:08x}: {hex:<40} {ascii}")?;
+		}
+		Ok(())
+	}
+
+	/// [`InterpreterMemory::dump`] into a string.
+	pub fn hex_dump(&self, range: Range<usize>) -> String {
+		let mut dump = Vec::new();
+		self.dump(range, &mut dump).expect("Writing into a Vec can't fail");
+		String::from_utf8(dump).expect("Dumps are ASCII")
+	}
+}
+
 impl Display for InterpreterMemory {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		write!(f, "[")?;
-		for idx in 0..self.highest_written {
-			let byte = self.memory[idx];
+		for byte in self.written() {
 			write!(f, "{byte:02X}.")?;
 		}
 		write!(f, "]")
 	}
 }
 
+/// Tapes are equal when their written parts are, cells written back to zero being the same as the
+/// ones never written.
+impl PartialEq for InterpreterMemory {
+	fn eq(&self, other: &Self) -> bool {
+		self.significant() == other.significant()
+	}
+}
+
+impl Eq for InterpreterMemory {}
+
 impl Debug for InterpreterMemory {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		Display::fmt(self, f)
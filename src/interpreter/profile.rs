@@ -0,0 +1,73 @@
+//! Per-loop counters collected by [`Interpreter::with_loop_profile`].
+//!
+//! [`Interpreter::with_loop_profile`]: crate::interpreter::Interpreter::with_loop_profile
+
+use std::collections::BTreeMap;
+
+/// How much a single `[`...`]` pair ran.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoopCounts {
+	/// Times the `[` was reached from before the loop, including when its body was skipped.
+	pub entries: u64,
+	/// Times the body ran.
+	pub iterations: u64,
+}
+
+/// Loop counts keyed by the source offset of each loop's `[`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoopProfile {
+	loops: BTreeMap<usize, LoopCounts>,
+}
+
+impl LoopProfile {
+	pub fn get(&self, loop_start: usize) -> Option<LoopCounts> {
+		self.loops.get(&loop_start).copied()
+	}
+
+	/// Every loop that was reached, by the offset of its `[`.
+	pub fn loops(&self) -> impl Iterator<Item = (usize, LoopCounts)> + '_ {
+		self.loops.iter().map(|(&loop_start, &counts)| (loop_start, counts))
+	}
+
+	/// Just the iterations of every loop that was reached, by the offset of its `[`.
+	pub fn iteration_counts(&self) -> BTreeMap<usize, u64> {
+		self.loops().map(|(loop_start, counts)| (loop_start, counts.iterations)).collect()
+	}
+
+	/// The `count` loops with the most iterations, the earliest first on ties.
+	pub fn hottest(&self, count: usize) -> Vec<(usize, LoopCounts)> {
+		let mut loops: Vec<_> = self.loops().collect();
+		loops.sort_by(|(left_start, left), (right_start, right)| {
+			right.iterations.cmp(&left.iterations).then(left_start.cmp(right_start))
+		});
+		loops.truncate(count);
+		loops
+	}
+
+	/// Counts the `[` at `loop_start` being reached, the body running if `enters`. Coming back from
+	/// the loop's `]`, `looping_back`, isn't an entry.
+	pub(crate) fn reach(&mut self, loop_start: usize, enters: bool, looping_back: bool) {
+		let counts = self.loops.entry(loop_start).or_default();
+		if !looping_back {
+			counts.entries = counts.entries.saturating_add(1);
+		}
+		if enters {
+			counts.iterations = counts.iterations.saturating_add(1);
+		}
+	}
+
+	/// Counts an entry into the loop at `loop_start` along with `iterations` runs of its body, for
+	/// compiled code that doesn't come back to the `[`.
+	pub(crate) fn enter(&mut self, loop_start: usize, iterations: u64) {
+		let counts = self.loops.entry(loop_start).or_default();
+		counts.entries = counts.entries.saturating_add(1);
+		counts.iterations = counts.iterations.saturating_add(iterations);
+	}
+
+	/// Counts `iterations` more for the loop at `loop_start` without an entry, for ops doing the
+	/// work of a loop the IR still enters right after.
+	pub(crate) fn add_iterations(&mut self, loop_start: usize, iterations: u64) {
+		let counts = self.loops.entry(loop_start).or_default();
+		counts.iterations = counts.iterations.saturating_add(iterations);
+	}
+}
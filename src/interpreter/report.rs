@@ -0,0 +1,120 @@
+//! A summary of a finished or stopped run, the same whichever way it was run.
+
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::interpreter::error::{InterpreterError, InterpreterErrorReason};
+use crate::interpreter::{Interpreter, InterpreterState};
+
+/// A limit a run went over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+	Fuel,
+	Output,
+	Tape,
+	LoopDepth,
+	Time,
+}
+
+impl Limit {
+	/// The limit behind errors of the `reason` kind, if it is one.
+	pub fn of(reason: &InterpreterErrorReason) -> Option<Self> {
+		match reason {
+			InterpreterErrorReason::OutputLimitExceeded { .. } => Some(Limit::Output),
+			InterpreterErrorReason::TapeLimitExceeded { .. } => Some(Limit::Tape),
+			InterpreterErrorReason::LoopDepthExceeded { .. } => Some(Limit::LoopDepth),
+			InterpreterErrorReason::TimedOut => Some(Limit::Time),
+			InterpreterErrorReason::StepLimitExceeded { .. } => Some(Limit::Fuel),
+			_ => None,
+		}
+	}
+}
+
+/// How the machine was left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinalState {
+	/// Ran to the end of the program.
+	Halted,
+	/// Stopped partway without an error, out of fuel, and may be resumed.
+	Paused,
+	Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunReport {
+	/// Counted only with [`Interpreter::with_stats`], like the counters below.
+	pub steps: u64,
+	pub wall_time: Duration,
+	pub bytes_read: u64,
+	pub bytes_written: u64,
+	pub peak_loop_depth: usize,
+	/// Cells from 0 to the highest written one.
+	pub tape_cells_used: usize,
+	/// The limit that stopped the run, if any did.
+	pub stopped_by: Option<Limit>,
+	pub state: FinalState,
+}
+
+impl RunReport {
+	/// Sums up the run that left `interpreter` as it is, failing with `error` if it did and having
+	/// taken `wall_time`.
+	pub fn new(interpreter: &Interpreter, error: Option<&InterpreterError>, wall_time: Duration) -> Self {
+		let stats = interpreter.stats().unwrap_or_default();
+		let (state, stopped_by) = match error {
+			Some(error) => (FinalState::Failed, Limit::of(&error.reason)),
+			None if interpreter.is_halted() => (FinalState::Halted, None),
+			None => (FinalState::Paused, Some(Limit::Fuel)),
+		};
+		RunReport {
+			steps: stats.steps,
+			wall_time,
+			bytes_read: stats.bytes_read,
+			bytes_written: stats.bytes_written,
+			peak_loop_depth: stats.peak_loop_depth,
+			tape_cells_used: stats.dirty_len,
+			stopped_by,
+			state,
+		}
+	}
+
+	/// A report for a program that never ran.
+	pub(crate) fn not_run() -> Self {
+		RunReport {
+			steps: 0,
+			wall_time: Duration::ZERO,
+			bytes_read: 0,
+			bytes_written: 0,
+			peak_loop_depth: 0,
+			tape_cells_used: 0,
+			stopped_by: None,
+			state: FinalState::Failed,
+		}
+	}
+}
+
+impl Display for RunReport {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{:?} after {} steps in {:.3?}: {} bytes read, {} written, loops {} deep, {} cells used",
+			self.state, self.steps, self.wall_time, self.bytes_read, self.bytes_written, self.peak_loop_depth,
+			self.tape_cells_used,
+		)?;
+		if let Some(limit) = self.stopped_by {
+			write!(f, ", stopped by the {limit:?} limit")?;
+		}
+		Ok(())
+	}
+}
+
+/// How [`Interpreter::execute`] went.
+#[derive(Debug)]
+pub struct ExecutionReport {
+	pub state: InterpreterState,
+	/// Instructions executed, loop bodies skipped over not counting.
+	pub steps: u64,
+	pub error: Option<InterpreterError>,
+	/// What the program printed, if the interpreter was made
+	/// [`Interpreter::with_captured_output`].
+	pub output: Option<Vec<u8>>,
+}
@@ -0,0 +1,29 @@
+//! Spotting loops that can never end: an iteration that wrote no cell, did no I/O and left the
+//! data pointer where it found it leaves the machine exactly as it was, so the next one does the
+//! same forever. A body like `+-` undoing its own writes isn't caught.
+
+#[derive(Clone, Default)]
+pub(crate) struct StallDetector {
+	/// Writes and I/O so far, telling whether an iteration did anything.
+	effects: u64,
+	/// Per active loop, outermost first: its `[`, and the data pointer and effects when its
+	/// current iteration started.
+	iterations: Vec<(usize, usize, u64)>,
+}
+
+impl StallDetector {
+	/// A cell was written or a byte read or printed.
+	pub fn effect(&mut self) {
+		self.effects = self.effects.wrapping_add(1);
+	}
+
+	/// The body of the loop at `loop_start`, at nesting `depth`, is about to run with the pointer
+	/// at `data_ptr`. Tells whether the previous iteration was the same as this one will be.
+	pub fn iterate(&mut self, depth: usize, loop_start: usize, data_ptr: usize, looping_back: bool) -> bool {
+		let iteration = (loop_start, data_ptr, self.effects);
+		let stalled = looping_back && self.iterations.get(depth) == Some(&iteration);
+		self.iterations.truncate(depth);
+		self.iterations.push(iteration);
+		stalled
+	}
+}
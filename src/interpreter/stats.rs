@@ -0,0 +1,159 @@
+//! Execution counters collected by [`Interpreter::with_stats`].
+//!
+//! [`Interpreter::with_stats`]: crate::interpreter::Interpreter::with_stats
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use crate::interpreter::MEM_SIZE;
+use crate::symbol::InterpreterInstruction;
+
+/// Reads and writes of a single cell.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CellAccesses {
+	/// `.` and `[` reading the cell.
+	pub reads: u64,
+	pub writes: u64,
+}
+
+impl CellAccesses {
+	pub fn total(&self) -> u64 {
+		self.reads.saturating_add(self.writes)
+	}
+}
+
+/// Counts of what a run did. Counters saturate instead of wrapping.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunStats {
+	/// Instructions executed, not counting the ones skipped over by a loop with a zero cell.
+	pub steps: u64,
+	/// Executions per instruction, indexed by [`InterpreterInstruction`] discriminant.
+	executed: [u64; InterpreterInstruction::ALL.len()],
+	/// Times a `[` found a non-zero cell and entered its body.
+	pub loop_iterations: u64,
+	pub bytes_read: u64,
+	pub bytes_written: u64,
+	/// Highest cell the data pointer reached.
+	pub peak_data_ptr: usize,
+	/// Highest cell written, by the program or the initial tape.
+	pub highest_written: usize,
+	/// Cells from 0 to the highest written one, 0 when nothing was written.
+	pub dirty_len: usize,
+	/// Most loops running inside each other at once.
+	pub peak_loop_depth: usize,
+	/// Accesses of every cell that was accessed, empty unless enabled with
+	/// [`Interpreter::with_memory_profile`].
+	///
+	/// [`Interpreter::with_memory_profile`]: crate::interpreter::Interpreter::with_memory_profile
+	pub cells: BTreeMap<usize, CellAccesses>,
+	profile_cells: bool,
+}
+
+impl RunStats {
+	pub(crate) fn with_cells() -> Self {
+		RunStats { profile_cells: true, ..RunStats::default() }
+	}
+
+	/// The `count` most accessed cells, the lowest address first on ties.
+	pub fn hottest_cells(&self, count: usize) -> Vec<(usize, CellAccesses)> {
+		let mut cells: Vec<_> = self.cells.iter().map(|(&address, &accesses)| (address, accesses)).collect();
+		cells.sort_by(|(left_address, left), (right_address, right)| {
+			right.total().cmp(&left.total()).then(left_address.cmp(right_address))
+		});
+		cells.truncate(count);
+		cells
+	}
+
+	pub fn executed(&self, instruction: InterpreterInstruction) -> u64 {
+		self.executed[instruction as usize]
+	}
+
+	pub(crate) fn count(&mut self, instruction: InterpreterInstruction, data_ptr: usize) {
+		self.steps = self.steps.saturating_add(1);
+		let executed = &mut self.executed[instruction as usize];
+		*executed = executed.saturating_add(1);
+		self.peak_data_ptr = self.peak_data_ptr.max(data_ptr);
+	}
+
+	pub(crate) fn count_loop_iteration(&mut self) {
+		self.loop_iterations = self.loop_iterations.saturating_add(1);
+	}
+
+	pub(crate) fn count_cell_read(&mut self, address: usize) {
+		if self.profile_cells {
+			let accesses = self.cells.entry(address).or_default();
+			accesses.reads = accesses.reads.saturating_add(1);
+		}
+	}
+
+	pub(crate) fn count_cell_write(&mut self, address: usize) {
+		if self.profile_cells {
+			let accesses = self.cells.entry(address).or_default();
+			accesses.writes = accesses.writes.saturating_add(1);
+		}
+	}
+
+	pub(crate) fn count_read(&mut self) {
+		self.bytes_read = self.bytes_read.saturating_add(1);
+	}
+
+	pub(crate) fn count_written(&mut self) {
+		self.bytes_written = self.bytes_written.saturating_add(1);
+	}
+}
+
+impl Display for RunStats {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		writeln!(f, "Steps:           {:>12}", self.steps)?;
+		for instruction in InterpreterInstruction::ALL {
+			writeln!(f, "  `{}`:           {:>12}", instruction.to_char(), self.executed(instruction))?;
+		}
+		writeln!(f, "Loop iterations: {:>12}", self.loop_iterations)?;
+		writeln!(f, "Bytes read:      {:>12}", self.bytes_read)?;
+		writeln!(f, "Bytes written:   {:>12}", self.bytes_written)?;
+		writeln!(f, "Peak data ptr:   {:>12}", self.peak_data_ptr)?;
+		writeln!(f, "Highest written: {:>12}", self.highest_written)?;
+		writeln!(f, "Tape used:       {:>12} / {MEM_SIZE} cells", self.dirty_len)?;
+		write!(f, "Peak loop depth: {:>12}", self.peak_loop_depth)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::interpreter::io::CapturedOutput;
+	use crate::interpreter::Interpreter;
+
+	#[test]
+	fn counts_every_instruction_of_a_run() {
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new()).with_stats();
+		interpreter.feed_input(b"\x03");
+		let program: Vec<char> = ",[->++<]>>+<.".chars().collect();
+		interpreter.run(&program).unwrap();
+		let stats = interpreter.stats().unwrap();
+
+		let executed = InterpreterInstruction::ALL.map(|instruction| stats.executed(instruction));
+		// `>` `<` `+` `-` `.` `,` `[` `]`, the `[` once more to leave the loop
+		assert_eq!(executed, [5, 4, 7, 3, 1, 1, 4, 3]);
+		assert_eq!((stats.steps, stats.loop_iterations, stats.bytes_read, stats.bytes_written), (28, 3, 1, 1));
+		assert_eq!((stats.peak_data_ptr, stats.highest_written, stats.dirty_len, stats.peak_loop_depth), (2, 2, 3, 1));
+		assert!(stats.to_string().starts_with("Steps:                     28\n  `>`:                      5\n"));
+		assert!(stats.to_string().ends_with("Tape used:                  3 / 30000 cells\nPeak loop depth:            1"));
+	}
+
+	#[test]
+	fn counters_saturate() {
+		let mut stats = RunStats { steps: u64::MAX, loop_iterations: u64::MAX, ..RunStats::default() };
+		stats.executed[InterpreterInstruction::PrintPtr as usize] = u64::MAX;
+		stats.count(InterpreterInstruction::PrintPtr, 0);
+		stats.count_loop_iteration();
+		assert_eq!((stats.steps, stats.executed(InterpreterInstruction::PrintPtr), stats.loop_iterations), (u64::MAX, u64::MAX, u64::MAX));
+	}
+
+	#[test]
+	fn nothing_is_counted_unless_enabled() {
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new());
+		interpreter.run(&['+']).unwrap();
+		assert_eq!(interpreter.stats(), None);
+	}
+}
@@ -0,0 +1,64 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchCondition {
+	/// Any write to the cell, even one storing the value it already held.
+	Write,
+	/// A write turning the cell into the value.
+	Becomes(u8),
+	/// `.` or `[` reading the cell. The read half of `+` and `-` doesn't count.
+	Read,
+}
+
+/// Pauses a run when the cell at `address` is accessed in a way matching `condition`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+	pub address: usize,
+	pub condition: WatchCondition,
+}
+
+impl Watchpoint {
+	pub fn write(address: usize) -> Self {
+		Watchpoint { address, condition: WatchCondition::Write }
+	}
+
+	pub fn becomes(address: usize, value: u8) -> Self {
+		Watchpoint { address, condition: WatchCondition::Becomes(value) }
+	}
+
+	pub fn read(address: usize) -> Self {
+		Watchpoint { address, condition: WatchCondition::Read }
+	}
+
+	pub fn fires_on_write(&self, address: usize, old_value: u8, new_value: u8) -> bool {
+		self.address == address && match self.condition {
+			WatchCondition::Write => true,
+			WatchCondition::Becomes(value) => old_value != value && new_value == value,
+			WatchCondition::Read => false,
+		}
+	}
+
+	pub fn fires_on_read(&self, address: usize) -> bool {
+		self.address == address && self.condition == WatchCondition::Read
+	}
+}
+
+impl Display for Watchpoint {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self.condition {
+			WatchCondition::Write => write!(f, "watch {}", self.address),
+			WatchCondition::Becomes(value) => write!(f, "watch {} == {value}", self.address),
+			WatchCondition::Read => write!(f, "rwatch {}", self.address),
+		}
+	}
+}
+
+/// A watchpoint that fired, with the instruction that accessed the cell and the cell's value
+/// before and after it. Both values are the same for reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+	pub watchpoint: Watchpoint,
+	pub instruction_ptr: usize,
+	pub old_value: u8,
+	pub new_value: u8,
+}
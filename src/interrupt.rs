@@ -0,0 +1,102 @@
+//! Ctrl+C handling for `run`: the first press sets the interpreter's interrupt flag so the run
+//! stops and the machine state can be reported, a second one quits on the spot.
+//!
+//! The handler is registered through the C runtime directly, `signal` on Unix and
+//! `SetConsoleCtrlHandler` on Windows, as std has no API for it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Exit status after an interrupted run, the shell convention for SIGINT.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+static FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Whether Ctrl+C was pressed before, in which case the press just seen has to force quit.
+fn interrupt() -> bool {
+	FLAG.get().is_some_and(|flag| flag.swap(true, Ordering::Relaxed))
+}
+
+#[cfg(unix)]
+mod platform {
+	use std::ffi::c_int;
+
+	const SIGINT: c_int = 2;
+
+	extern "C" {
+		fn signal(signum: c_int, handler: usize) -> usize;
+		fn _exit(status: c_int) -> !;
+	}
+
+	extern "C" fn on_sigint(_: c_int) {
+		if super::interrupt() {
+			// Only async-signal-safe calls here, so no destructors or buffered output
+			unsafe { _exit(super::EXIT_INTERRUPTED) }
+		}
+	}
+
+	pub fn install() {
+		unsafe {
+			signal(SIGINT, on_sigint as extern "C" fn(c_int) as usize);
+		}
+	}
+}
+
+#[cfg(windows)]
+mod platform {
+	const CTRL_C_EVENT: u32 = 0;
+	const CTRL_BREAK_EVENT: u32 = 1;
+
+	#[link(name = "kernel32")]
+	extern "system" {
+		fn SetConsoleCtrlHandler(handler: extern "system" fn(u32) -> i32, add: i32) -> i32;
+		fn ExitProcess(exit_code: u32) -> !;
+	}
+
+	extern "system" fn on_ctrl(event: u32) -> i32 {
+		if event != CTRL_C_EVENT && event != CTRL_BREAK_EVENT {
+			return 0;
+		}
+		if super::interrupt() {
+			unsafe { ExitProcess(super::EXIT_INTERRUPTED as u32) }
+		}
+		1
+	}
+
+	pub fn install() {
+		unsafe {
+			SetConsoleCtrlHandler(on_ctrl, 1);
+		}
+	}
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+	pub fn install() {}
+}
+
+/// Routes Ctrl+C to the returned flag, meant for [`Interpreter::with_interrupt_flag`].
+///
+/// [`Interpreter::with_interrupt_flag`]: brainfuck::interpreter::Interpreter::with_interrupt_flag
+pub fn install() -> Arc<AtomicBool> {
+	let flag = FLAG.get_or_init(|| {
+		platform::install();
+		Arc::new(AtomicBool::new(false))
+	});
+	Arc::clone(flag)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn second_press_forces_quit() {
+		let flag = install();
+		flag.store(false, Ordering::Relaxed);
+		assert!(!interrupt(), "the first press only stops the run");
+		assert!(flag.load(Ordering::Relaxed));
+		assert!(interrupt());
+		flag.store(false, Ordering::Relaxed);
+	}
+}
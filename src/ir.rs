@@ -0,0 +1,492 @@
+pub mod bytecode;
+
+use std::fmt::{Display, Formatter};
+
+use crate::InterpreterSymbol;
+use crate::interpreter::MEM_SIZE;
+use crate::symbol::InterpreterInstruction;
+
+pub const DEFAULT_OPT_LEVEL: u8 = 1;
+
+/// A single operation of the compiled program.
+///
+/// Jump targets are absolute indices into the op list, pointing at the matching bracket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+	Move(isize),
+	Add(i8),
+	SetZero,
+	/// `[>]`: moves right until the current cell is zero.
+	ScanRight,
+	/// `[<]`: moves left until the current cell is zero.
+	ScanLeft,
+	/// Does the work of the loop right after it at once, leaving the current cell zero so the loop
+	/// is skipped. When a cell would overflow or the loop would leave the tape it does nothing, and
+	/// the loop runs as written to fail where it would have.
+	MulAdd(MulAdd),
+	Print,
+	Read,
+	JumpIfZero(usize),
+	JumpIfNonZero(usize),
+}
+
+/// A loop that counts the current cell down to zero, adding a multiple of it to other cells:
+/// `[->+<]` moves a value, `[->+++>+<<]` adds three times it to one cell and once to the next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MulAdd {
+	/// Offset from the current cell and what every iteration adds there, in the order the loop
+	/// visits them.
+	pub targets: Vec<(isize, i8)>,
+	/// The lowest and highest offsets the loop moves to.
+	pub reach: (isize, isize),
+}
+
+/// Where an op came from in the source, with 1-based line and column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+	pub offset: usize,
+	pub line: usize,
+	pub column: usize,
+}
+
+impl Span {
+	/// The span of every character of `characters`, in order.
+	pub fn table(characters: &[char]) -> Vec<Span> {
+		let (mut line, mut column) = (1usize, 1usize);
+		characters.iter().enumerate().map(|(offset, character)| {
+			let span = Span { offset, line, column };
+			if *character == '\n' {
+				line += 1;
+				column = 1;
+			} else {
+				column += 1;
+			}
+			span
+		}).collect()
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CompileError {
+	/// A `[` with no matching `]`.
+	UnmatchedLoopStart(Span),
+	/// A `]` with no matching `[`.
+	UnmatchedLoopEnd(Span),
+}
+
+/// Every unmatched bracket of `characters`, in source order, unlike [`Ir::compile`] which stops at
+/// the first.
+pub fn bracket_errors(characters: &[char]) -> Vec<CompileError> {
+	let mut errors = Vec::new();
+	let mut open_loops: Vec<Span> = Vec::new();
+	for (character, span) in characters.iter().zip(Span::table(characters)) {
+		match character {
+			'[' => open_loops.push(span),
+			']' if open_loops.pop().is_none() => errors.push(CompileError::UnmatchedLoopEnd(span)),
+			_ => {}
+		}
+	}
+	errors.extend(open_loops.into_iter().map(CompileError::UnmatchedLoopStart));
+	errors.sort_by_key(|error| match error {
+		CompileError::UnmatchedLoopStart(span) | CompileError::UnmatchedLoopEnd(span) => span.offset,
+	});
+	errors
+}
+
+/// A program compiled into a flat list of ops with resolved jumps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ir {
+	ops: Vec<Op>,
+	spans: Vec<Span>,
+}
+
+impl Ir {
+	/// Compiles `characters`, applying the peephole passes when `opt_level` is above zero:
+	/// same-direction runs of `+`/`-` and `<`/`>` become a single op, `[-]` becomes `SetZero`,
+	/// `[>]`/`[<]` become `ScanRight`/`ScanLeft` and copy loops get a `MulAdd`.
+	pub fn compile(characters: &[char], opt_level: u8) -> Result<Self, CompileError> {
+		let (ops, spans) = tokenize(characters)?;
+		let (mut ops, spans) = if opt_level > 0 {
+			optimize(&ops, &spans)
+		} else {
+			(ops, spans)
+		};
+		link(&mut ops);
+
+		Ok(Ir { ops, spans })
+	}
+
+	pub fn ops(&self) -> &[Op] {
+		&self.ops
+	}
+
+	pub fn spans(&self) -> &[Span] {
+		&self.spans
+	}
+
+	/// How deep the loops nest, not counting those folded into a single op.
+	pub fn max_depth(&self) -> usize {
+		let (mut depth, mut max_depth) = (0usize, 0usize);
+		for op in &self.ops {
+			match op {
+				Op::JumpIfZero(_) => {
+					depth += 1;
+					max_depth = max_depth.max(depth);
+				}
+				Op::JumpIfNonZero(_) => depth -= 1,
+				_ => {}
+			}
+		}
+		max_depth
+	}
+
+	/// Renders the ops back as brainfuck source with the same behavior, which is how compiled
+	/// programs are run by the interpreter.
+	pub fn to_brainfuck(&self) -> String {
+		let mut source = String::new();
+		for op in &self.ops {
+			render(op, &mut source);
+		}
+		source
+	}
+
+	/// [`Ir::to_brainfuck`], every op then spanning where it is in the rendering rather than in a
+	/// source that may be long gone, as for a `.bfc` file.
+	pub fn respan_to_brainfuck(&mut self) -> String {
+		let mut source = String::new();
+		for (op, span) in self.ops.iter().zip(&mut self.spans) {
+			// Only brackets and instructions, one byte each
+			*span = Span { offset: source.len(), line: 1, column: source.len() + 1 };
+			render(op, &mut source);
+		}
+		source
+	}
+}
+
+impl Display for Op {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Op::Move(delta) => write!(f, "Move({delta:+})"),
+			Op::Add(delta) => write!(f, "Add({delta:+})"),
+			Op::SetZero => write!(f, "SetZero"),
+			Op::ScanRight => write!(f, "ScanRight"),
+			Op::ScanLeft => write!(f, "ScanLeft"),
+			Op::MulAdd(mul_add) => {
+				write!(f, "MulAdd(")?;
+				for (idx, (offset, factor)) in mul_add.targets.iter().enumerate() {
+					let separator = if idx > 0 { ", " } else { "" };
+					write!(f, "{separator}{offset:+}: {factor:+}")?;
+				}
+				write!(f, ")")
+			}
+			Op::Print => write!(f, "Print"),
+			Op::Read => write!(f, "Read"),
+			Op::JumpIfZero(target) => write!(f, "JumpIfZero -> {target:04}"),
+			Op::JumpIfNonZero(target) => write!(f, "JumpIfNonZero -> {target:04}"),
+		}
+	}
+}
+
+impl Display for Ir {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		for (idx, (op, span)) in self.ops.iter().zip(&self.spans).enumerate() {
+			let op = op.to_string();
+			writeln!(f, "{idx:04} {op:<24} ; line {}, col {}", span.line, span.column)?;
+		}
+		Ok(())
+	}
+}
+
+/// Appends the brainfuck doing what `op` does to `source`.
+fn render(op: &Op, source: &mut String) {
+	match *op {
+		// The loop after it has the same effect
+		Op::MulAdd(_) => {}
+		Op::Move(delta) => {
+			let c = if delta > 0 { '>' } else { '<' };
+			source.extend(std::iter::repeat_n(c, delta.unsigned_abs()));
+		}
+		Op::Add(delta) => {
+			let c = if delta > 0 { '+' } else { '-' };
+			source.extend(std::iter::repeat_n(c, delta.unsigned_abs() as usize));
+		}
+		Op::SetZero => source.push_str("[-]"),
+		Op::ScanRight => source.push_str("[>]"),
+		Op::ScanLeft => source.push_str("[<]"),
+		Op::Print => source.push('.'),
+		Op::Read => source.push(','),
+		Op::JumpIfZero(_) => source.push('['),
+		Op::JumpIfNonZero(_) => source.push(']'),
+	}
+}
+
+/// One op per instruction character, jumps left unresolved.
+fn tokenize(characters: &[char]) -> Result<(Vec<Op>, Vec<Span>), CompileError> {
+	let mut ops = Vec::new();
+	let mut spans = Vec::new();
+	let mut open_loops: Vec<Span> = Vec::new();
+
+	for (character, span) in characters.iter().zip(Span::table(characters)) {
+		let instruction = match InterpreterSymbol::from_char(Some(character)) {
+			InterpreterSymbol::Instruction(instruction) => instruction,
+			_ => continue,
+		};
+
+		let op = match instruction {
+			InterpreterInstruction::MovePtrRight => Op::Move(1),
+			InterpreterInstruction::MovePtrLeft => Op::Move(-1),
+			InterpreterInstruction::IncrementPtr => Op::Add(1),
+			InterpreterInstruction::DecrementPtr => Op::Add(-1),
+			InterpreterInstruction::PrintPtr => Op::Print,
+			InterpreterInstruction::ReadPtr => Op::Read,
+			InterpreterInstruction::LoopStart => {
+				open_loops.push(span);
+				Op::JumpIfZero(0)
+			}
+			InterpreterInstruction::LoopEnd => {
+				open_loops.pop().ok_or(CompileError::UnmatchedLoopEnd(span))?;
+				Op::JumpIfNonZero(0)
+			}
+		};
+		ops.push(op);
+		spans.push(span);
+	}
+
+	if let Some(&span) = open_loops.first() {
+		return Err(CompileError::UnmatchedLoopStart(span));
+	}
+
+	Ok((ops, spans))
+}
+
+fn optimize(ops: &[Op], spans: &[Span]) -> (Vec<Op>, Vec<Span>) {
+	let mut optimized_ops: Vec<Op> = Vec::with_capacity(ops.len());
+	let mut optimized_spans = Vec::with_capacity(spans.len());
+
+	let mut idx = 0;
+	while idx < ops.len() {
+		let replacement = match ops[idx..] {
+			[Op::JumpIfZero(_), Op::Add(-1), Op::JumpIfNonZero(_), ..] => Some(Op::SetZero),
+			[Op::JumpIfZero(_), Op::Move(1), Op::JumpIfNonZero(_), ..] => Some(Op::ScanRight),
+			[Op::JumpIfZero(_), Op::Move(-1), Op::JumpIfNonZero(_), ..] => Some(Op::ScanLeft),
+			_ => None,
+		};
+		if let Some(op) = replacement {
+			optimized_ops.push(op);
+			optimized_spans.push(spans[idx]);
+			idx += 3;
+			continue;
+		}
+		if let Op::JumpIfZero(_) = ops[idx] {
+			if let Some(mul_add) = mul_add(&ops[idx + 1..]) {
+				#[cfg(test)]
+				let mul_add = miscompiled(mul_add);
+				optimized_ops.push(Op::MulAdd(mul_add));
+				optimized_spans.push(spans[idx]);
+			}
+		}
+
+		// Only same-direction runs are folded, so a checked add or move still fails where the
+		// unfolded sequence would have
+		let folded = match (optimized_ops.last_mut(), &ops[idx]) {
+			(Some(Op::Add(last)), &Op::Add(delta)) if last.signum() == delta.signum() => {
+				last.checked_add(delta).map(|sum| *last = sum).is_some()
+			}
+			// Never past the length of the tape, as far as bytecode allows a move to go
+			(Some(Op::Move(last)), &Op::Move(delta)) if last.signum() == delta.signum() && last.unsigned_abs() < MEM_SIZE => {
+				*last += delta;
+				true
+			}
+			_ => false,
+		};
+		if !folded {
+			optimized_ops.push(ops[idx].clone());
+			optimized_spans.push(spans[idx]);
+		}
+		idx += 1;
+	}
+
+	(optimized_ops, optimized_spans)
+}
+
+/// The [`MulAdd`] doing the work of the loop whose body starts `body`, if the loop only moves by
+/// single cells and adds, returns to where it started and takes exactly one off the current cell.
+/// Every other cell must only go up or only go down, so checking the final values is enough to
+/// know none of them overflows along the way.
+fn mul_add(body: &[Op]) -> Option<MulAdd> {
+	let mut offset = 0isize;
+	let mut reach = (0, 0);
+	let mut targets: Vec<(isize, i8)> = Vec::new();
+
+	for op in body {
+		match *op {
+			Op::Move(delta) => {
+				offset += delta;
+				reach = (reach.0.min(offset), reach.1.max(offset));
+			}
+			Op::Add(delta) => match targets.iter_mut().find(|(target, _)| *target == offset) {
+				Some((_, total)) if total.signum() == delta.signum() => *total = total.checked_add(delta)?,
+				Some(_) => return None,
+				None => targets.push((offset, delta)),
+			},
+			Op::JumpIfNonZero(_) => {
+				let counter = targets.iter().position(|&(target, _)| target == 0)?;
+				if offset != 0 || targets.remove(counter).1 != -1 {
+					return None;
+				}
+				return Some(MulAdd { targets, reach });
+			}
+			_ => return None,
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+thread_local! {
+	/// Set by tests that need a miscompile to catch: copy loops then add one more per iteration.
+	pub(crate) static BREAK_MUL_ADD: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+#[cfg(test)]
+fn miscompiled(mut mul_add: MulAdd) -> MulAdd {
+	if BREAK_MUL_ADD.get() {
+		for (_, factor) in &mut mul_add.targets {
+			*factor += 1;
+		}
+	}
+	mul_add
+}
+
+/// Resolves every jump to its matching bracket. The brackets must be balanced.
+fn link(ops: &mut [Op]) {
+	let mut open_loops = Vec::new();
+	for idx in 0..ops.len() {
+		match ops[idx] {
+			Op::JumpIfZero(_) => open_loops.push(idx),
+			Op::JumpIfNonZero(_) => {
+				let start = open_loops.pop().expect("Brackets are validated while tokenizing");
+				ops[start] = Op::JumpIfZero(idx);
+				ops[idx] = Op::JumpIfNonZero(start);
+			}
+			_ => {}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn ops(source: &str) -> Vec<Op> {
+		let characters: Vec<char> = source.chars().collect();
+		Ir::compile(&characters, DEFAULT_OPT_LEVEL).unwrap().ops
+	}
+
+	#[test]
+	fn respanned_ops_point_into_the_rendering() {
+		let characters: Vec<char> = "+ +\n[>]\n.".chars().collect();
+		let mut ir = Ir::compile(&characters, DEFAULT_OPT_LEVEL).unwrap();
+		assert_eq!(ir.spans()[2].offset, 8);
+		assert_eq!(ir.respan_to_brainfuck(), "++[>].");
+		let offsets: Vec<usize> = ir.spans().iter().map(|span| span.offset).collect();
+		assert_eq!(offsets, [0, 2, 5]);
+		assert_eq!(ir.spans()[2], Span { offset: 5, line: 1, column: 6 });
+	}
+
+	#[test]
+	fn dumps_match_their_golden_file() {
+		let dump = |source: &str, opt_level| {
+			let characters: Vec<char> = source.chars().collect();
+			Ir::compile(&characters, opt_level).unwrap().to_string()
+		};
+		assert_eq!(dump("++\n[->+++<]>.\n", 0), include_str!("ir/golden/mul_add_o0.txt"));
+		assert_eq!(dump("++\n[->+++<]>.\n", 1), include_str!("ir/golden/mul_add_o1.txt"));
+		assert_eq!(dump(",[.,]\n+[>]<[-]\n", 1), include_str!("ir/golden/echo_scan_o1.txt"));
+	}
+
+	#[test]
+	fn scan_loops_become_one_op() {
+		assert_eq!(ops("[>]"), [Op::ScanRight]);
+		assert_eq!(ops("+[<]."), [Op::Add(1), Op::ScanLeft, Op::Print]);
+	}
+
+	#[test]
+	fn scan_loops_stay_loops_unoptimized() {
+		let characters: Vec<char> = "[>]".chars().collect();
+		let ir = Ir::compile(&characters, 0).unwrap();
+		assert_eq!(ir.ops, [Op::JumpIfZero(2), Op::Move(1), Op::JumpIfNonZero(0)]);
+	}
+
+	#[test]
+	fn wider_scans_stay_loops() {
+		assert_eq!(ops("[>>]"), [Op::JumpIfZero(2), Op::Move(2), Op::JumpIfNonZero(0)]);
+	}
+
+	#[test]
+	fn copy_loop_gets_mul_add() {
+		assert_eq!(ops("[->+<]"), [
+			Op::MulAdd(MulAdd { targets: vec![(1, 1)], reach: (0, 1) }),
+			Op::JumpIfZero(6),
+			Op::Add(-1),
+			Op::Move(1),
+			Op::Add(1),
+			Op::Move(-1),
+			Op::JumpIfNonZero(1),
+		]);
+	}
+
+	#[test]
+	fn multiply_loop_gets_every_target() {
+		let Op::MulAdd(mul_add) = &ops("[>+++>-<<-]")[0] else { panic!("expected a MulAdd") };
+		assert_eq!(mul_add, &MulAdd { targets: vec![(1, 3), (2, -1)], reach: (0, 2) });
+		let Op::MulAdd(mul_add) = &ops("[-<++>]")[0] else { panic!("expected a MulAdd") };
+		assert_eq!(mul_add, &MulAdd { targets: vec![(-1, 2)], reach: (-1, 0) });
+	}
+
+	#[test]
+	fn other_loops_get_no_mul_add() {
+		let has_mul_add = |source| ops(source).iter().any(|op| matches!(op, Op::MulAdd(_)));
+		// Doesn't return to the current cell
+		assert!(!has_mul_add("[->+<<]"));
+		// Counts down by two
+		assert!(!has_mul_add("[-->+<]"));
+		// Goes both up and down on a cell
+		assert!(!has_mul_add("[->+-<]"));
+		// Does more than add
+		assert!(!has_mul_add("[->.<]"));
+		assert!(!has_mul_add("[->[-]<]"));
+	}
+
+	#[test]
+	fn mul_add_renders_as_its_loop() {
+		let characters: Vec<char> = "[->++<]".chars().collect();
+		assert_eq!(Ir::compile(&characters, DEFAULT_OPT_LEVEL).unwrap().to_brainfuck(), "[->++<]");
+	}
+
+	#[test]
+	fn scans_stop_on_the_first_zero_cell() {
+		use crate::interpreter::Interpreter;
+		use crate::interpreter::error::InterpreterErrorReason;
+
+		let region = vec![1u8; 20_000];
+		let right = Ir::compile(&"  [>]+".chars().collect::<Vec<_>>(), DEFAULT_OPT_LEVEL).unwrap();
+		let mut interpreter = Interpreter::new().with_initial_tape(&region).unwrap();
+		interpreter.run_ir(&right).unwrap();
+		assert!(interpreter.is_halted());
+		assert_eq!((interpreter.get_data_ptr(), interpreter.tape()[20_000]), (20_000, 1));
+
+		let left = Ir::compile(&"  [<]+".chars().collect::<Vec<_>>(), DEFAULT_OPT_LEVEL).unwrap();
+		let mut tape = vec![0u8; 20_001];
+		tape[100..].fill(1);
+		let mut interpreter = Interpreter::new().with_initial_tape(&tape).unwrap().with_start_ptr(20_000).unwrap();
+		interpreter.run_ir(&left).unwrap();
+		assert_eq!((interpreter.get_data_ptr(), interpreter.tape()[99]), (99, 1));
+
+		// Off the edge, failing at the scan as the loop would
+		let mut interpreter = Interpreter::new().with_initial_tape(&[1; MEM_SIZE]).unwrap();
+		let err = interpreter.run_ir(&right).unwrap_err();
+		assert!(matches!(err.reason, InterpreterErrorReason::PtrOutOfBounds(ptr) if ptr == MEM_SIZE - 1));
+		assert_eq!((interpreter.get_data_ptr(), interpreter.get_instruction_ptr()), (MEM_SIZE - 1, 2));
+	}
+}
@@ -0,0 +1,375 @@
+//! Compact binary form of an [`Ir`], stored in `.bfc` files.
+//!
+//! Layout: the [`MAGIC`] bytes, a format version byte, the cell width in bits, a flags byte, then
+//! the op count and each op as a tag byte followed by its varint operand, if any. When
+//! [`FLAG_SPANS`] is set every op is followed by its source span as three varints: the offset
+//! delta from the previous op, the line delta and the column.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::interpreter::MEM_SIZE;
+use crate::ir::{Ir, MulAdd, Op, Span};
+
+pub const MAGIC: [u8; 4] = *b"BFC\0";
+pub const FORMAT_VERSION: u8 = 1;
+pub const CELL_WIDTH: u8 = 8;
+pub const FLAG_SPANS: u8 = 0b0000_0001;
+
+const TAG_MOVE: u8 = 0;
+const TAG_ADD: u8 = 1;
+const TAG_SET_ZERO: u8 = 2;
+const TAG_PRINT: u8 = 3;
+const TAG_READ: u8 = 4;
+const TAG_JUMP_IF_ZERO: u8 = 5;
+const TAG_JUMP_IF_NON_ZERO: u8 = 6;
+const TAG_SCAN_RIGHT: u8 = 7;
+const TAG_SCAN_LEFT: u8 = 8;
+const TAG_MUL_ADD: u8 = 9;
+
+#[derive(Debug)]
+pub enum LoadError {
+	Io(io::Error),
+	BadMagic,
+	UnsupportedVersion(u8),
+	UnsupportedCellWidth(u8),
+	/// The file ended in the middle of the header or an op.
+	Truncated,
+	/// Unknown op tag at the given byte offset.
+	InvalidOp { offset: usize, tag: u8 },
+	/// An operand that doesn't fit its op, at the given byte offset.
+	InvalidOperand { offset: usize },
+	/// The op at the given index jumps somewhere other than its matching bracket.
+	InvalidJump(usize),
+	/// Extra bytes after the last op.
+	TrailingBytes,
+}
+
+impl Ir {
+	/// Whether `bytes` look like a serialized program rather than brainfuck source.
+	pub fn is_bytecode(bytes: &[u8]) -> bool {
+		bytes.starts_with(&MAGIC)
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::from(MAGIC);
+		bytes.extend([FORMAT_VERSION, CELL_WIDTH, FLAG_SPANS]);
+		write_varint(&mut bytes, self.ops.len() as u64);
+
+		let mut last_span = Span { offset: 0, line: 1, column: 1 };
+		for (op, span) in self.ops.iter().zip(&self.spans) {
+			match op {
+				&Op::Move(delta) => {
+					bytes.push(TAG_MOVE);
+					write_varint(&mut bytes, zigzag(delta as i64));
+				}
+				&Op::Add(delta) => {
+					bytes.push(TAG_ADD);
+					write_varint(&mut bytes, zigzag(delta as i64));
+				}
+				Op::SetZero => bytes.push(TAG_SET_ZERO),
+				Op::ScanRight => bytes.push(TAG_SCAN_RIGHT),
+				Op::ScanLeft => bytes.push(TAG_SCAN_LEFT),
+				Op::Print => bytes.push(TAG_PRINT),
+				Op::Read => bytes.push(TAG_READ),
+				Op::MulAdd(mul_add) => {
+					bytes.push(TAG_MUL_ADD);
+					write_varint(&mut bytes, mul_add.targets.len() as u64);
+					for &(offset, factor) in &mul_add.targets {
+						write_varint(&mut bytes, zigzag(offset as i64));
+						write_varint(&mut bytes, zigzag(factor as i64));
+					}
+					write_varint(&mut bytes, zigzag(mul_add.reach.0 as i64));
+					write_varint(&mut bytes, zigzag(mul_add.reach.1 as i64));
+				}
+				&Op::JumpIfZero(target) => {
+					bytes.push(TAG_JUMP_IF_ZERO);
+					write_varint(&mut bytes, target as u64);
+				}
+				&Op::JumpIfNonZero(target) => {
+					bytes.push(TAG_JUMP_IF_NON_ZERO);
+					write_varint(&mut bytes, target as u64);
+				}
+			}
+
+			write_varint(&mut bytes, (span.offset - last_span.offset) as u64);
+			write_varint(&mut bytes, (span.line - last_span.line) as u64);
+			write_varint(&mut bytes, span.column as u64);
+			last_span = *span;
+		}
+
+		bytes
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoadError> {
+		let mut reader = ByteReader { bytes, position: 0 };
+
+		if reader.take(MAGIC.len())? != MAGIC {
+			return Err(LoadError::BadMagic);
+		}
+		let version = reader.byte()?;
+		if version != FORMAT_VERSION {
+			return Err(LoadError::UnsupportedVersion(version));
+		}
+		let cell_width = reader.byte()?;
+		if cell_width != CELL_WIDTH {
+			return Err(LoadError::UnsupportedCellWidth(cell_width));
+		}
+		let has_spans = reader.byte()? & FLAG_SPANS != 0;
+
+		let op_count = reader.varint()? as usize;
+		// Every op takes at least a byte, which bounds the allocation for corrupt counts
+		let mut ops = Vec::with_capacity(op_count.min(bytes.len()));
+		let mut spans = Vec::with_capacity(op_count.min(bytes.len()));
+		let mut last_span = Span { offset: 0, line: 1, column: 1 };
+
+		for idx in 0..op_count {
+			let op_offset = reader.position;
+			let op = match reader.byte()? {
+				TAG_MOVE => Op::Move(reader.tape_offset(op_offset)?),
+				TAG_ADD => Op::Add(reader.signed_operand(op_offset)?),
+				TAG_SET_ZERO => Op::SetZero,
+				TAG_SCAN_RIGHT => Op::ScanRight,
+				TAG_SCAN_LEFT => Op::ScanLeft,
+				TAG_MUL_ADD => Op::MulAdd(reader.mul_add(op_offset)?),
+				TAG_PRINT => Op::Print,
+				TAG_READ => Op::Read,
+				TAG_JUMP_IF_ZERO => Op::JumpIfZero(reader.unsigned_operand(op_offset)?),
+				TAG_JUMP_IF_NON_ZERO => Op::JumpIfNonZero(reader.unsigned_operand(op_offset)?),
+				tag => return Err(LoadError::InvalidOp { offset: op_offset, tag }),
+			};
+
+			let span = if has_spans {
+				let offset_delta: usize = reader.unsigned_operand(op_offset)?;
+				let line_delta: usize = reader.unsigned_operand(op_offset)?;
+				let column = reader.unsigned_operand(op_offset)?;
+				let offset = last_span.offset.checked_add(offset_delta);
+				let line = last_span.line.checked_add(line_delta);
+				let (Some(offset), Some(line)) = (offset, line) else {
+					return Err(LoadError::InvalidOperand { offset: op_offset });
+				};
+				Span { offset, line, column }
+			} else {
+				Span { offset: idx, line: 1, column: idx + 1 }
+			};
+
+			ops.push(op);
+			spans.push(span);
+			last_span = span;
+		}
+
+		if reader.position != bytes.len() {
+			return Err(LoadError::TrailingBytes);
+		}
+		validate_jumps(&ops)?;
+
+		Ok(Ir { ops, spans })
+	}
+
+	pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+		fs::write(path, self.to_bytes())
+	}
+
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+		let bytes = fs::read(path).map_err(LoadError::Io)?;
+		Self::from_bytes(&bytes)
+	}
+}
+
+/// Every jump must point at a jump of the opposite kind pointing back at it, nested properly.
+fn validate_jumps(ops: &[Op]) -> Result<(), LoadError> {
+	let mut open_loops = Vec::new();
+	for (idx, op) in ops.iter().enumerate() {
+		match *op {
+			Op::JumpIfZero(target) => {
+				let matches = ops.get(target) == Some(&Op::JumpIfNonZero(idx));
+				if !matches {
+					return Err(LoadError::InvalidJump(idx));
+				}
+				open_loops.push(idx);
+			}
+			Op::JumpIfNonZero(target) => {
+				let start = open_loops.pop();
+				if start != Some(target) {
+					return Err(LoadError::InvalidJump(idx));
+				}
+			}
+			_ => {}
+		}
+	}
+
+	if let Some(&idx) = open_loops.last() {
+		return Err(LoadError::InvalidJump(idx));
+	}
+	Ok(())
+}
+
+/// Reads the varint encoded parts of a file, also used for core dumps.
+pub(crate) struct ByteReader<'a> {
+	pub(crate) bytes: &'a [u8],
+	pub(crate) position: usize,
+}
+
+impl ByteReader<'_> {
+	pub(crate) fn take(&mut self, count: usize) -> Result<&[u8], LoadError> {
+		let end = self.position.checked_add(count).ok_or(LoadError::Truncated)?;
+		let taken = self.bytes.get(self.position..end).ok_or(LoadError::Truncated)?;
+		self.position = end;
+		Ok(taken)
+	}
+
+	pub(crate) fn byte(&mut self) -> Result<u8, LoadError> {
+		Ok(self.take(1)?[0])
+	}
+
+	pub(crate) fn varint(&mut self) -> Result<u64, LoadError> {
+		let mut value = 0u64;
+		for shift in (0..64).step_by(7) {
+			let byte = self.byte()?;
+			value |= u64::from(byte & 0x7F) << shift;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+		Err(LoadError::InvalidOperand { offset: self.position })
+	}
+
+	fn mul_add(&mut self, offset: usize) -> Result<MulAdd, LoadError> {
+		let count: usize = self.unsigned_operand(offset)?;
+		// Every target takes at least two bytes, which bounds the allocation for corrupt counts
+		let mut targets = Vec::with_capacity(count.min(self.bytes.len() / 2));
+		for _ in 0..count {
+			targets.push((self.tape_offset(offset)?, self.signed_operand(offset)?));
+		}
+		let reach = (self.tape_offset(offset)?, self.tape_offset(offset)?);
+		Ok(MulAdd { targets, reach })
+	}
+
+	/// A distance along the tape, which no program moves further than the tape is long. Anything
+	/// more is corrupt, and would render into that many `<` or `>`.
+	fn tape_offset(&mut self, offset: usize) -> Result<isize, LoadError> {
+		let delta: isize = self.signed_operand(offset)?;
+		if delta.unsigned_abs() > MEM_SIZE {
+			return Err(LoadError::InvalidOperand { offset });
+		}
+		Ok(delta)
+	}
+
+	pub(crate) fn unsigned_operand<T: TryFrom<u64>>(&mut self, offset: usize) -> Result<T, LoadError> {
+		T::try_from(self.varint()?).map_err(|_| LoadError::InvalidOperand { offset })
+	}
+
+	pub(crate) fn signed_operand<T: TryFrom<i64>>(&mut self, offset: usize) -> Result<T, LoadError> {
+		T::try_from(unzigzag(self.varint()?)).map_err(|_| LoadError::InvalidOperand { offset })
+	}
+}
+
+pub(crate) fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+	loop {
+		let byte = (value & 0x7F) as u8;
+		value >>= 7;
+		if value == 0 {
+			bytes.push(byte);
+			break;
+		}
+		bytes.push(byte | 0x80);
+	}
+}
+
+fn zigzag(value: i64) -> u64 {
+	((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+	((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn compile(source: &str) -> Ir {
+		let characters: Vec<char> = source.chars().collect();
+		Ir::compile(&characters, crate::ir::DEFAULT_OPT_LEVEL).unwrap()
+	}
+
+	/// A file without spans holding `ops`, each a tag and its operand bytes.
+	fn encoded(ops: &[&[u8]]) -> Vec<u8> {
+		let mut bytes = Vec::from(MAGIC);
+		bytes.extend([FORMAT_VERSION, CELL_WIDTH, 0]);
+		write_varint(&mut bytes, ops.len() as u64);
+		ops.iter().for_each(|op| bytes.extend(*op));
+		bytes
+	}
+
+	#[test]
+	fn round_trips_every_op() {
+		let ir = compile(",[->++>-<<]>[-]+[>]<[<]\n.>>");
+		assert!(ir.ops().iter().any(|op| matches!(op, Op::MulAdd(_))));
+		let bytes = ir.to_bytes();
+		assert!(Ir::is_bytecode(&bytes));
+		assert_eq!(Ir::from_bytes(&bytes).unwrap(), ir);
+	}
+
+	#[test]
+	fn loads_without_spans() {
+		let ir = Ir::from_bytes(&encoded(&[&[TAG_ADD, 2], &[TAG_PRINT]])).unwrap();
+		assert_eq!(ir.ops(), [Op::Add(1), Op::Print]);
+		assert_eq!(ir.spans()[1], Span { offset: 1, line: 1, column: 2 });
+	}
+
+	#[test]
+	fn rejects_bad_header() {
+		assert!(matches!(Ir::from_bytes(b"+++."), Err(LoadError::BadMagic)));
+		assert!(matches!(Ir::from_bytes(&MAGIC), Err(LoadError::Truncated)));
+		let mut bytes = encoded(&[]);
+		bytes[MAGIC.len()] = FORMAT_VERSION + 1;
+		assert!(matches!(Ir::from_bytes(&bytes), Err(LoadError::UnsupportedVersion(_))));
+		let mut bytes = encoded(&[]);
+		bytes[MAGIC.len() + 1] = 16;
+		assert!(matches!(Ir::from_bytes(&bytes), Err(LoadError::UnsupportedCellWidth(16))));
+	}
+
+	#[test]
+	fn rejects_corrupt_ops() {
+		assert!(matches!(
+			Ir::from_bytes(&encoded(&[&[TAG_PRINT], &[0xFF]])),
+			Err(LoadError::InvalidOp { tag: 0xFF, .. })
+		));
+		assert!(matches!(Ir::from_bytes(&encoded(&[&[TAG_ADD]])), Err(LoadError::Truncated)));
+		// 128 doesn't fit an `Add`
+		assert!(matches!(
+			Ir::from_bytes(&encoded(&[&[TAG_ADD, 0x80, 0x02]])),
+			Err(LoadError::InvalidOperand { .. })
+		));
+		let mut bytes = encoded(&[&[TAG_PRINT]]);
+		bytes.push(0);
+		assert!(matches!(Ir::from_bytes(&bytes), Err(LoadError::TrailingBytes)));
+	}
+
+	#[test]
+	fn rejects_unmatched_jumps() {
+		assert!(matches!(Ir::from_bytes(&encoded(&[&[TAG_JUMP_IF_ZERO, 0]])), Err(LoadError::InvalidJump(0))));
+		assert!(matches!(
+			Ir::from_bytes(&encoded(&[&[TAG_JUMP_IF_ZERO, 1], &[TAG_JUMP_IF_NON_ZERO, 1]])),
+			Err(LoadError::InvalidJump(0))
+		));
+		assert!(matches!(
+			Ir::from_bytes(&encoded(&[&[TAG_PRINT], &[TAG_JUMP_IF_NON_ZERO, 0]])),
+			Err(LoadError::InvalidJump(1))
+		));
+	}
+
+	#[test]
+	fn rejects_moves_past_the_tape() {
+		let mut huge_move = vec![TAG_MOVE];
+		write_varint(&mut huge_move, zigzag(MEM_SIZE as i64 + 1));
+		assert!(matches!(Ir::from_bytes(&encoded(&[&huge_move])), Err(LoadError::InvalidOperand { .. })));
+
+		// What the compiler makes of a longer run still loads
+		let ir = compile(&">".repeat(MEM_SIZE + 5));
+		assert_eq!(ir.ops(), [Op::Move(MEM_SIZE as isize), Op::Move(5)]);
+		assert_eq!(Ir::from_bytes(&ir.to_bytes()).unwrap(), ir);
+	}
+}
@@ -0,0 +1,237 @@
+//! Just enough JSON writing for the machine readable reports, keys in insertion order.
+
+use std::fmt::{Display, Formatter, Write};
+
+/// `text` as a JSON string literal, quotes included.
+pub fn string(text: &str) -> String {
+	let mut literal = String::with_capacity(text.len() + 2);
+	literal.push('"');
+	for character in text.chars() {
+		match character {
+			'"' => literal.push_str("\\\""),
+			'\\' => literal.push_str("\\\\"),
+			'\n' => literal.push_str("\\n"),
+			'\r' => literal.push_str("\\r"),
+			'\t' => literal.push_str("\\t"),
+			control if control < ' ' => {
+				// Writing into a String can't fail
+				let _ = write!(literal, "\\u{:04x}", u32::from(control));
+			}
+			character => literal.push(character),
+		}
+	}
+	literal.push('"');
+	literal
+}
+
+/// An array of the already rendered `values`.
+pub fn array(values: impl IntoIterator<Item = impl Display>) -> String {
+	let values: Vec<String> = values.into_iter().map(|value| value.to_string()).collect();
+	format!("[{}]", values.join(","))
+}
+
+#[derive(Default)]
+pub struct Object {
+	fields: Vec<(String, String)>,
+}
+
+impl Object {
+	pub fn new() -> Self {
+		Object::default()
+	}
+
+	/// Adds a field holding `value` rendered as is, for numbers, arrays and nested objects.
+	pub fn raw(mut self, key: &str, value: impl Display) -> Self {
+		self.fields.push((key.to_string(), value.to_string()));
+		self
+	}
+
+	pub fn string(self, key: &str, value: &str) -> Self {
+		self.raw(key, string(value))
+	}
+
+	/// Adds `value`, or `null` without one.
+	pub fn optional(self, key: &str, value: Option<impl Display>) -> Self {
+		match value {
+			Some(value) => self.raw(key, value),
+			None => self.raw(key, "null"),
+		}
+	}
+}
+
+impl Display for Object {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{{")?;
+		for (idx, (key, value)) in self.fields.iter().enumerate() {
+			if idx > 0 {
+				write!(f, ",")?;
+			}
+			write!(f, "{}:{value}", string(key))?;
+		}
+		write!(f, "}}")
+	}
+}
+
+/// Reading JSON back, for tests checking what the reports wrote.
+#[cfg(test)]
+pub mod read {
+	/// A parsed JSON value.
+	#[derive(Debug, Clone, PartialEq)]
+	pub enum Value {
+		Null,
+		Bool(bool),
+		Number(f64),
+		String(String),
+		Array(Vec<Value>),
+		Object(Vec<(String, Value)>),
+	}
+
+	impl Value {
+		pub fn get(&self, key: &str) -> Option<&Value> {
+			match self {
+				Value::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+				_ => None,
+			}
+		}
+
+		pub fn as_u64(&self) -> Option<u64> {
+			match *self {
+				Value::Number(number) if number >= 0.0 && number.fract() == 0.0 => Some(number as u64),
+				_ => None,
+			}
+		}
+	}
+
+	/// Parses `text` as a single JSON document, `None` if it isn't one.
+	pub fn parse(text: &str) -> Option<Value> {
+		let mut parser = Parser { text: text.as_bytes(), position: 0 };
+		let value = parser.value()?;
+		parser.skip_whitespace();
+		(parser.position == text.len()).then_some(value)
+	}
+
+	struct Parser<'a> {
+		text: &'a [u8],
+		position: usize,
+	}
+
+	impl Parser<'_> {
+		fn skip_whitespace(&mut self) {
+			while self.text.get(self.position).is_some_and(u8::is_ascii_whitespace) {
+				self.position += 1;
+			}
+		}
+
+		fn eat(&mut self, byte: u8) -> bool {
+			self.skip_whitespace();
+			let found = self.text.get(self.position) == Some(&byte);
+			self.position += usize::from(found);
+			found
+		}
+
+		fn value(&mut self) -> Option<Value> {
+			self.skip_whitespace();
+			let rest = &self.text[self.position..];
+			for (literal, value) in [("null", Value::Null), ("true", Value::Bool(true)), ("false", Value::Bool(false))] {
+				if rest.starts_with(literal.as_bytes()) {
+					self.position += literal.len();
+					return Some(value);
+				}
+			}
+			match rest.first()? {
+				b'"' => self.string().map(Value::String),
+				b'[' => {
+					self.position += 1;
+					let mut values = Vec::new();
+					if !self.eat(b']') {
+						loop {
+							values.push(self.value()?);
+							if self.eat(b']') {
+								break;
+							}
+							self.eat(b',').then_some(())?;
+						}
+					}
+					Some(Value::Array(values))
+				}
+				b'{' => {
+					self.position += 1;
+					let mut fields = Vec::new();
+					if !self.eat(b'}') {
+						loop {
+							self.skip_whitespace();
+							let key = self.string()?;
+							self.eat(b':').then_some(())?;
+							fields.push((key, self.value()?));
+							if self.eat(b'}') {
+								break;
+							}
+							self.eat(b',').then_some(())?;
+						}
+					}
+					Some(Value::Object(fields))
+				}
+				_ => {
+					let length = rest.iter()
+						.position(|byte| !matches!(byte, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'))
+						.unwrap_or(rest.len());
+					self.position += length;
+					std::str::from_utf8(&rest[..length]).ok()?.parse().ok().map(Value::Number)
+				}
+			}
+		}
+
+		fn string(&mut self) -> Option<String> {
+			(self.text.get(self.position) == Some(&b'"')).then_some(())?;
+			self.position += 1;
+			let mut string = String::new();
+			loop {
+				let character = std::str::from_utf8(&self.text[self.position..]).ok()?.chars().next()?;
+				self.position += character.len_utf8();
+				match character {
+					'"' => return Some(string),
+					'\\' => {
+						let escape = *self.text.get(self.position)?;
+						self.position += 1;
+						string.push(match escape {
+							b'"' => '"',
+							b'\\' => '\\',
+							b'/' => '/',
+							b'n' => '\n',
+							b'r' => '\r',
+							b't' => '\t',
+							b'u' => {
+								let code = std::str::from_utf8(self.text.get(self.position..self.position + 4)?).ok()?;
+								self.position += 4;
+								char::from_u32(u32::from_str_radix(code, 16).ok()?)?
+							}
+							_ => return None,
+						});
+					}
+					character => string.push(character),
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::read::{parse, Value};
+
+	#[test]
+	fn written_documents_parse_back() {
+		let object = Object::new()
+			.string("name", "a \"quoted\"\n\u{1}")
+			.raw("count", 3)
+			.optional("missing", None::<u8>)
+			.raw("cells", array([1, 2]));
+		let parsed = parse(&object.to_string()).unwrap();
+		assert_eq!(parsed.get("name"), Some(&Value::String("a \"quoted\"\n\u{1}".to_string())));
+		assert_eq!(parsed.get("count").and_then(Value::as_u64), Some(3));
+		assert_eq!(parsed.get("missing"), Some(&Value::Null));
+		assert_eq!(parsed.get("cells"), Some(&Value::Array(vec![Value::Number(1.0), Value::Number(2.0)])));
+		assert_eq!(parse("{\"a\":1,}"), None);
+	}
+}
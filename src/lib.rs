@@ -0,0 +1,15 @@
+pub mod backend;
+pub mod corpus;
+pub mod debugger;
+pub mod dialect;
+pub mod digest;
+pub mod emit;
+pub mod interpreter;
+pub mod ir;
+pub mod native;
+pub mod sandbox;
+pub mod symbol;
+#[cfg(any(feature = "wasm", target_arch = "wasm32"))]
+pub mod wasm;
+
+pub use symbol::InterpreterSymbol;
@@ -1,82 +1,1402 @@
 use std::env;
 use std::fs;
-use interpreter::error::{InterpreterError, InterpreterErrorReason};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Instant;
+use brainfuck::interpreter::error::{InterpreterError, InterpreterErrorReason};
 
-use interpreter::Interpreter;
-use symbol::InterpreterSymbol;
+use brainfuck::backend::{self, Backend, Divergence};
+use brainfuck::debugger::Debugger;
+use brainfuck::digest::RunDigest;
+use brainfuck::emit;
+use brainfuck::interpreter::Interpreter;
+use brainfuck::interpreter::config::InterpreterConfig;
+use brainfuck::interpreter::hot_loop::DEFAULT_HOT_LOOP_ITERATIONS;
+use brainfuck::interpreter::report::{Limit, RunReport};
+use brainfuck::interpreter::coredump::{self, CoreDump, RecentSteps};
+use brainfuck::interpreter::io::CapturedOutput;
+use brainfuck::corpus::{self, TestResult};
+use brainfuck::sandbox::Outcome;
+use brainfuck::dialect::{Dialect, SymbolMap};
+use brainfuck::symbol;
+use brainfuck::ir::{self, CompileError, DEFAULT_OPT_LEVEL, Ir, Span};
+use brainfuck::native::{self, BuildError};
 
-mod interpreter;
-mod symbol;
+mod cli;
+mod diagnostic;
+mod interrupt;
+mod json;
+mod profile;
+mod sources;
+mod timing;
+mod trace;
+#[cfg(feature = "tui")]
+mod tui;
+mod visualize;
 
-fn read_file(filename: &str) -> Vec<char> {
-	let file_contents: String = fs::read_to_string(
-		filename
-	).expect(&format!("Open file: {filename}"));
+use cli::{STDIN_SOURCE, BuildCommandOptions, CliError, Command, CompileOptions, DebugOptions, EmitFormat, MemoryDump, RunOptions, TestOptions};
+use profile::ReportFormat;
+use sources::SourceMap;
+use timing::RunTiming;
+use trace::{TraceOptions, Tracer};
 
-	file_contents.chars().collect()
+/// Exit status of arguments that don't parse.
+const EXIT_USAGE: i32 = 2;
+/// Exit status of a program that failed to run or was stopped by an error.
+const EXIT_FAILED: i32 = 1;
+/// Exit status of a run stopped by `--max-steps` or any other limit but time.
+const EXIT_LIMIT_EXCEEDED: i32 = 3;
+/// Exit status of a run stopped by `--timeout`, the same as `timeout(1)`'s.
+const EXIT_TIMED_OUT: i32 = 124;
+
+
+/// What a program file name names: the file, or stdin read to the end for [`STDIN_SOURCE`].
+fn read_source(filename: &str) -> Option<Vec<u8>> {
+	read_source_from(filename, io::stdin())
+}
+
+/// [`read_source`], with `stdin` standing for stdin.
+fn read_source_from(filename: &str, mut stdin: impl Read) -> Option<Vec<u8>> {
+	if filename != STDIN_SOURCE {
+		return Some(fs::read(filename).unwrap_or_else(|err| {
+			eprintln!("Error! Couldn't open {filename}: {err}");
+			std::process::exit(EXIT_USAGE);
+		}));
+	}
+	let mut source = Vec::new();
+	stdin.read_to_end(&mut source)
+		.map_err(|err| eprintln!("Error! Couldn't read the program from stdin: {err}"))
+		.ok()?;
+	Some(source)
+}
+
+/// A file an option names, such as `--replay` or `--init`, exiting with [`EXIT_USAGE`] if it can't
+/// be read.
+fn read_named_file(path: &str) -> Vec<u8> {
+	fs::read(path).unwrap_or_else(|err| {
+		eprintln!("Error! Couldn't open {path}: {err}");
+		std::process::exit(EXIT_USAGE);
+	})
+}
+
+/// Creates the file an option names, such as `--record`, exiting with [`EXIT_USAGE`] if it can't.
+fn create_named_file(path: &str) -> fs::File {
+	fs::File::create(path).unwrap_or_else(|err| {
+		eprintln!("Error! Couldn't create {path}: {err}");
+		std::process::exit(EXIT_USAGE);
+	})
 }
 
-fn read_instruction(characters: &Vec<char>, bf_interpreter: &Interpreter) -> InterpreterSymbol {
-	let character = characters.get(bf_interpreter.get_instruction_ptr());
+/// `characters` of `filename` as brainfuck, from `dialect` or the one its extension tells.
+fn translate(filename: &str, characters: &[char], dialect: Option<Dialect>) -> Option<Vec<char>> {
+	match file_dialect(filename, dialect).translate(characters) {
+		Ok(translated) => Some(translated),
+		Err(err) => {
+			eprintln!("{filename}:");
+			eprint!("{}", diagnostic::render(&format!("Reason: {err}"), characters, Some(err.offset()), diagnostic::use_color()));
+			None
+		}
+	}
+}
 
-	InterpreterSymbol::from_char(character)
+/// Reads either brainfuck source or a compiled `.bfc` file, told apart by the bytecode magic.
+fn read_program(filename: &str) -> Option<Vec<char>> {
+	read_program_as(filename, None)
 }
 
-fn print_out_error(interpreter_error: &InterpreterError) {
-	let reason: &InterpreterErrorReason = &interpreter_error.reason;
-	println!("Error! Reason: {reason:?}");
+/// [`read_program`], with source in `dialect` rather than the one the extension tells.
+fn read_program_as(filename: &str, dialect: Option<Dialect>) -> Option<Vec<char>> {
+	match read_untranslated(filename)? {
+		(characters, None) => translate(filename, &characters, dialect),
+		(characters, Some(_)) => Some(characters),
+	}
+}
+
+/// The characters of `filename` as written, along with the IR of a `.bfc` file, whose characters
+/// are its brainfuck rendering and which the IR spans.
+fn read_untranslated(filename: &str) -> Option<(Vec<char>, Option<Ir>)> {
+	let file_contents = read_source(filename)?;
+	if !Ir::is_bytecode(&file_contents) {
+		return Some((symbol::decode_source(&file_contents), None));
+	}
+
+	match Ir::from_bytes(&file_contents) {
+		Ok(mut ir) => Some((ir.respan_to_brainfuck().chars().collect(), Some(ir))),
+		Err(err) => {
+			eprintln!("Error! Invalid bytecode file: {err:?}");
+			None
+		}
+	}
+}
+
+/// Prints the error, pointing at `characters[index]` if given.
+fn print_out_error(interpreter_error: &InterpreterError, characters: &[char], sources: &SourceMap, index: Option<usize>) {
+	let message = match &interpreter_error.reason {
+		InterpreterErrorReason::MismatchedBrackets(brackets) => {
+			format!("Reason: unmatched '[' at position {}", brackets.unmatched_loop_start)
+		}
+		reason => format!("Reason: {reason:?}"),
+	};
+	eprint!("{}", diagnostic::render_mapped(&message, characters, index, sources, diagnostic::use_color()));
+}
+
+/// The instruction to blame for `err`, ending the run on `interpreter`.
+fn error_location(interpreter: &Interpreter, backend: Backend, err: &InterpreterError) -> Option<usize> {
+	match err.reason {
+		InterpreterErrorReason::SeedTooLarge(_)
+		| InterpreterErrorReason::HaltedMachine
+		| InterpreterErrorReason::Interrupted => None,
+		InterpreterErrorReason::InfiniteLoopDetected(loop_start)
+		| InterpreterErrorReason::LoopIterationLimit { loop_start, .. } => Some(loop_start),
+		// The machine only notices at the end of the program, the bracket left open is the culprit
+		InterpreterErrorReason::MismatchedBrackets(ref brackets) => Some(brackets.unmatched_loop_start),
+		_ if backend == Backend::Closures => None,
+		_ => Some(interpreter.get_instruction_ptr()),
+	}
 }
 
 fn print_interpreter(interpreter: &Interpreter) {
-	println!("{interpreter:?}");
+	eprintln!("{interpreter:?}");
+}
+
+fn print_heatmap(interpreter: &Interpreter) {
+	let Some(access_counts) = interpreter.access_counts() else {
+		return;
+	};
+
+	let mut hot_cells: Vec<(usize, u64)> = access_counts.iter().copied().enumerate()
+		.filter(|&(_, count)| count > 0)
+		.collect();
+	hot_cells.sort_by(|(left_cell, left_count), (right_cell, right_count)| {
+		right_count.cmp(left_count).then(left_cell.cmp(right_cell))
+	});
+
+	if hot_cells.is_empty() {
+		eprintln!("Cell writes: none");
+		return;
+	}
+	eprintln!("Cell writes:");
+	for (cell, count) in hot_cells {
+		eprintln!("{cell:>5}: {count}");
+	}
 }
 
-fn run_interpreter(characters: Vec<char>) -> Result<Interpreter, InterpreterError> {
-	let mut bf_interpreter = Interpreter::new();
-	loop {
-		if bf_interpreter.is_halted() {
-			break Ok(bf_interpreter);
+/// Sets up the interpreter the options ask for, starting on `tape` instead of the `--init` file if
+/// given. Fails if the initial tape doesn't fit.
+fn new_interpreter(characters: &[char], options: &RunOptions, tape: Option<&[u8]>, input: Option<&[u8]>) -> Result<Interpreter, InterpreterError> {
+	let mut bf_interpreter = match &options.replay {
+		// Input that came with the program, and is all there is
+		_ if input.is_some() => {
+			let mut interpreter = Interpreter::with_output(io::stdout());
+			interpreter.feed_input(input.unwrap_or_default());
+			interpreter
 		}
+		Some(replay) => {
+			let mut interpreter = Interpreter::with_output(io::stdout());
+			interpreter.feed_input(&read_named_file(replay));
+			interpreter
+		}
+		// No reader at all, the input is whatever was replayed
+		None if options.deterministic => Interpreter::with_output(io::stdout()),
+		None => Interpreter::new(),
+	};
+	if let Some(record) = &options.record {
+		bf_interpreter = bf_interpreter.with_input_recorder(create_named_file(record));
+	}
+	if options.heatmap {
+		bf_interpreter = bf_interpreter.with_access_counts();
+	}
+	if options.coverage || options.coverage_annotate || options.annotate.is_some() {
+		bf_interpreter = bf_interpreter.with_coverage();
+	}
+	if options.stats || options.time || options.json || options.deterministic || options.verbose {
+		bf_interpreter = bf_interpreter.with_stats();
+	}
+	if options.json {
+		bf_interpreter = bf_interpreter.with_output_sink(io::stderr());
+	}
+	if options.time {
+		bf_interpreter = bf_interpreter.with_input_timing();
+	}
+	if options.memory_profile.is_some() {
+		bf_interpreter = bf_interpreter.with_memory_profile();
+	}
+	if options.loop_profile.is_some() {
+		bf_interpreter = bf_interpreter.with_loop_profile();
+	}
+	if options.loop_warning && !options.quiet {
+		let spans = Span::table(characters);
+		bf_interpreter = bf_interpreter.with_hot_loop_warning(DEFAULT_HOT_LOOP_ITERATIONS, move |hot_loop| {
+			let span = spans[hot_loop.loop_start];
+			eprintln!(
+				"\nWarning! The loop at line {}, col {} ran {} times without any I/O, it may never end",
+				span.line, span.column, hot_loop.iterations
+			);
+		});
+	}
+	bf_interpreter = bf_interpreter.configured(InterpreterConfig {
+		eof_policy: options.eof_policy,
+		tape_cells: options.tape_size,
+		start_ptr: options.start_ptr,
+		max_loop_depth: options.max_loop_depth,
+		max_loop_iterations: options.max_loop_iterations,
+		max_output: options.max_output,
+		timeout: options.timeout,
+		timeout_includes_input: options.timeout_includes_input,
+		echo_input: options.echo_input,
+		debug_char: options.debug_char,
+		detect_infinite_loops: options.detect_infinite,
+	})?;
+	bf_interpreter = bf_interpreter.with_interrupt_flag(interrupt::install());
+	if let Some(tape) = tape {
+		bf_interpreter = bf_interpreter.with_initial_tape(tape)?;
+	} else if let Some(init) = &options.init {
+		bf_interpreter = bf_interpreter.with_initial_tape(&read_named_file(init))?;
+	}
+	Ok(bf_interpreter)
+}
 
-		let symbol = read_instruction(&characters, &bf_interpreter);
-		if let Err(interpreter_error) = bf_interpreter.interpret_symbol(&symbol) {
-			break Err(interpreter_error);
+impl RunOptions {
+	/// Whether the run has to step through the characters whatever the backend. Coverage, which
+	/// annotating is built on, stats, memory profiles included, stalled loops and the debug
+	/// character are only seen that way, and the closures don't profile loops either.
+	fn steps_characters(&self) -> bool {
+		self.coverage
+			|| self.coverage_annotate
+			|| self.deterministic
+			|| self.detect_infinite
+			|| self.debug_char.is_some()
+			|| self.annotate.is_some()
+			|| self.stats
+			|| self.memory_profile.is_some()
+			|| (self.loop_profile.is_some() && self.backend == Backend::Closures)
+	}
+}
+
+/// Runs the program, handing back the machine even when the run failed so it can be inspected.
+fn run_interpreter(
+	characters: &[char],
+	ir: Option<&Ir>,
+	options: &RunOptions,
+	mut bf_interpreter: Interpreter,
+	write_failed: &mut bool,
+) -> (Interpreter, Result<(), InterpreterError>) {
+	let result = if let Some(delay) = options.visualize {
+		let output = CapturedOutput::new();
+		bf_interpreter = bf_interpreter.with_output_sink(output.clone());
+		bf_interpreter.run_observed(characters, |interpreter| {
+			visualize::show_frame(interpreter, &output.contents(), delay);
+		})
+	} else if let Some(trace) = &options.trace {
+		run_traced(characters, &mut bf_interpreter, trace, write_failed)
+	} else if let Some(path) = &options.coredump {
+		run_dumping_core(characters, &mut bf_interpreter, path)
+	} else if options.steps_characters() {
+		match options.max_steps {
+			Some(max_steps) => run_budgeted(characters, None, Backend::Naive, &mut bf_interpreter, max_steps),
+			None => bf_interpreter.run(characters),
 		}
+	} else if let Some(max_steps) = options.max_steps {
+		run_budgeted(characters, ir, options.backend, &mut bf_interpreter, max_steps)
+	} else if let Some(ir) = ir {
+		options.backend.run_compiled(&mut bf_interpreter, ir)
+	} else {
+		options.backend.run(&mut bf_interpreter, characters)
+	};
+	(bf_interpreter, result)
+}
+
+/// Runs the program for at most `max_steps` steps, leaving the machine unhalted if they ran out.
+/// The closures can't stop halfway, so they run the IR instead, `ir` if it is compiled already.
+fn run_budgeted(characters: &[char], ir: Option<&Ir>, backend: Backend, interpreter: &mut Interpreter, max_steps: u64) -> Result<(), InterpreterError> {
+	if backend == Backend::Naive {
+		interpreter.load(characters);
+		interpreter.run_for(max_steps)?;
+	} else {
+		let compiled;
+		let ir = match ir {
+			Some(ir) => ir,
+			None => {
+				compiled = Ir::compile(characters, DEFAULT_OPT_LEVEL).expect("Prepared programs have balanced brackets");
+				&compiled
+			}
+		};
+		interpreter.run_with_fuel(ir, max_steps)?;
 	}
+	Ok(())
 }
 
-fn print_usage(program_name: &str) -> () {
-	println!("Usage: {program_name} brainfuck.bf");
+/// Steps through the program writing every executed instruction to the trace file. The run goes on
+/// if the file can't be written, setting `write_failed`, with the trace cut short.
+fn run_traced(characters: &[char], interpreter: &mut Interpreter, options: &TraceOptions, write_failed: &mut bool) -> Result<(), InterpreterError> {
+	let mut tracer = Tracer::create(options, characters).unwrap_or_else(|err| {
+		eprintln!("Error! Couldn't create {}: {err}", options.path);
+		std::process::exit(EXIT_USAGE);
+	});
+	let mut trace_error = None;
+	let result = interpreter.events(characters).try_for_each(|event| {
+		let event = event?;
+		if trace_error.is_none() {
+			trace_error = tracer.record(characters[event.instruction_ptr], &event).err();
+		}
+		Ok(())
+	});
+	if let Some(err) = trace_error.or_else(|| tracer.finish().err()) {
+		eprintln!("Error! Couldn't write the trace to {}: {err}", options.path);
+		*write_failed = true;
+	}
+	result
 }
 
-fn extract_filename() -> Option<String> {
-	let args: Vec<String> = env::args().collect();
-	match &args[..] {
-		[_, filename] => Some(filename.clone()),
-		[] => {
-			print_usage("brainfuck.exe");
-			None
+/// Steps through the program keeping the last instructions, to save them in a core dump if it fails.
+fn run_dumping_core(characters: &[char], interpreter: &mut Interpreter, path: &str) -> Result<(), InterpreterError> {
+	let mut recent = RecentSteps::new(coredump::DEFAULT_RECENT_STEPS);
+	let result = interpreter.events(characters).try_for_each(|event| event.map(|event| recent.push(event)));
+	if let Err(err) = &result {
+		match CoreDump::capture(interpreter, characters, err, &recent).save(path) {
+			Ok(()) => eprintln!("Core dumped to {path}"),
+			Err(io_err) => eprintln!("Error! Couldn't write core dump to {path}: {io_err}"),
+		}
+	}
+	result
+}
+
+/// Instructions executed by a run with stats, if they were counted: only stepping through the
+/// characters does.
+fn counted_steps(interpreter: &Interpreter, backend: Backend) -> Option<u64> {
+	interpreter.stats()
+		.filter(|stats| stats.steps > 0 || backend == Backend::Naive)
+		.map(|stats| stats.steps)
+}
+
+/// How a run ended and the machine it left, for `--json`.
+fn machine_json(interpreter: &Interpreter, result: &Result<(), InterpreterError>, backend: Backend) -> json::Object {
+	let object = match result {
+		Ok(()) => json::Object::new().string("result", "ok"),
+		Err(err) => json::Object::new().string("result", "error").string("reason", &format!("{:?}", err.reason)),
+	};
+	object
+		.raw("data_ptr", interpreter.get_data_ptr())
+		.raw("instruction_ptr", interpreter.get_instruction_ptr())
+		.optional("steps", counted_steps(interpreter, backend))
+		.raw("max_stack_depth", interpreter.max_stack_depth())
+		.raw("tape", json::array(interpreter.tape()))
+}
+
+/// The cells `dump` asks for, as `address: value (character)` lines for the nonzero ones and as a
+/// hex dump otherwise.
+fn memory_dump(interpreter: &Interpreter, dump: &MemoryDump) -> String {
+	match dump {
+		MemoryDump::Written => interpreter.hex_dump(),
+		MemoryDump::Range(range) => interpreter.hex_dump_range(range.clone()),
+		MemoryDump::NonZero => interpreter.nonzero_cells()
+			.map(|(address, value)| {
+				let character = if value.is_ascii_graphic() || value == b' ' { value as char } else { '.' };
+				format!("{address}: {value} ({character})\n")
+			})
+			.collect(),
+	}
+}
+
+/// `dump` for `--json`: the cells of the range as an array, or the nonzero ones as objects.
+fn memory_dump_json(interpreter: &Interpreter, dump: &MemoryDump) -> String {
+	let range = match dump {
+		MemoryDump::NonZero => {
+			let cells = interpreter.nonzero_cells()
+				.map(|(address, value)| json::Object::new().raw("address", address).raw("value", value));
+			return json::array(cells);
+		}
+		MemoryDump::Written => 0..interpreter.highest_written().map_or(0, |last| last + 1),
+		MemoryDump::Range(range) => range.clone(),
+	};
+	let cells = range.clone().filter_map(|address| interpreter.cell(address));
+	json::Object::new().raw("start", range.start).raw("cells", json::array(cells)).to_string()
+}
+
+/// Prints the stats and the loop and memory profiles that were asked for to stderr.
+fn print_reports(characters: &[char], options: &RunOptions, interpreter: &Interpreter) {
+	if options.report_format == ReportFormat::Json {
+		if options.stats || options.loop_profile.is_some() || options.memory_profile.is_some() {
+			let document = profile::json_document(
+				characters,
+				interpreter,
+				options.stats,
+				options.loop_profile,
+				options.memory_profile,
+			);
+			eprintln!("{document}");
+		}
+		return;
+	}
+
+	if let Some(stats) = interpreter.stats().filter(|_| options.stats) {
+		eprintln!("{stats}");
+	}
+	if let (Some(stats), Some(top)) = (interpreter.stats(), options.memory_profile) {
+		eprint!("{}", profile::memory_report(&stats, interpreter, top));
+	}
+	if let (Some(loop_profile), Some(top)) = (interpreter.loop_profile(), options.loop_profile) {
+		eprint!("{}", profile::loop_report(characters, loop_profile, top));
+	}
+}
+
+/// Where an interrupted run was: the instruction and data pointers, the loop depth and the cells
+/// around the data pointer.
+fn interrupted_report(characters: &[char], sources: &SourceMap, interpreter: &Interpreter, backend: Backend) -> String {
+	let ip = interpreter.get_instruction_ptr();
+	let data_ptr = interpreter.get_data_ptr();
+	let mut report = String::from("Interrupted!\n");
+	let (file, characters, index) = match sources.locate(characters, ip) {
+		Some((name, file, index)) => (format!("{name}, "), file, index),
+		None => (String::new(), characters, ip),
+	};
+	report.push_str(&match (backend, Span::table(characters).get(index)) {
+		(Backend::Closures, _) => "Instruction pointer and loop depth: not tracked by the closures backend\n".to_string(),
+		(_, Some(span)) => format!("Instruction pointer: {ip} ({file}line {}, col {})\n", span.line, span.column),
+		(_, None) => format!("Instruction pointer: {ip}, the end of the program\n"),
+	});
+	// Only the naive backend keeps a loop stack
+	if backend == Backend::Naive {
+		report.push_str(&format!("Loop depth: {}\n", interpreter.loop_stack().len()));
+	}
+	report.push_str(&format!("Data pointer: {data_ptr}\n"));
+	let start = data_ptr.saturating_sub(32) / 16 * 16;
+	report.push_str(&interpreter.hex_dump_range(start..start + 64));
+	report
+}
+
+fn print_divergence(divergence: &Divergence) {
+	eprintln!("Backends diverged: {:?}", divergence.kind);
+	if let Some(expected) = &divergence.expected {
+		eprintln!("Reference: {}", expected.state);
+	}
+	if let Some(actual) = &divergence.actual {
+		eprintln!("Backend:   {}", actual.state);
+	}
+}
+
+/// Runs the program under both backends with the whole of stdin as input, comparing the results.
+/// Tells whether the backends agreed.
+fn verify_interpreter(characters: &[char], backend: Backend, reference: Backend) -> bool {
+	let mut input = Vec::new();
+	if let Err(err) = io::stdin().read_to_end(&mut input) {
+		eprintln!("Error! Couldn't read the input from stdin: {err}");
+		return false;
+	}
+
+	match backend::verify(backend, reference, characters, &input) {
+		Ok(snapshot) => {
+			if let Err(err) = io::stdout().write_all(&snapshot.output) {
+				eprintln!("Error! Couldn't write to stdout: {err}");
+				return false;
+			}
+			eprintln!("\nVerified against {reference:?}: output, tape and result match");
+			true
+		}
+		Err(divergence) => {
+			print_divergence(&divergence);
+			false
+		}
+	}
+}
+
+/// The name of the kind of `err`, and where it is.
+fn compile_error_parts(err: &CompileError) -> (&'static str, Span) {
+	match *err {
+		CompileError::UnmatchedLoopStart(span) => ("UnmatchedLoopStart", span),
+		CompileError::UnmatchedLoopEnd(span) => ("UnmatchedLoopEnd", span),
+	}
+}
+
+/// Reports `err` in `characters` to stderr, or as the result on stdout with `--json`.
+fn print_compile_error(err: &CompileError, characters: &[char], sources: &SourceMap, json: bool) {
+	let (reason, span) = compile_error_parts(err);
+	if json {
+		println!("{}", json::Object::new().string("result", "error").string("reason", reason).raw("offset", span.offset));
+		return;
+	}
+	let message = format!("Reason: {reason}");
+	eprint!("{}", diagnostic::render_mapped(&message, characters, Some(span.offset), sources, diagnostic::use_color()));
+}
+
+/// Looks for unmatched brackets anywhere in `characters`, not only where the run goes, telling
+/// whether the program can run. With `--strict` they are errors, otherwise the naive backend runs
+/// the program anyway, only failing if it reaches one, and they are warned about. They are shown in
+/// `source`, what `characters` was translated from.
+fn check_brackets(characters: &[char], source: &[char], sources: &SourceMap, options: &RunOptions) -> bool {
+	let errors = ir::bracket_errors(characters);
+	if options.strict {
+		// A single result for --json
+		let reported = if options.json { &errors[..errors.len().min(1)] } else { &errors[..] };
+		for err in reported {
+			print_compile_error(err, source, sources, options.json);
+		}
+		return errors.is_empty();
+	}
+	// The compiled backends refuse them anyway
+	if options.backend == Backend::Naive && !options.quiet {
+		for err in &errors {
+			let (bracket, span) = match *err {
+				CompileError::UnmatchedLoopStart(span) => ("[ is never closed", span),
+				CompileError::UnmatchedLoopEnd(span) => ("] closes no loop", span),
+			};
+			let location = match sources.locate(source, span.offset) {
+				Some((name, file, offset)) => {
+					let span = Span::table(file)[offset];
+					format!("{name}:{}:{}", span.line, span.column)
+				}
+				None => format!("line {}, col {}", span.line, span.column),
+			};
+			eprintln!("Warning! The {bracket} at {location}, the run fails if it gets there");
 		}
-		[program_name, ..] => {
-			print_usage(&program_name);
+	}
+	true
+}
+
+/// Compiles the `filenames` joined in order.
+fn compile_file(filenames: &[String], opt_level: u8) -> Option<Ir> {
+	let files: Vec<(String, Vec<char>)> = filenames.iter()
+		.map(|filename| Some((filename.clone(), symbol::decode_source(&read_source(filename)?))))
+		.collect::<Option<_>>()?;
+	let translated = files.iter()
+		.map(|(filename, characters)| translate(filename, characters, None))
+		.collect::<Option<Vec<_>>>()?
+		.concat();
+	// Errors show the files as written
+	let (source, sources) = SourceMap::concatenate(files);
+	match Ir::compile(&translated, opt_level) {
+		Ok(ir) => Some(ir),
+		Err(err) => {
+			let (reason, span) = compile_error_parts(&err);
+			let message = format!("Reason: {reason}");
+			eprint!("{}", diagnostic::render_mapped(&message, &source, Some(span.offset), &sources, diagnostic::use_color()));
 			None
 		}
 	}
 }
 
-fn main() {
-	if let Some(filename) = extract_filename() {
-		let characters = read_file(&filename);
-		println!("Running...");
-		let result = run_interpreter(characters);
-		match result {
-			Ok(final_interpreter) => {
-				println!("\nFinished successfully!");
-				print_interpreter(&final_interpreter);
+/// Validates the brackets of every program of `options` without running them, read as a run would
+/// read them, telling whether they all match.
+fn check(options: &RunOptions, symbol_map: Option<&SymbolMap>) -> bool {
+	let mut valid = true;
+	// Joined, the files are a single program
+	let programs = if options.concat { &options.filenames[..1] } else { &options.filenames[..] };
+	for filename in programs {
+		let Some(LoadedSource { characters, source, sources, .. }) = load_program(filename, options, symbol_map) else {
+			valid = false;
+			continue;
+		};
+		let errors = ir::bracket_errors(&characters);
+		if errors.is_empty() {
+			println!("{filename}: OK");
+		}
+		for err in &errors {
+			let (reason, span) = compile_error_parts(err);
+			eprintln!("{filename}:");
+			let message = format!("Reason: {reason}");
+			eprint!("{}", diagnostic::render_mapped(&message, &source, Some(span.offset), &sources, diagnostic::use_color()));
+		}
+		valid &= errors.is_empty();
+	}
+	valid
+}
+
+/// Runs the tests in the directory of `options`, printing how each went and a diff of the output
+/// of those that printed the wrong thing. Tells whether they all passed.
+fn test(options: &TestOptions) -> bool {
+	let cases = match corpus::discover(Path::new(&options.dir), options.filter.as_deref()) {
+		Ok(cases) => cases,
+		Err(err) => {
+			eprintln!("Error! Couldn't read the tests in {}: {err}", options.dir);
+			return false;
+		}
+	};
+	let mut failed = 0;
+	for case in &cases {
+		let result = corpus::run_case(case, options.limits, options.eof_policy);
+		match &result {
+			TestResult::Passed => println!("PASS {}", case.name),
+			TestResult::Mismatch { output } => {
+				println!("FAIL {}: wrong output", case.name);
+				print!("{}", corpus::diff(&case.expected, output));
+			}
+			TestResult::Failed(Outcome::RuntimeError { error, .. }) => println!("FAIL {}: {:?}", case.name, error.reason),
+			TestResult::Failed(Outcome::LimitExceeded { which, .. }) => println!("FAIL {}: {which:?} limit exceeded", case.name),
+			TestResult::Failed(Outcome::ParseError(errors)) => {
+				let reasons: Vec<&str> = errors.iter().map(|err| compile_error_parts(err).0).collect();
+				println!("FAIL {}: {}", case.name, reasons.join(", "));
 			}
+			TestResult::Failed(outcome) => println!("FAIL {}: {outcome:?}", case.name),
+		}
+		failed += usize::from(!result.passed());
+	}
+	println!("\n{} passed, {failed} failed", cases.len() - failed);
+	failed == 0
+}
+
+/// Compiles the program as `options` say, telling whether it compiled and was written.
+fn compile(options: &CompileOptions) -> bool {
+	let Some(ir) = compile_file(&options.filenames, options.opt_level) else {
+		return false;
+	};
+
+	let contents = match options.emit {
+		EmitFormat::Bytecode => ir.to_bytes(),
+		EmitFormat::C => emit::c::emit(&ir).into_bytes(),
+		EmitFormat::Ir => ir.to_string().into_bytes(),
+		EmitFormat::Llvm => emit::llvm::emit(&ir, &options.llvm).into_bytes(),
+		EmitFormat::Wasm => emit::wasm::emit(&ir),
+	};
+	if let Some(output) = &options.output {
+		if let Err(err) = fs::write(output, contents) {
+			eprintln!("Error! Couldn't write {output}: {err}");
+			return false;
+		}
+	} else if let Err(err) = io::stdout().write_all(&contents) {
+		eprintln!("Error! Couldn't write to stdout: {err}");
+		return false;
+	}
+	true
+}
+
+/// Builds the executable, telling whether it was.
+fn build(options: &BuildCommandOptions) -> bool {
+	let Some(ir) = compile_file(std::slice::from_ref(&options.filename), DEFAULT_OPT_LEVEL) else {
+		return false;
+	};
+
+	match native::build_executable(&ir, Path::new(&options.output), &options.build) {
+		Ok(temp_dir) => {
+			if let Some(temp_dir) = temp_dir {
+				println!("Kept temporary files in {}", temp_dir.display());
+			}
+			println!("Built {}", options.output);
+			return true;
+		}
+		Err(BuildError::TempFiles(err)) => eprintln!("Error! Couldn't write temporary files: {err}"),
+		Err(BuildError::CompilerNotFound { compiler, error }) => {
+			eprintln!("Error! Couldn't run C compiler '{compiler}' ({error}), install one or set $CC");
+		}
+		Err(BuildError::CompilerFailed { compiler, stderr }) => eprintln!("Error! '{compiler}' failed:\n{stderr}"),
+		Err(BuildError::MissingExecutable(path)) => {
+			eprintln!("Error! Compiler produced no executable at {}", path.display());
+		}
+	}
+	false
+}
+
+/// Runs the debugger, telling whether it could: the program loaded and the session didn't fail.
+fn debug(options: &DebugOptions) -> bool {
+	let Some(characters) = read_program(&options.filename) else {
+		return false;
+	};
+	let input = match &options.input {
+		Some(input) => read_named_file(input),
+		None => Vec::new(),
+	};
+
+	let mut debugger = match &options.core {
+		Some(core) => match CoreDump::load(core) {
+			Ok(core) => Debugger::from_core(characters, core),
 			Err(err) => {
-				print_out_error(&err);
+				eprintln!("Error! Invalid core dump: {err:?}");
+				return false;
+			}
+		},
+		None => Debugger::new(characters, move || {
+			let mut interpreter = Interpreter::with_output(io::stdout());
+			interpreter.feed_input(&input);
+			interpreter
+		}),
+	}
+	.with_journal_window(options.journal_window);
+	if let Err(err) = debugger.repl(io::stdin().lock(), io::stdout()) {
+		eprintln!("Error! Debugger I/O failed: {err}");
+		return false;
+	}
+	true
+}
+
+#[cfg(feature = "tui")]
+fn run_tui(options: &DebugOptions) -> bool {
+	let Some(characters) = read_program(&options.filename) else {
+		return false;
+	};
+	let input = match &options.input {
+		Some(input) => read_named_file(input),
+		None => Vec::new(),
+	};
+
+	if let Err(err) = tui::Tui::new(characters, &input).run() {
+		eprintln!("Error! Terminal I/O failed: {err}");
+		return false;
+	}
+	true
+}
+
+
+/// A program read and translated to brainfuck, along with what it was read from.
+struct LoadedSource {
+	/// The brainfuck to run, offset for offset the same as `source`.
+	characters: Vec<char>,
+	/// The program as written, before comments were blanked and a dialect translated, which is
+	/// what diagnostics show.
+	source: Vec<char>,
+	sources: SourceMap,
+	/// The input `--bang-input` split off the end.
+	bang_input: Option<Vec<u8>>,
+	/// The IR of a `.bfc` file, run as it is by the compiled backends rather than parsed again.
+	ir: Option<Ir>,
+}
+
+/// The dialect `filename` is written in, `dialect` if given.
+fn file_dialect(filename: &str, dialect: Option<Dialect>) -> Dialect {
+	dialect.unwrap_or_else(|| Dialect::of_file(Path::new(filename)))
+}
+
+/// The program `filename` stands for: the `-e` source, every file joined with `--concat`, or the
+/// file itself, renamed back to brainfuck by `symbol_map` if given.
+fn load_program(filename: &str, options: &RunOptions, symbol_map: Option<&SymbolMap>) -> Option<LoadedSource> {
+	// A map replaces the dialect, the source is read as it is
+	let dialect = if symbol_map.is_some() { Some(Dialect::Brainfuck) } else { options.dialect };
+	// Named after no file, `-e` source is brainfuck unless told otherwise
+	let dialect = if options.eval.is_some() { Some(dialect.unwrap_or_default()) } else { dialect };
+	let mut files: Vec<(String, Vec<char>, Option<Ir>)> = if let Some(program) = &options.eval {
+		vec![(filename.to_string(), symbol::decode_source(program.as_bytes()), None)]
+	} else if !options.concat {
+		let (characters, ir) = read_untranslated(filename)?;
+		vec![(filename.to_string(), characters, ir)]
+	} else {
+		options.filenames.iter()
+			.map(|filename| {
+				let (characters, ir) = read_untranslated(filename)?;
+				Some((filename.clone(), characters, ir))
+			})
+			.collect::<Option<_>>()?
+	};
+	// Only a whole program runs as it was compiled, one with its characters renamed or commented
+	// out is another
+	let ir = match &mut files[..] {
+		[(_, _, ir)] if symbol_map.is_none() && options.line_comments.is_none() => ir.take(),
+		_ => None,
+	};
+	let translations: Vec<(String, usize, bool)> = files.iter()
+		.map(|(filename, characters, file_ir)| (filename.clone(), characters.len(), file_ir.is_none() && ir.is_none()))
+		.collect();
+	let (mut characters, mut sources) = SourceMap::concatenate(
+		files.into_iter().map(|(filename, characters, _)| (filename, characters)).collect()
+	);
+	if options.eval.is_some() {
+		sources = SourceMap::named(filename.to_string(), characters.len());
+	}
+
+	// Written in the source, so split before a dialect renames it, at a `!` none of its tokens hold
+	let mut bang_input = None;
+	if options.bang_input {
+		let bang = match symbol_map {
+			Some(symbol_map) => symbol_map.bang_offset(&characters),
+			None => {
+				let mut start = 0;
+				translations.iter().find_map(|(filename, len, is_source)| {
+					let file = &characters[start..start + len];
+					let dialect = if *is_source { file_dialect(filename, dialect) } else { Dialect::Brainfuck };
+					let bang = dialect.bang_offset(file).map(|offset| start + offset);
+					start += len;
+					bang
+				})
+			}
+		};
+		let (program, input) = symbol::split_input_at(&characters, bang);
+		(characters, bang_input) = (program.to_vec(), input);
+		sources.truncate(characters.len());
+	}
+	let source = characters.clone();
+	// Comments too, so they can hold tokens
+	if let Some(marker) = &options.line_comments {
+		symbol::strip_line_comments(&mut characters, marker);
+	}
+
+	let mut start = 0;
+	for (filename, len, is_source) in translations {
+		let range = start.min(characters.len())..(start + len).min(characters.len());
+		start += len;
+		// Bytecode is brainfuck already
+		if !is_source {
+			continue;
+		}
+		let translated = translate(&filename, &characters[range.clone()], dialect)?;
+		characters[range].copy_from_slice(&translated);
+	}
+	if let Some(symbol_map) = symbol_map {
+		characters = symbol_map.translate(&characters);
+	}
+	Some(LoadedSource { characters, source, sources, bang_input, ir })
+}
+
+/// The `--symbol-map` in `path`, printing why if it doesn't load.
+fn load_symbol_map(path: &str) -> Option<SymbolMap> {
+	let text = fs::read_to_string(path)
+		.map_err(|err| eprintln!("Error! Couldn't open {path}: {err}"))
+		.ok()?;
+	SymbolMap::parse(&text)
+		.map_err(|err| eprintln!("Error! Invalid symbol map {path}, {err}"))
+		.ok()
+}
+
+/// Runs the program in `filename` from a fresh machine, with `tape` on it if given. Returns the
+/// exit status, 0 if it finished successfully, and with `--shared-tape` hands back the tape it left
+/// for the next one.
+/// Whether the program reads input that isn't there, stdin having been used up reading the
+/// program itself, so `,` would only ever find it at its end.
+fn input_lost_to_stdin(filename: &str, options: &RunOptions, has_bang_input: bool, characters: &[char]) -> bool {
+	filename == STDIN_SOURCE && options.replay.is_none() && !has_bang_input && characters.contains(&',')
+}
+
+fn run_program(
+	filename: &str,
+	options: &RunOptions,
+	tape: Option<&[u8]>,
+	symbol_map: Option<&SymbolMap>,
+) -> (i32, Option<Vec<u8>>) {
+	let load_start = Instant::now();
+	let Some(LoadedSource { characters, source, sources, bang_input, ir }) = load_program(filename, options, symbol_map) else {
+		return (EXIT_FAILED, None);
+	};
+	if input_lost_to_stdin(filename, options, bang_input.is_some(), &characters) {
+		eprintln!("Error! The program reads input but was itself read from stdin, give its input with --input file");
+		return (EXIT_FAILED, None);
+	}
+	if let Some(reference) = options.verify_against {
+		let verified = verify_interpreter(&characters, options.backend, reference);
+		return (if verified { 0 } else { EXIT_FAILED }, None);
+	}
+	if !check_brackets(&characters, &source, &sources, options) {
+		return (EXIT_FAILED, None);
+	}
+	// Only the tools stepping through characters walk the IR rendering, everything else runs or
+	// compiles the same IR from the source, so errors and profiles land where they are written
+	let runs_rendering = options.backend != Backend::Naive
+		&& (options.visualize.is_some() || options.trace.is_some() || options.coredump.is_some())
+		&& options.loop_profile.is_none()
+		&& !options.steps_characters();
+	// A `.bfc` file is compiled already, for the compiled backends to run as it is
+	let runs_compiled = options.backend != Backend::Naive
+		&& options.visualize.is_none()
+		&& options.trace.is_none()
+		&& options.coredump.is_none()
+		&& !options.steps_characters();
+	let ir = ir.filter(|_| runs_compiled);
+	let characters = match options.backend.prepare(&characters) {
+		_ if ir.is_some() => characters,
+		Ok(prepared) if runs_rendering => prepared,
+		Ok(_) => characters,
+		Err(err) => {
+			print_compile_error(&err, &source, &sources, options.json);
+			return (EXIT_FAILED, None);
+		}
+	};
+	// The rendering has offsets of its own
+	let shown = if runs_rendering { &characters } else { &source };
+	// The compiled backends don't check the depth while running, so it's worth knowing beforehand
+	let depth = match &ir {
+		Some(ir) => Some(ir.max_depth()),
+		None => Ir::compile(&characters, 0).ok().map(|ir| ir.max_depth()),
+	};
+	if let Some(depth) = depth.filter(|&depth| depth > options.max_loop_depth && !options.quiet) {
+		eprintln!("Warning! Loops nest {depth} deep, more than the limit of {}", options.max_loop_depth);
+	}
+	// Held back and hashed, then printed once the run is over
+	let captured = options.deterministic.then(CapturedOutput::new);
+	let bf_interpreter = match new_interpreter(&characters, options, tape, bang_input.as_deref()) {
+		Ok(bf_interpreter) => match &captured {
+			Some(captured) => bf_interpreter.with_output_sink(captured.clone()),
+			None => bf_interpreter,
+		},
+		Err(err) if options.json => {
+			println!("{}", json::Object::new().string("result", "error").string("reason", &format!("{:?}", err.reason)));
+			return (EXIT_FAILED, None);
+		}
+		Err(err) => {
+			print_out_error(&err, &source, &sources, None);
+			return (EXIT_FAILED, None);
+		}
+	};
+	let load = load_start.elapsed();
+	if options.verbose && !options.json {
+		if options.filenames.len() > 1 && !options.concat {
+			eprintln!("Running {filename}...");
+		} else {
+			eprintln!("Running...");
+		}
+	}
+	// Files written during or after the run, failing a run that otherwise succeeded
+	let mut write_failed = false;
+	let run_start = Instant::now();
+	let (final_interpreter, result) = run_interpreter(&characters, ir.as_ref(), options, bf_interpreter, &mut write_failed);
+	let run = run_start.elapsed();
+	if let Some(captured) = &captured {
+		let output = captured.contents();
+		let mut stdout = io::stdout();
+		let _ = stdout.write_all(&output).and_then(|()| stdout.flush());
+		let steps = final_interpreter.stats().map_or(0, |stats| stats.steps);
+		eprintln!("\nDigest: {}", RunDigest::new(&output, &final_interpreter.snapshot().tape, steps));
+	}
+	let interrupted = matches!(&result, Err(err) if matches!(err.reason, InterpreterErrorReason::Interrupted));
+	let out_of_steps = result.is_ok() && !final_interpreter.is_halted();
+	let timed_out = matches!(&result, Err(err) if matches!(err.reason, InterpreterErrorReason::TimedOut));
+	let succeeded = result.is_ok() && !out_of_steps;
+	let stopped_by_limit = matches!(&result, Err(err) if Limit::of(&err.reason).is_some());
+	// Before the result is reported, which moves the error
+	let report = options.verbose.then(|| RunReport::new(&final_interpreter, result.as_ref().err(), run));
+	match result {
+		_ if options.json => {
+			let object = machine_json(&final_interpreter, &result, options.backend);
+			match &options.dump_memory {
+				Some(dump) => println!("{}", object.raw("memory", memory_dump_json(&final_interpreter, dump))),
+				None => println!("{object}"),
+			}
+		}
+		Ok(()) if out_of_steps => {
+			let message = format!("Reason: step limit of {} reached", options.max_steps.unwrap_or_default());
+			let location = Some(final_interpreter.get_instruction_ptr());
+			eprint!("{}", diagnostic::render_mapped(&message, shown, location, &sources, diagnostic::use_color()));
+		}
+		Ok(()) => {
+			if options.verbose {
+				eprintln!("\nFinished successfully!");
+				print_interpreter(&final_interpreter);
+			}
+			print_heatmap(&final_interpreter);
+			if options.dump_on_exit {
+				eprint!("{}", final_interpreter.hex_dump());
+			}
+		}
+		Err(_) if interrupted => eprint!("\n{}", interrupted_report(shown, &sources, &final_interpreter, options.backend)),
+		Err(err) => {
+			let location = error_location(&final_interpreter, options.backend, &err);
+			print_out_error(&err, shown, &sources, location);
+		}
+	}
+	if options.time {
+		let timing = RunTiming {
+			load,
+			run,
+			input_wait: final_interpreter.input_wait().unwrap_or_default(),
+			steps: counted_steps(&final_interpreter, options.backend),
+		};
+		eprintln!("{timing}");
+	}
+	if let Some(report) = report {
+		eprintln!("{report}");
+	}
+	print_reports(&characters, options, &final_interpreter);
+	if let Some(counts) = final_interpreter.execution_counts() {
+		// Worth knowing after a failed run too, which is where execution stopped
+		if options.coverage {
+			eprint!("{}", profile::coverage_report(&characters, counts));
+		}
+		if options.coverage_annotate {
+			eprintln!("{}", profile::coverage_annotate(&characters, counts));
+		}
+	}
+	if let (Some(counts), Some(output)) = (final_interpreter.execution_counts(), &options.annotate) {
+		let annotated = profile::annotate(&characters, counts);
+		match output {
+			Some(path) => {
+				if let Err(err) = fs::write(path, annotated) {
+					eprintln!("Error! Couldn't write {path}: {err}");
+					write_failed = true;
+				}
+			}
+			None => eprint!("{annotated}"),
+		}
+	}
+	if let (Some(dump), false) = (&options.dump_memory, options.json) {
+		eprint!("{}", memory_dump(&final_interpreter, dump));
+	}
+	if let Some(path) = &options.dump_memory_to {
+		if let Err(err) = fs::write(path, final_interpreter.tape()) {
+			eprintln!("Error! Couldn't write the tape to {path}: {err}");
+			write_failed = true;
+		}
+	}
+	if interrupted {
+		std::process::exit(interrupt::EXIT_INTERRUPTED);
+	}
+	let status = match () {
+		_ if succeeded && !write_failed => 0,
+		_ if succeeded => EXIT_FAILED,
+		_ if timed_out => EXIT_TIMED_OUT,
+		_ if out_of_steps || stopped_by_limit => EXIT_LIMIT_EXCEEDED,
+		_ => EXIT_FAILED,
+	};
+	(status, options.shared_tape.then(|| final_interpreter.tape().to_vec()))
+}
+
+fn main() -> ExitCode {
+	let args: Vec<String> = env::args().collect();
+	let program_name = args.first().map_or(env!("CARGO_PKG_NAME"), String::as_str);
+	let command = match cli::parse(args.get(1..).unwrap_or_default()) {
+		Ok(command) => command,
+		Err(CliError::Help(subcommand)) => {
+			cli::print_help(program_name, subcommand);
+			return ExitCode::SUCCESS;
+		}
+		Err(CliError::Version) => {
+			println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+			return ExitCode::SUCCESS;
+		}
+		Err(CliError::UnknownFlag(flag)) => {
+			eprintln!("Error! Unknown flag: {flag}, see {program_name} --help");
+			cli::print_usage(program_name, &mut io::stderr());
+			return exit_code(EXIT_USAGE);
+		}
+		// The flags are right, the usage wouldn't tell what's wrong with them
+		Err(CliError::Invalid { flag, reason }) => {
+			eprintln!("Error! {flag} {reason}, see {program_name} --help");
+			// Unless nothing was given at all
+			if args.len() <= 1 {
+				cli::print_usage(program_name, &mut io::stderr());
+			}
+			return exit_code(EXIT_USAGE);
+		}
+	};
+	match command {
+		Command::Run(options) => {
+			let symbol_map = match &options.symbol_map {
+				Some(path) => match load_symbol_map(path) {
+					Some(symbol_map) => Some(symbol_map),
+					None => return exit_code(EXIT_USAGE),
+				},
+				None => None,
+			};
+			let mut tape = None;
+			let mut succeeded = 0;
+			// The first failure decides the status of the whole batch
+			let mut status = 0;
+			// Joined, the files are a single program
+			let programs = if options.concat { &options.filenames[..1] } else { &options.filenames[..] };
+			for (idx, filename) in programs.iter().enumerate() {
+				let (program_status, left) = run_program(filename, &options, tape.as_deref(), symbol_map.as_ref());
+				tape = left;
+				if program_status == 0 {
+					succeeded += 1;
+					continue;
+				}
+				if status == 0 {
+					status = program_status;
+				}
+				if options.fail_fast {
+					eprintln!("Stopping after {filename} failed, {} not run", programs.len() - idx - 1);
+					break;
+				}
+			}
+			if programs.len() > 1 && !options.quiet {
+				eprintln!("\n{succeeded} of {} programs finished successfully", programs.len());
 			}
+			exit_code(status)
+		}
+		Command::Check(options) => {
+			let symbol_map = match options.symbol_map.as_deref().map(load_symbol_map) {
+				Some(None) => return exit_code(EXIT_USAGE),
+				symbol_map => symbol_map.flatten(),
+			};
+			exit_code(if check(&options, symbol_map.as_ref()) { 0 } else { EXIT_FAILED })
+		}
+		Command::Test(options) => exit_code(if test(&options) { 0 } else { EXIT_FAILED }),
+		Command::Compile(options) => exit_code(if compile(&options) { 0 } else { EXIT_FAILED }),
+		Command::Build(options) => exit_code(if build(&options) { 0 } else { EXIT_FAILED }),
+		Command::Debug(options) => exit_code(if debug(&options) { 0 } else { EXIT_FAILED }),
+		#[cfg(feature = "tui")]
+		Command::Tui(options) => exit_code(if run_tui(&options) { 0 } else { EXIT_FAILED }),
+	}
+}
+
+fn exit_code(status: i32) -> ExitCode {
+	ExitCode::from(u8::try_from(status).unwrap_or(u8::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn run_options(line: &str) -> RunOptions {
+		match cli::parse(&line.split_whitespace().map(str::to_string).collect::<Vec<_>>()) {
+			Ok(Command::Run(options)) => *options,
+			_ => panic!("`{line}` isn't a run"),
+		}
+	}
+
+	/// A file named after `name` in the temporary directory, holding `contents`.
+	fn temp_file(name: &str, contents: &str) -> String {
+		let path = env::temp_dir().join(format!("brainfuck-{}-{name}", std::process::id()));
+		fs::write(&path, contents).unwrap();
+		path.to_string_lossy().into_owned()
+	}
+
+	#[test]
+	fn loaded_programs_keep_their_source() {
+		let path = temp_file("source.ook", "Ook. Ook! Ook! Ook.\n");
+		let loaded = load_program(&path, &run_options(&path), None).unwrap();
+		assert_eq!(loaded.characters.iter().collect::<String>(), ",         .        \n");
+		assert_eq!(loaded.source.iter().collect::<String>(), "Ook. Ook! Ook! Ook.\n");
+		// Blanked comments still show
+		let path = temp_file("source.bf", "+. # print\n");
+		let loaded = load_program(&path, &run_options(&format!("--line-comments # {path}")), None).unwrap();
+		assert_eq!(loaded.characters.iter().collect::<String>(), "+.        \n");
+		assert_eq!(loaded.source.iter().collect::<String>(), "+. # print\n");
+	}
+
+	#[test]
+	fn bytecode_runs_as_it_was_compiled() {
+		let ir = Ir::compile(&"++[->+<]>.".chars().collect::<Vec<_>>(), DEFAULT_OPT_LEVEL).unwrap();
+		let path = temp_file("compiled.bfc", "");
+		ir.save(&path).unwrap();
+		let loaded = load_program(&path, &run_options(&path), None).unwrap();
+		let loaded_ir = loaded.ir.unwrap();
+		assert_eq!(loaded_ir.ops(), ir.ops());
+		for backend in [Backend::Ir, Backend::Closures] {
+			let mut interpreter = Interpreter::new();
+			backend.run_compiled(&mut interpreter, &loaded_ir).unwrap();
+			assert_eq!(interpreter.tape()[..2], [0, 2], "{backend:?}");
+		}
+		// Commented out, it isn't the same program
+		let loaded = load_program(&path, &run_options(&format!("--line-comments # {path}")), None).unwrap();
+		assert!(loaded.ir.is_none());
+	}
+
+	#[test]
+	fn unwritable_traces_fail_the_run_without_stopping_it() {
+		// Takes every write and fails it
+		if !Path::new("/dev/full").exists() {
+			return;
+		}
+		let options = run_options("--trace /dev/full hello.bf");
+		let characters: Vec<char> = "+++>++".chars().collect();
+		let mut interpreter = Interpreter::new();
+		let mut write_failed = false;
+		let result = run_traced(&characters, &mut interpreter, options.trace.as_ref().unwrap(), &mut write_failed);
+		assert!(result.is_ok() && write_failed);
+		assert_eq!(interpreter.tape()[..2], [3, 2]);
+	}
+
+	#[test]
+	fn check_reads_programs_as_a_run_would() {
+		let path = temp_file("check.bf", "+[ # ]\n");
+		let check_line = |line: &str| match cli::parse(&line.split_whitespace().map(str::to_string).collect::<Vec<_>>()) {
+			Ok(Command::Check(options)) => check(&options, None),
+			_ => panic!("`{line}` isn't a check"),
+		};
+		assert!(check_line(&format!("check {path}")));
+		assert!(!check_line(&format!("check --line-comments # {path}")));
+		assert!(!check_line(&format!("--check --line-comments # {path}")));
+		let path = temp_file("check-bang.bf", "+.!]");
+		assert!(!check_line(&format!("check {path}")));
+		assert!(check_line(&format!("check --bang-input {path}")));
+	}
+
+	#[test]
+	fn ook_splits_bang_input_after_its_tokens() {
+		let path = temp_file("bang.ook", "Ook. Ook! Ook! Ook.\n!hi");
+		let loaded = load_program(&path, &run_options(&format!("--bang-input {path}")), None).unwrap();
+		assert_eq!(loaded.bang_input.as_deref(), Some(&b"hi"[..]));
+		let map = SymbolMap::parse(include_str!("../maps/ook.map")).unwrap();
+		let loaded = load_program(&path, &run_options(&format!("--bang-input {path}")), Some(&map)).unwrap();
+		assert_eq!(loaded.characters.iter().collect::<String>(), ",         .        \n");
+		assert_eq!(loaded.bang_input.as_deref(), Some(&b"hi"[..]));
+	}
+
+	#[test]
+	fn interrupted_runs_report_where_they_were() {
+		use std::sync::Arc;
+		use std::sync::atomic::{AtomicBool, Ordering};
+
+		let flag = Arc::new(AtomicBool::new(false));
+		let mut interpreter = Interpreter::new().with_interrupt_flag(Arc::clone(&flag));
+		let pressed = Arc::clone(&flag);
+		let ctrl_c = std::thread::spawn(move || {
+			std::thread::sleep(std::time::Duration::from_millis(20));
+			pressed.store(true, Ordering::Relaxed);
+		});
+		let program: Vec<char> = "+++>\n+[]".chars().collect();
+		let err = interpreter.run(&program).unwrap_err();
+		ctrl_c.join().unwrap();
+		assert!(matches!(err.reason, InterpreterErrorReason::Interrupted));
+
+		let report = interrupted_report(&program, &SourceMap::named("spin.bf".to_string(), program.len()), &interpreter, Backend::Naive);
+		let ip = interpreter.get_instruction_ptr();
+		assert!(ip == 6 || ip == 7, "{ip}");
+		assert!(report.starts_with(&format!("Interrupted!\nInstruction pointer: {ip} (spin.bf, line 2, col {})\n", ip - 4)), "{report}");
+		// Back at the `[` testing the cell, the loop is left until it's entered again
+		assert!(report.contains(&format!("Loop depth: {}\nData pointer: 1\n00000000: 0301 0000", ip - 6)), "{report}");
+	}
+
+	#[test]
+	fn core_dumps_open_at_the_failing_instruction() {
+		let program: Vec<char> = "+>++\n<<".chars().collect();
+		let path = temp_file("failed.bfcore", "");
+		let mut interpreter = Interpreter::new();
+		let err = run_dumping_core(&program, &mut interpreter, &path).unwrap_err();
+		assert!(matches!(err.reason, InterpreterErrorReason::PtrOutOfBounds(_)));
+
+		let mut debugger = Debugger::from_core(program, CoreDump::load(&path).unwrap());
+		let mut transcript = Vec::new();
+		debugger.repl("print ptr\nprint cell 1\nrecent\nstep\n".as_bytes(), &mut transcript).unwrap();
+		let transcript = String::from_utf8(transcript).unwrap();
+		assert_eq!(
+			transcript,
+			concat!(
+				"Core dump of a run that failed with PtrOutOfBounds(0) at instruction 6 (line 2, col 2)\n",
+				" 2 | <<\n",
+				"   |  ^\n",
+				"(bfdb) ptr = 0\n",
+				"(bfdb) cell 1 = 2\n",
+				"(bfdb) `+` at instruction 0 (line 1, col 1): ptr = 0, cell = 1\n",
+				"`>` at instruction 1 (line 1, col 2): ptr = 1, cell = 0\n",
+				"`+` at instruction 2 (line 1, col 3): ptr = 1, cell = 1\n",
+				"`+` at instruction 3 (line 1, col 4): ptr = 1, cell = 2\n",
+				"`<` at instruction 5 (line 2, col 1): ptr = 0, cell = 1\n",
+				"(bfdb) A core dump is read-only, it can only be inspected\n",
+				"(bfdb) \n",
+			)
+		);
+	}
+
+	#[test]
+	fn eval_runs_as_the_same_file_would() {
+		use cli::EVAL_SOURCE;
+		const PROGRAM: &str = "++++++++[>++++++++<-]>+.,.";
+		let path = temp_file("eval.bf", PROGRAM);
+		let input = temp_file("eval.in", "!");
+		let mut outputs = Vec::new();
+		for line in [format!("--input {input} -e {PROGRAM}"), format!("--input {input} {path}")] {
+			let options = run_options(&line);
+			let loaded = load_program(&options.filenames[0], &options, None).unwrap();
+			assert_eq!(loaded.characters, PROGRAM.chars().collect::<Vec<_>>(), "{line}");
+			let output = CapturedOutput::new();
+			let mut interpreter = new_interpreter(&loaded.characters, &options, None, None).unwrap()
+				.with_output_sink(output.clone())
+				.with_interrupt_flag(std::sync::Arc::default());
+			interpreter.run(&loaded.characters).unwrap();
+			outputs.push(output.contents());
 		}
+		assert_eq!(outputs, [b"A!", b"A!"]);
+
+		let options = run_options("-e +<<");
+		let loaded = load_program(&options.filenames[0], &options, None).unwrap();
+		let rendered = diagnostic::render_mapped("Reason: PtrOutOfBounds(0)", &loaded.source, Some(2), &loaded.sources, false);
+		assert!(rendered.contains(&format!(" --> {EVAL_SOURCE}:1:3\n")), "{rendered}");
+	}
+
+	#[test]
+	fn programs_read_from_stdin_need_their_input_elsewhere() {
+		let source = read_source_from(STDIN_SOURCE, &b"+[,.]"[..]).unwrap();
+		assert_eq!(source, b"+[,.]");
+		let characters: Vec<char> = symbol::decode_source(&source);
+
+		assert!(input_lost_to_stdin(STDIN_SOURCE, &run_options("-"), false, &characters));
+		let input = temp_file("stdin.in", "x");
+		assert!(!input_lost_to_stdin(STDIN_SOURCE, &run_options(&format!("--input {input} -")), false, &characters));
+		// Input given after a `!`, or not needed at all
+		assert!(!input_lost_to_stdin(STDIN_SOURCE, &run_options("--bang-input -"), true, &characters));
+		assert!(!input_lost_to_stdin(STDIN_SOURCE, &run_options("-"), false, &['+', '.']));
+		// A file leaves stdin to the program
+		let path = temp_file("stdin.bf", "+[,.]");
+		assert_eq!(read_source_from(&path, &b"ignored"[..]).unwrap(), b"+[,.]");
+		assert!(!input_lost_to_stdin(&path, &run_options(&path), false, &characters));
+	}
+
+	#[test]
+	fn concatenated_files_run_as_one_program() {
+		use brainfuck::interpreter::io::CapturedOutput;
+
+		// The loop opens in the first file and closes in the second
+		let header = temp_file("header.bf", "++[>+++");
+		let footer = temp_file("footer.bf", "\n<-]>.");
+		let options = run_options(&format!("--concat {header} {footer}"));
+		let loaded = load_program(&header, &options, None).unwrap();
+		let output = CapturedOutput::new();
+		Interpreter::with_output(output.clone()).run(&loaded.characters).unwrap();
+		assert_eq!(output.contents(), [6]);
+		let ir = compile_file(&options.filenames, DEFAULT_OPT_LEVEL).unwrap();
+		let output = CapturedOutput::new();
+		Interpreter::with_output(output.clone()).run_ir(&ir).unwrap();
+		assert_eq!(output.contents(), [6]);
+
+		let body = temp_file("body.bf", "+\n<<");
+		let options = run_options(&format!("--concat {header} {body}"));
+		let loaded = load_program(&header, &options, None).unwrap();
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new());
+		let err = interpreter.run(&loaded.characters).unwrap_err();
+		// Inside the loop the header opened, the second `<` runs off the tape
+		let location = error_location(&interpreter, Backend::Naive, &err);
+		let rendered = diagnostic::render_mapped("Reason", &loaded.source, location, &loaded.sources, false);
+		assert!(rendered.starts_with(&format!("Error! Reason\n --> {body}:2:2\n")), "{rendered}");
+	}
+
+	#[test]
+	fn bang_input_runs_like_a_separate_input_file() {
+		use brainfuck::interpreter::io::CapturedOutput;
+
+		let run = |path: &str, line: &str| {
+			let options = run_options(line);
+			let loaded = load_program(path, &options, None).unwrap();
+			let output = CapturedOutput::new();
+			let mut interpreter = new_interpreter(&loaded.characters, &options, None, loaded.bang_input.as_deref()).unwrap()
+				.with_output_sink(output.clone())
+				.with_interrupt_flag(std::sync::Arc::default());
+			interpreter.run(&loaded.characters).unwrap();
+			(loaded.bang_input, output.contents())
+		};
+		let combined = temp_file("combined.bf", ",.,.!hi");
+		let (program, input) = (temp_file("parts.bf", ",.,."), temp_file("parts.in", "hi"));
+		let (bang_input, output) = run(&combined, &format!("--bang-input {combined}"));
+		assert_eq!(bang_input.as_deref(), Some(&b"hi"[..]));
+		assert_eq!(output, run(&program, &format!("--input {input} {program}")).1);
+		assert_eq!(output, b"hi");
+
+		// Nothing to split off
+		assert_eq!(run(&program, &format!("--bang-input --input {input} {program}")), (None, b"hi".to_vec()));
+	}
+
+	#[test]
+	fn shebang_lines_are_skipped() {
+		use brainfuck::interpreter::io::CapturedOutput;
+
+		let run = |path: &str, line: &str| {
+			let options = run_options(line);
+			let loaded = load_program(path, &options, None).unwrap();
+			let output = CapturedOutput::new();
+			let mut interpreter = Interpreter::with_output(output.clone());
+			interpreter.feed_input(loaded.bang_input.as_deref().unwrap_or_default());
+			let result = interpreter.run(&loaded.characters);
+			(loaded, interpreter, result, output.contents())
+		};
+		let hello = temp_file("shebang.bf", "#!/usr/bin/env brainfuck\n++++++++[>+++++++++<-]>.+.\n");
+		assert_eq!(run(&hello, &hello).3, b"HI");
+		// The `!` of the shebang doesn't start the input
+		let echo = temp_file("shebang-bang.bf", "#!/usr/bin/env -S brainfuck --bang-input\n,.,.!HI");
+		let (loaded, _, _, output) = run(&echo, &format!("--bang-input {echo}"));
+		assert_eq!((loaded.bang_input.as_deref(), &output[..]), (Some(&b"HI"[..]), &b"HI"[..]));
+
+		let failing = temp_file("shebang-fails.bf", "#!/usr/bin/env brainfuck\n+\n<\n");
+		let (loaded, interpreter, result, _) = run(&failing, &failing);
+		let location = error_location(&interpreter, Backend::Naive, &result.unwrap_err());
+		let rendered = diagnostic::render_mapped("Reason", &loaded.source, location, &loaded.sources, false);
+		assert!(rendered.contains(" --> line 3, col 1\n  |\n3 | <\n"), "{rendered}");
+	}
+
+	#[test]
+	fn failed_runs_dump_their_nonzero_cells() {
+		let mut interpreter = Interpreter::new();
+		let program: Vec<char> = "++++++++[>++++++++<-]>+>>++<<<<".chars().collect();
+		assert!(interpreter.run(&program).is_err());
+		assert_eq!(memory_dump(&interpreter, &MemoryDump::NonZero), "1: 65 (A)\n3: 2 (.)\n");
+		assert_eq!(
+			memory_dump_json(&interpreter, &MemoryDump::NonZero),
+			r#"[{"address":1,"value":65},{"address":3,"value":2}]"#
+		);
+		assert_eq!(memory_dump_json(&interpreter, &MemoryDump::Range(2..5)), r#"{"start":2,"cells":[0,2,0]}"#);
 	}
 }
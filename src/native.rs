@@ -0,0 +1,122 @@
+//! Ahead-of-time compilation into a native executable through the system C compiler.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::emit;
+use crate::ir::Ir;
+
+pub const DEFAULT_C_OPT_FLAG: &str = "-O2";
+
+pub struct BuildOptions {
+	/// Flags passed straight to the C compiler, e.g. `-O3`.
+	pub cc_flags: Vec<String>,
+	/// Keep the generated C source around instead of deleting it after compiling.
+	pub keep_temps: bool,
+}
+
+impl Default for BuildOptions {
+	fn default() -> Self {
+		BuildOptions {
+			cc_flags: vec![DEFAULT_C_OPT_FLAG.to_string()],
+			keep_temps: false,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+	/// Couldn't write the temporary C source.
+	TempFiles(io::Error),
+	/// The compiler couldn't be started at all, usually because it isn't installed.
+	CompilerNotFound { compiler: String, error: io::Error },
+	/// The compiler ran but rejected the generated source.
+	CompilerFailed { compiler: String, stderr: String },
+	/// The compiler claimed success but left no executable behind.
+	MissingExecutable(PathBuf),
+}
+
+/// The C compiler to use: `$CC` if set, `cc` otherwise.
+pub fn c_compiler() -> String {
+	env::var("CC").ok().filter(|cc| !cc.is_empty()).unwrap_or_else(|| "cc".to_string())
+}
+
+/// Compiles `ir` into a native executable at `output`.
+///
+/// Returns the directory holding the C source when `keep_temps` was requested.
+pub fn build_executable(ir: &Ir, output: &Path, options: &BuildOptions) -> Result<Option<PathBuf>, BuildError> {
+	let temp_dir = env::temp_dir().join(format!("brainfuck-build-{}", std::process::id()));
+	fs::create_dir_all(&temp_dir).map_err(BuildError::TempFiles)?;
+	let source_path = temp_dir.join("program.c");
+	fs::write(&source_path, emit::c::emit(ir)).map_err(BuildError::TempFiles)?;
+
+	let result = run_compiler(&source_path, output, options);
+
+	if options.keep_temps {
+		result.map(|()| Some(temp_dir))
+	} else {
+		let _ = fs::remove_dir_all(&temp_dir);
+		result.map(|()| None)
+	}
+}
+
+fn run_compiler(source_path: &Path, output: &Path, options: &BuildOptions) -> Result<(), BuildError> {
+	let compiler = c_compiler();
+	let compiled = Command::new(&compiler)
+		.args(&options.cc_flags)
+		.arg(source_path)
+		.arg("-o")
+		.arg(output)
+		.output()
+		.map_err(|error| BuildError::CompilerNotFound { compiler: compiler.clone(), error })?;
+
+	if !compiled.status.success() {
+		let stderr = String::from_utf8_lossy(&compiled.stderr).into_owned();
+		return Err(BuildError::CompilerFailed { compiler, stderr });
+	}
+
+	if is_executable(output) {
+		Ok(())
+	} else {
+		Err(BuildError::MissingExecutable(output.to_path_buf()))
+	}
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+	use std::os::unix::fs::PermissionsExt;
+
+	fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+	path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn built_executables_print_the_program_output() {
+		if Command::new(c_compiler()).arg("--version").output().is_err() {
+			eprintln!("no C compiler, skipping");
+			return;
+		}
+		let characters: Vec<char> = "++++++++[>++++++++<-]>+.+.\n".chars().collect();
+		let ir = Ir::compile(&characters, crate::ir::DEFAULT_OPT_LEVEL).unwrap();
+		let output = env::temp_dir().join(format!("brainfuck-{}-native-test", std::process::id()));
+
+		let temps = build_executable(&ir, &output, &BuildOptions { keep_temps: true, ..BuildOptions::default() }).unwrap();
+		let temps = temps.expect("temps were kept");
+		assert!(temps.join("program.c").is_file());
+		let run = Command::new(&output).output().unwrap();
+		let _ = (fs::remove_dir_all(temps), fs::remove_file(&output));
+		assert!(run.status.success());
+		assert_eq!(run.stdout, b"AB");
+	}
+}
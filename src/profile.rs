@@ -0,0 +1,387 @@
+//! Reports for `--profile`: the loops that ran the most iterations with where they are, for
+//! `loops`, the most accessed cells, for `memory`, and the source annotated with how often every
+//! line ran, for `annotate`. Loops, cells and `--stats` can also be written as a single JSON
+//! document. `--coverage` and `--coverage-annotate` are built from the same execution counts.
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use brainfuck::interpreter::profile::LoopProfile;
+#[cfg(test)]
+use brainfuck::interpreter::profile::LoopCounts;
+use brainfuck::interpreter::stats::RunStats;
+use brainfuck::interpreter::Interpreter;
+use brainfuck::ir::Span;
+use brainfuck::symbol::InterpreterInstruction;
+use brainfuck::InterpreterSymbol;
+
+use crate::json;
+
+/// Loops or cells listed unless `--profile-top` says otherwise.
+pub const DEFAULT_TOP: usize = 10;
+
+/// Version of the layout of [`json_document`], bumped when fields change meaning or go away.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+	Table,
+	Json,
+}
+
+impl ReportFormat {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"table" => Some(ReportFormat::Table),
+			"json" => Some(ReportFormat::Json),
+			_ => None,
+		}
+	}
+}
+
+/// The `top` hottest loops of `profile`, located in `characters`.
+pub fn loop_report(characters: &[char], profile: &LoopProfile, top: usize) -> String {
+	let spans = Span::table(characters);
+	let hottest = profile.hottest(top);
+	// Writing into a String can't fail
+	let mut report = String::new();
+	if hottest.is_empty() {
+		report.push_str("Loops: none ran\n");
+	} else {
+		let _ = writeln!(report, "{:>10}  {:>12}  {:>14}", "loop", "entries", "iterations");
+	}
+	for (loop_start, counts) in hottest {
+		let span = spans[loop_start];
+		let position = format!("{}:{}", span.line, span.column);
+		let _ = writeln!(report, "{position:>10}  {:>12}  {:>14}", counts.entries, counts.iterations);
+	}
+	report
+}
+
+/// The `top` most accessed cells of a run profiled with [`Interpreter::with_memory_profile`], with
+/// their final values, and how far the pointer went.
+pub fn memory_report(stats: &RunStats, interpreter: &Interpreter, top: usize) -> String {
+	// Writing into a String can't fail
+	let mut report = String::new();
+	let _ = writeln!(report, "Cells touched: {}, data pointer reached cell {}", stats.cells.len(), stats.peak_data_ptr);
+	let hottest = stats.hottest_cells(top);
+	if !hottest.is_empty() {
+		let _ = writeln!(report, "{:>8}  {:>12}  {:>12}  {:>5}", "cell", "reads", "writes", "value");
+	}
+	for (address, accesses) in hottest {
+		let value = interpreter.cell(address).unwrap_or_default();
+		let _ = writeln!(report, "{address:>8}  {:>12}  {:>12}  {value:>5}", accesses.reads, accesses.writes);
+	}
+	report
+}
+
+/// The reports that were asked for as one JSON object, the ones left out being absent:
+/// `stats` for `--stats`, `loops` for `--profile loops` and `memory` for `--profile memory`.
+pub fn json_document(
+	characters: &[char],
+	interpreter: &Interpreter,
+	stats: bool,
+	loop_top: Option<usize>,
+	memory_top: Option<usize>,
+) -> json::Object {
+	let mut document = json::Object::new().raw("schema_version", SCHEMA_VERSION);
+	let run_stats = interpreter.stats();
+	if let (true, Some(run_stats)) = (stats, &run_stats) {
+		document = document.raw("stats", stats_json(run_stats));
+	}
+	if let (Some(top), Some(profile)) = (loop_top, interpreter.loop_profile()) {
+		let spans = Span::table(characters);
+		let loops = profile.hottest(top).into_iter().map(|(loop_start, counts)| {
+			let span = spans[loop_start];
+			json::Object::new()
+				.raw("offset", loop_start)
+				.raw("line", span.line)
+				.raw("column", span.column)
+				.raw("entries", counts.entries)
+				.raw("iterations", counts.iterations)
+		});
+		document = document.raw("loops", json::array(loops));
+	}
+	if let (Some(top), Some(run_stats)) = (memory_top, &run_stats) {
+		let cells = run_stats.hottest_cells(top).into_iter().map(|(address, accesses)| {
+			json::Object::new()
+				.raw("cell", address)
+				.raw("reads", accesses.reads)
+				.raw("writes", accesses.writes)
+				.raw("value", interpreter.cell(address).unwrap_or_default())
+		});
+		let memory = json::Object::new()
+			.raw("cells_touched", run_stats.cells.len())
+			.raw("peak_data_ptr", run_stats.peak_data_ptr)
+			.raw("hottest", json::array(cells));
+		document = document.raw("memory", memory);
+	}
+	document
+}
+
+fn stats_json(stats: &RunStats) -> json::Object {
+	let mut executed = json::Object::new();
+	for instruction in InterpreterInstruction::ALL {
+		executed = executed.raw(&instruction.to_char().to_string(), stats.executed(instruction));
+	}
+	json::Object::new()
+		.raw("steps", stats.steps)
+		.raw("executed", executed)
+		.raw("loop_iterations", stats.loop_iterations)
+		.raw("bytes_read", stats.bytes_read)
+		.raw("bytes_written", stats.bytes_written)
+		.raw("peak_data_ptr", stats.peak_data_ptr)
+		.raw("highest_written", stats.highest_written)
+		.raw("dirty_len", stats.dirty_len)
+		.raw("peak_loop_depth", stats.peak_loop_depth)
+}
+
+/// The source with the executions of the instructions on every line in a left column, gcov style:
+/// `#####` for lines whose instructions never ran and blanks for lines without instructions.
+pub fn annotate(characters: &[char], counts: &[Option<u64>]) -> String {
+	let mut annotated = String::new();
+	let mut offset = 0;
+	// Nothing follows a final newline
+	let characters = characters.strip_suffix(&['\n']).unwrap_or(characters);
+	for line in characters.split(|&character| character == '\n') {
+		let mut has_instructions = false;
+		let mut executions = 0u64;
+		for (idx, character) in line.iter().enumerate() {
+			if let InterpreterSymbol::Instruction(_) = InterpreterSymbol::from_char(Some(character)) {
+				has_instructions = true;
+				executions = executions.saturating_add(counts.get(offset + idx).copied().flatten().unwrap_or(0));
+			}
+		}
+		offset += line.len() + 1;
+
+		let column = match (has_instructions, executions) {
+			(false, _) => String::new(),
+			(true, 0) => String::from("#####"),
+			(true, executions) => executions.to_string(),
+		};
+		let source: String = line.iter().collect();
+		// Writing into a String can't fail
+		let _ = writeln!(annotated, "{column:>9}: {source}");
+	}
+	annotated
+}
+
+/// Runs of instructions that never executed, as source offset ranges. Comments between two such
+/// instructions don't split a run.
+fn unexecuted_runs(characters: &[char], counts: &[Option<u64>]) -> Vec<Range<usize>> {
+	let mut runs: Vec<Range<usize>> = Vec::new();
+	let mut extends = false;
+	for (idx, character) in characters.iter().enumerate() {
+		if let InterpreterSymbol::Instruction(_) = InterpreterSymbol::from_char(Some(character)) {
+			let executed = counts.get(idx).copied().flatten().unwrap_or(0) > 0;
+			match runs.last_mut() {
+				_ if executed => extends = false,
+				Some(run) if extends => run.end = idx + 1,
+				_ => {
+					runs.push(idx..idx + 1);
+					extends = true;
+				}
+			}
+		}
+	}
+	runs
+}
+
+/// How many instructions ran, as a share of all of them, and where the ones that didn't are.
+pub fn coverage_report(characters: &[char], counts: &[Option<u64>]) -> String {
+	let instructions: Vec<usize> = (0..characters.len())
+		.filter(|&idx| matches!(InterpreterSymbol::from_char(characters.get(idx)), InterpreterSymbol::Instruction(_)))
+		.collect();
+	let executed = instructions.iter()
+		.filter(|&&idx| counts.get(idx).copied().flatten().unwrap_or(0) > 0)
+		.count();
+	let percentage = if instructions.is_empty() { 100.0 } else { executed as f64 * 100.0 / instructions.len() as f64 };
+
+	// Writing into a String can't fail
+	let mut report = String::new();
+	let _ = writeln!(report, "Coverage: {executed} of {} instructions executed ({percentage:.1}%)", instructions.len());
+	let runs = unexecuted_runs(characters, counts);
+	if !runs.is_empty() {
+		report.push_str("Never executed:\n");
+	}
+	let spans = Span::table(characters);
+	for run in runs {
+		let (first, last) = (spans[run.start], spans[run.end - 1]);
+		let _ = writeln!(
+			report,
+			"  {}:{}-{}:{} (offsets {}..{})",
+			first.line, first.column, last.line, last.column, run.start, run.end,
+		);
+	}
+	report
+}
+
+/// The source with every run of instructions that never executed wrapped in `{` and `}`, which
+/// being comments leave the program the same.
+pub fn coverage_annotate(characters: &[char], counts: &[Option<u64>]) -> String {
+	let runs = unexecuted_runs(characters, counts);
+	let mut runs = runs.iter().peekable();
+	let mut annotated = String::new();
+	for (idx, character) in characters.iter().enumerate() {
+		if runs.peek().is_some_and(|run| run.start == idx) {
+			annotated.push('{');
+		}
+		annotated.push(*character);
+		if runs.next_if(|run| run.end == idx + 1).is_some() {
+			annotated.push('}');
+		}
+	}
+	annotated
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use brainfuck::backend::Backend;
+	use brainfuck::interpreter::io::CapturedOutput;
+
+	const NESTED: &str = "+++\n[>++[>+<-]<-]";
+
+	fn chars(program: &str) -> Vec<char> {
+		program.chars().collect()
+	}
+
+	/// A run of `program`, compiled from the source so the IR keeps pointing into it.
+	fn profiled(program: &str, backend: Backend) -> Interpreter {
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new()).with_loop_profile();
+		let ir = brainfuck::ir::Ir::compile(&chars(program), brainfuck::ir::DEFAULT_OPT_LEVEL).unwrap();
+		match backend {
+			Backend::Naive => interpreter.run(&chars(program)).unwrap(),
+			_ => backend.run_compiled(&mut interpreter, &ir).unwrap(),
+		}
+		interpreter
+	}
+
+	#[test]
+	fn loops_are_counted_by_their_bracket() {
+		let interpreter = profiled(NESTED, Backend::Naive);
+		let profile = interpreter.loop_profile().unwrap();
+		assert_eq!(profile.get(4), Some(LoopCounts { entries: 1, iterations: 3 }));
+		assert_eq!(profile.get(8), Some(LoopCounts { entries: 3, iterations: 6 }));
+		assert_eq!(
+			loop_report(&chars(NESTED), profile, DEFAULT_TOP),
+			concat!(
+				"      loop       entries      iterations\n",
+				"       2:5             3               6\n",
+				"       2:1             1               3\n",
+			)
+		);
+		assert_eq!(loop_report(&chars(NESTED), profile, 1).lines().count(), 2);
+	}
+
+	#[test]
+	fn optimized_loops_count_the_same() {
+		// The inner loop runs as a single MulAdd
+		let naive = profiled(NESTED, Backend::Naive);
+		assert_eq!(profiled(NESTED, Backend::Ir).loop_profile(), naive.loop_profile());
+	}
+
+	#[test]
+	fn annotated_lines_count_their_executions() {
+		let program = chars("++ set up\n\n[>+<-] move\n>[-]\n[\n  +++\n]\n");
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new()).with_coverage();
+		interpreter.run(&program).unwrap();
+		assert_eq!(
+			annotate(&program, interpreter.execution_counts().unwrap()),
+			concat!(
+				"        2: ++ set up\n",
+				"         : \n",
+				"       13: [>+<-] move\n",
+				"        8: >[-]\n",
+				"        1: [\n",
+				"    #####:   +++\n",
+				"    #####: ]\n",
+			)
+		);
+		// Without the final newline, the last line still shows
+		assert!(annotate(&program[..program.len() - 1], interpreter.execution_counts().unwrap()).ends_with("    #####: ]\n"));
+	}
+
+	#[test]
+	fn memory_report_puts_the_hottest_cell_first() {
+		let program = chars(">>>++++++++[-]+++>>>>>>>+");
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new()).with_memory_profile();
+		interpreter.run(&program).unwrap();
+		let stats = interpreter.stats().unwrap();
+		assert_eq!(
+			memory_report(&stats, &interpreter, DEFAULT_TOP),
+			concat!(
+				"Cells touched: 2, data pointer reached cell 10\n",
+				"    cell         reads        writes  value\n",
+				"       3             9            19      3\n",
+				"      10             0             1      1\n",
+			)
+		);
+		// Every `[` test reads the cell, every `+` and `-` writes it
+		let cells: Vec<_> = stats.cells.iter().map(|(&address, accesses)| (address, accesses.reads, accesses.writes)).collect();
+		assert_eq!(cells, [(3, 9, 19), (10, 0, 1)]);
+		assert_eq!(memory_report(&stats, &interpreter, 1).lines().count(), 3);
+	}
+
+	#[test]
+	fn json_document_has_the_enabled_reports() {
+		let program = chars(",[->+<]>.");
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new()).with_memory_profile().with_loop_profile();
+		interpreter.feed_input(b"A");
+		interpreter.run(&program).unwrap();
+
+		let document = json_document(&program, &interpreter, true, Some(DEFAULT_TOP), Some(DEFAULT_TOP)).to_string();
+		let document = json::read::parse(&document).unwrap();
+		assert_eq!(document.get("schema_version").and_then(json::read::Value::as_u64), Some(u64::from(SCHEMA_VERSION)));
+
+		let stats = document.get("stats").unwrap();
+		for key in ["steps", "loop_iterations", "bytes_read", "bytes_written", "peak_data_ptr", "highest_written", "dirty_len", "peak_loop_depth"] {
+			assert!(stats.get(key).and_then(json::read::Value::as_u64).is_some(), "stats.{key}");
+		}
+		let executed = stats.get("executed").unwrap();
+		for instruction in InterpreterInstruction::ALL {
+			assert!(executed.get(&instruction.to_char().to_string()).and_then(json::read::Value::as_u64).is_some());
+		}
+		assert_eq!(executed.get("+").and_then(json::read::Value::as_u64), Some(65));
+
+		let Some(json::read::Value::Array(loops)) = document.get("loops") else { panic!("no loops") };
+		assert_eq!(loops.len(), 1);
+		for key in ["offset", "line", "column", "entries", "iterations"] {
+			assert!(loops[0].get(key).and_then(json::read::Value::as_u64).is_some(), "loops.{key}");
+		}
+		assert_eq!(loops[0].get("iterations").and_then(json::read::Value::as_u64), Some(65));
+
+		let memory = document.get("memory").unwrap();
+		assert_eq!(memory.get("cells_touched").and_then(json::read::Value::as_u64), Some(2));
+		let Some(json::read::Value::Array(hottest)) = memory.get("hottest") else { panic!("no hottest cells") };
+		assert_eq!(hottest[0].get("cell").and_then(json::read::Value::as_u64), Some(0));
+		assert_eq!(hottest[1].get("value").and_then(json::read::Value::as_u64), Some(65));
+
+		// Only what was asked for
+		let document = json::read::parse(&json_document(&program, &interpreter, false, None, Some(1)).to_string()).unwrap();
+		assert!(document.get("stats").is_none() && document.get("loops").is_none() && document.get("memory").is_some());
+	}
+
+	#[test]
+	fn loops_skipped_on_a_zero_cell_are_uncovered() {
+		let program = chars("+.>\n[-<+>]<. done");
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new()).with_coverage();
+		interpreter.run(&program).unwrap();
+		let counts = interpreter.execution_counts().unwrap();
+		assert_eq!(interpreter.unexecuted_indices(), [5, 6, 7, 8, 9]);
+		assert_eq!(
+			coverage_report(&program, counts),
+			concat!(
+				"Coverage: 6 of 11 instructions executed (54.5%)\n",
+				"Never executed:\n",
+				"  2:2-2:6 (offsets 5..10)\n",
+			)
+		);
+		assert_eq!(coverage_annotate(&program, counts), "+.>\n[{-<+>]}<. done");
+
+		let program = chars("+[-]");
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new()).with_coverage();
+		interpreter.run(&program).unwrap();
+		assert_eq!(coverage_report(&program, interpreter.execution_counts().unwrap()), "Coverage: 4 of 4 instructions executed (100.0%)\n");
+	}
+}
@@ -0,0 +1,218 @@
+//! Running untrusted programs in one call, for embedders such as web services.
+//!
+//! [`run`] never reads the real stdin or writes the real stdout, always terminates, bounded by
+//! [`Limits`], and doesn't panic: every way a run can end is an [`Outcome`], with the output
+//! printed up to there.
+
+use std::time::{Duration, Instant};
+
+use crate::interpreter::error::{InterpreterError, InterpreterErrorReason};
+use crate::interpreter::report::RunReport;
+use crate::interpreter::cancel::CancellationToken;
+use crate::interpreter::config::InterpreterConfig;
+use crate::interpreter::io::CapturedOutput;
+use crate::interpreter::stats::RunStats;
+use crate::interpreter::{EofPolicy, Interpreter, SliceOutcome, DEFAULT_MAX_LOOP_DEPTH, MEM_SIZE};
+use crate::ir::{self, CompileError};
+
+/// What a sandboxed run may use. Fuel alone makes sure it ends, the rest stop runaway programs
+/// sooner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+	/// Instructions that may run.
+	pub fuel: u64,
+	/// Bytes that may be printed.
+	pub max_output: u64,
+	/// Cells of the tape that may be used, at most [`MEM_SIZE`].
+	pub max_tape_cells: usize,
+	/// Loops that may be nested.
+	pub max_loop_depth: usize,
+	/// Wall-clock time the run may take.
+	pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Limits {
+			fuel: 100_000_000,
+			max_output: 1 << 20,
+			max_tape_cells: MEM_SIZE,
+			max_loop_depth: DEFAULT_MAX_LOOP_DEPTH,
+			timeout: None,
+		}
+	}
+}
+
+pub use crate::interpreter::report::Limit;
+
+#[derive(Debug)]
+pub enum Outcome {
+	Completed { output: Vec<u8>, stats: RunStats },
+	RuntimeError { error: InterpreterError, output_so_far: Vec<u8> },
+	LimitExceeded { which: Limit, output_so_far: Vec<u8> },
+	/// The token given to [`run_cancellable`] was cancelled.
+	Cancelled { output_so_far: Vec<u8> },
+	/// The program's brackets don't match, it didn't run.
+	ParseError(Vec<CompileError>),
+}
+
+/// Runs `program` on `input` within `limits`. Reading past the end of `input` is a
+/// [`Outcome::RuntimeError`].
+pub fn run(program: &str, input: &[u8], limits: Limits) -> Outcome {
+	run_cancellable(program, input, limits, &CancellationToken::new())
+}
+
+/// Like [`run`], also stopping soon after `token` is cancelled from another thread.
+pub fn run_cancellable(program: &str, input: &[u8], limits: Limits, token: &CancellationToken) -> Outcome {
+	run_reported(program, input, limits, token).0
+}
+
+/// Like [`run`], with `,` past the end of `input` doing what `eof_policy` says.
+pub fn run_with_eof(program: &str, input: &[u8], limits: Limits, eof_policy: EofPolicy) -> Outcome {
+	run_configured(program, input, limits, eof_policy, &CancellationToken::new()).0
+}
+
+/// Like [`run_cancellable`], along with a [`RunReport`] of the run.
+pub fn run_reported(program: &str, input: &[u8], limits: Limits, token: &CancellationToken) -> (Outcome, RunReport) {
+	run_configured(program, input, limits, EofPolicy::Error, token)
+}
+
+fn run_configured(
+	program: &str,
+	input: &[u8],
+	limits: Limits,
+	eof_policy: EofPolicy,
+	token: &CancellationToken,
+) -> (Outcome, RunReport) {
+	let characters: Vec<char> = program.chars().collect();
+	let errors = ir::bracket_errors(&characters);
+	if !errors.is_empty() {
+		return (Outcome::ParseError(errors), RunReport::not_run());
+	}
+
+	let output = CapturedOutput::new();
+	let configured = Interpreter::with_output(output.clone()).configured(InterpreterConfig {
+		eof_policy,
+		tape_cells: limits.max_tape_cells,
+		max_loop_depth: limits.max_loop_depth,
+		max_output: Some(limits.max_output),
+		timeout: limits.timeout,
+		..InterpreterConfig::default()
+	});
+	let mut interpreter = match configured {
+		Ok(interpreter) => interpreter.with_stats().with_cancellation(token),
+		Err(error) => return (Outcome::RuntimeError { error, output_so_far: Vec::new() }, RunReport::not_run()),
+	};
+	interpreter.feed_input(input);
+	interpreter.load(&characters);
+
+	let start = Instant::now();
+	let result = interpreter.run_for(limits.fuel);
+	let report = RunReport::new(&interpreter, result.as_ref().err(), start.elapsed());
+	let output_so_far = output.contents();
+	let outcome = match result {
+		Ok(SliceOutcome::Halted) => {
+			Outcome::Completed { output: output_so_far, stats: interpreter.stats().unwrap_or_default() }
+		}
+		Ok(SliceOutcome::OutOfSteps) => Outcome::LimitExceeded { which: Limit::Fuel, output_so_far },
+		Err(error) => match Limit::of(&error.reason) {
+			Some(which) => Outcome::LimitExceeded { which, output_so_far },
+			None if matches!(error.reason, InterpreterErrorReason::Interrupted) => Outcome::Cancelled { output_so_far },
+			None => Outcome::RuntimeError { error, output_so_far },
+		},
+	};
+	(outcome, report)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::interpreter::report::FinalState;
+
+	/// Echoes two bytes, one per cell, then counts a third cell down from one.
+	const ECHO: &str = ">,.>,.>+[-]";
+
+	#[test]
+	fn reports_a_finished_run() {
+		let (outcome, report) = run_reported(ECHO, b"hi", Limits::default(), &CancellationToken::new());
+		assert!(matches!(outcome, Outcome::Completed { output, .. } if output == b"hi"));
+		let RunReport { steps, bytes_read, bytes_written, peak_loop_depth, tape_cells_used, stopped_by, state, .. } = report;
+		assert_eq!(
+			(steps, bytes_read, bytes_written, peak_loop_depth, tape_cells_used, stopped_by, state),
+			(12, 2, 2, 1, 4, None, FinalState::Halted)
+		);
+	}
+
+	#[test]
+	fn reports_a_run_out_of_fuel() {
+		let limits = Limits { fuel: 4, ..Limits::default() };
+		let (outcome, report) = run_reported(ECHO, b"hi", limits, &CancellationToken::new());
+		assert!(matches!(outcome, Outcome::LimitExceeded { which: Limit::Fuel, output_so_far } if output_so_far == b"h"));
+		let RunReport { steps, bytes_read, bytes_written, peak_loop_depth, tape_cells_used, stopped_by, state, .. } = report;
+		assert_eq!(
+			(steps, bytes_read, bytes_written, peak_loop_depth, tape_cells_used, stopped_by, state),
+			(4, 1, 1, 0, 2, Some(Limit::Fuel), FinalState::Paused)
+		);
+	}
+
+	/// Prints `!`, then does `rest`.
+	fn after_bang(rest: &str) -> String {
+		format!("{}.{rest}", "+".repeat(33))
+	}
+
+	#[test]
+	fn finished_and_failed_runs_keep_the_output() {
+		let outcome = run(&after_bang(""), b"", Limits::default());
+		assert!(matches!(&outcome, Outcome::Completed { output, .. } if output == b"!"), "{outcome:?}");
+		let outcome = run(&after_bang("<"), b"", Limits::default());
+		assert!(matches!(
+			&outcome,
+			Outcome::RuntimeError { error: InterpreterError { reason: InterpreterErrorReason::PtrOutOfBounds(_) }, output_so_far }
+				if output_so_far == b"!"
+		), "{outcome:?}");
+	}
+
+	#[test]
+	fn every_limit_keeps_the_output() {
+		let cases = [
+			(Limit::Fuel, "+.", Limits { fuel: 35, ..Limits::default() }),
+			(Limit::Output, "..", Limits { max_output: 1, ..Limits::default() }),
+			(Limit::Tape, "[>+]", Limits { max_tape_cells: 4, ..Limits::default() }),
+			(Limit::LoopDepth, "[[[]]]", Limits { max_loop_depth: 2, ..Limits::default() }),
+			(Limit::Time, "[]", Limits { fuel: u64::MAX, timeout: Some(Duration::from_millis(1)), ..Limits::default() }),
+		];
+		for (limit, rest, limits) in cases {
+			match run(&after_bang(rest), b"", limits) {
+				Outcome::LimitExceeded { which, output_so_far } => {
+					assert_eq!((which, output_so_far), (limit, b"!".to_vec()), "{limit:?}");
+				}
+				outcome => panic!("{limit:?}: {outcome:?}"),
+			}
+		}
+	}
+
+	#[test]
+	fn cancelled_runs_keep_the_output() {
+		let token = CancellationToken::new();
+		let canceller = {
+			let token = token.clone();
+			std::thread::spawn(move || {
+				std::thread::sleep(Duration::from_millis(20));
+				token.cancel();
+			})
+		};
+		let limits = Limits { fuel: u64::MAX, ..Limits::default() };
+		let outcome = run_cancellable(&after_bang("[]"), b"", limits, &token);
+		canceller.join().unwrap();
+		assert!(matches!(&outcome, Outcome::Cancelled { output_so_far } if output_so_far == b"!"), "{outcome:?}");
+	}
+
+	#[test]
+	fn unmatched_brackets_are_not_run() {
+		let outcome = run(&after_bang("[]]["), b"", Limits::default());
+		let Outcome::ParseError(errors) = outcome else {
+			panic!("expected a parse error, got {outcome:?}");
+		};
+		assert!(matches!(errors[..], [CompileError::UnmatchedLoopEnd(end), CompileError::UnmatchedLoopStart(start)] if end.offset == 36 && start.offset == 37));
+	}
+}
@@ -0,0 +1,89 @@
+//! Programs split over several files, run or compiled as one, and where each offset came from.
+
+use std::ops::Range;
+
+/// Which part of a concatenated program each file is, in order.
+pub struct SourceMap {
+	files: Vec<(String, Range<usize>)>,
+}
+
+impl SourceMap {
+	/// A program all from one file, which messages don't need to name.
+	pub fn single() -> Self {
+		SourceMap { files: Vec::new() }
+	}
+
+	/// A program of `len` characters all from one source, named in messages all the same, such as
+	/// one given on the command line that no file name would point to.
+	pub fn named(name: String, len: usize) -> Self {
+		SourceMap { files: vec![(name, 0..len)] }
+	}
+
+	/// Joins the `files`, names along with their characters, one right after the other, so a loop
+	/// can open in a file and close in a later one. Just one file is [`SourceMap::single`].
+	pub fn concatenate(mut files: Vec<(String, Vec<char>)>) -> (Vec<char>, Self) {
+		if files.len() == 1 {
+			return (files.remove(0).1, Self::single());
+		}
+		let mut characters = Vec::new();
+		let mut map = Vec::new();
+		for (name, file) in files {
+			let start = characters.len();
+			characters.extend(file);
+			map.push((name, start..characters.len()));
+		}
+		(characters, SourceMap { files: map })
+	}
+
+	/// Cuts the program down to its first `len` characters, files past them dropping out.
+	pub fn truncate(&mut self, len: usize) {
+		self.files.retain(|(_, range)| range.start < len);
+		if let Some((_, range)) = self.files.last_mut() {
+			range.end = range.end.min(len);
+		}
+	}
+
+	/// The name and characters of the file holding `offset` of the whole `characters`, along with
+	/// the offset within it. `None` for a single file or an offset past the end.
+	pub fn locate<'a>(&'a self, characters: &'a [char], offset: usize) -> Option<(&'a str, &'a [char], usize)> {
+		let (name, range) = self.files.iter().find(|(_, range)| range.contains(&offset))?;
+		Some((name, &characters[range.clone()], offset - range.start))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn joined() -> (Vec<char>, SourceMap) {
+		SourceMap::concatenate(vec![
+			("a.bf".to_string(), "+[".chars().collect()),
+			("b.bf".to_string(), "-]".chars().collect()),
+		])
+	}
+
+	#[test]
+	fn locates_offsets_in_their_file() {
+		let (characters, sources) = joined();
+		assert_eq!(sources.locate(&characters, 1), Some(("a.bf", &['+', '['][..], 1)));
+		assert_eq!(sources.locate(&characters, 3), Some(("b.bf", &['-', ']'][..], 1)));
+		assert_eq!(sources.locate(&characters, 4), None);
+	}
+
+	#[test]
+	fn truncating_cuts_the_last_file_short() {
+		let (mut characters, mut sources) = joined();
+		characters.truncate(1);
+		sources.truncate(1);
+		assert_eq!(sources.locate(&characters, 0), Some(("a.bf", &['+'][..], 0)));
+		assert_eq!(sources.locate(&characters, 2), None);
+	}
+
+	#[test]
+	fn single_named_source_is_located() {
+		let characters: Vec<char> = "+[".chars().collect();
+		let sources = SourceMap::named("<command line>".to_string(), characters.len());
+		assert_eq!(sources.locate(&characters, 1), Some(("<command line>", &characters[..], 1)));
+		assert_eq!(SourceMap::single().locate(&characters, 1), None);
+	}
+}
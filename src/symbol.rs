@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InterpreterInstruction {
 	MovePtrRight,
 	MovePtrLeft,
@@ -9,28 +10,191 @@ pub enum InterpreterInstruction {
 	LoopEnd,
 }
 
+impl InterpreterInstruction {
+	/// Every instruction, in declaration order.
+	pub const ALL: [InterpreterInstruction; 8] = [
+		InterpreterInstruction::MovePtrRight,
+		InterpreterInstruction::MovePtrLeft,
+		InterpreterInstruction::IncrementPtr,
+		InterpreterInstruction::DecrementPtr,
+		InterpreterInstruction::PrintPtr,
+		InterpreterInstruction::ReadPtr,
+		InterpreterInstruction::LoopStart,
+		InterpreterInstruction::LoopEnd,
+	];
+
+	/// The source character of the instruction.
+	pub fn to_char(self) -> char {
+		match self {
+			InterpreterInstruction::MovePtrRight => '>',
+			InterpreterInstruction::MovePtrLeft => '<',
+			InterpreterInstruction::IncrementPtr => '+',
+			InterpreterInstruction::DecrementPtr => '-',
+			InterpreterInstruction::PrintPtr => '.',
+			InterpreterInstruction::ReadPtr => ',',
+			InterpreterInstruction::LoopStart => '[',
+			InterpreterInstruction::LoopEnd => ']',
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InterpreterSymbol {
 	Instruction(InterpreterInstruction),
-	EOF,
+	Eof,
 	Other(char),
 }
 
 impl InterpreterSymbol {
 	pub fn from_char(c: Option<&char>) -> Self {
-		if let Some(c) = c {
-			match c {
-				'>' => InterpreterSymbol::Instruction(InterpreterInstruction::MovePtrRight),
-				'<' => InterpreterSymbol::Instruction(InterpreterInstruction::MovePtrLeft),
-				'+' => InterpreterSymbol::Instruction(InterpreterInstruction::IncrementPtr),
-				'-' => InterpreterSymbol::Instruction(InterpreterInstruction::DecrementPtr),
-				'.' => InterpreterSymbol::Instruction(InterpreterInstruction::PrintPtr),
-				',' => InterpreterSymbol::Instruction(InterpreterInstruction::ReadPtr),
-				'[' => InterpreterSymbol::Instruction(InterpreterInstruction::LoopStart),
-				']' => InterpreterSymbol::Instruction(InterpreterInstruction::LoopEnd),
-				any_c => InterpreterSymbol::Other(any_c.clone()),
+		match c {
+			Some(c) if c.is_ascii() => Self::from_byte(Some(&(*c as u8))),
+			Some(c) => InterpreterSymbol::Other(*c),
+			None => InterpreterSymbol::Eof,
+		}
+	}
+
+	/// Classifies every character once, for runs that visit the same ones over and over.
+	pub fn tokenize(characters: &[char]) -> Vec<Self> {
+		characters.iter().map(|character| Self::from_char(Some(character))).collect()
+	}
+
+	/// The symbol at `index` of a [`InterpreterSymbol::tokenize`]d program, past its end being
+	/// [`InterpreterSymbol::Eof`].
+	pub fn at(symbols: &[Self], index: usize) -> Self {
+		symbols.get(index).copied().unwrap_or(InterpreterSymbol::Eof)
+	}
+
+	/// Classifies a raw source byte, anything but the eight instructions being a comment.
+	pub fn from_byte(byte: Option<&u8>) -> Self {
+		if let Some(byte) = byte {
+			match byte {
+				b'>' => InterpreterSymbol::Instruction(InterpreterInstruction::MovePtrRight),
+				b'<' => InterpreterSymbol::Instruction(InterpreterInstruction::MovePtrLeft),
+				b'+' => InterpreterSymbol::Instruction(InterpreterInstruction::IncrementPtr),
+				b'-' => InterpreterSymbol::Instruction(InterpreterInstruction::DecrementPtr),
+				b'.' => InterpreterSymbol::Instruction(InterpreterInstruction::PrintPtr),
+				b',' => InterpreterSymbol::Instruction(InterpreterInstruction::ReadPtr),
+				b'[' => InterpreterSymbol::Instruction(InterpreterInstruction::LoopStart),
+				b']' => InterpreterSymbol::Instruction(InterpreterInstruction::LoopEnd),
+				any_byte => InterpreterSymbol::Other(*any_byte as char),
 			}
 		} else {
-			InterpreterSymbol::EOF
+			InterpreterSymbol::Eof
 		}
 	}
-}
\ No newline at end of file
+}
+
+/// Turns raw source bytes into the characters the interpreter runs, never failing: instructions
+/// are ASCII, so invalid UTF-8 can only be in comments and is replaced there.
+///
+/// A leading `#!` line is skipped by blanking it out rather than removing it, so line numbers and
+/// offsets in the rest of the file stay the same.
+pub fn decode_source(bytes: &[u8]) -> Vec<char> {
+	let mut characters: Vec<char> = String::from_utf8_lossy(bytes).chars().collect();
+	if characters.starts_with(&['#', '!']) {
+		for character in characters.iter_mut().take_while(|character| **character != '\n') {
+			*character = ' ';
+		}
+	}
+	characters
+}
+
+/// Blanks out everything from each `marker` to the end of its line, instructions included, so
+/// prose comments can use any character. Like the `#!` line, the newlines and every offset stay.
+/// An empty marker leaves the program alone.
+pub fn strip_line_comments(characters: &mut [char], marker: &str) {
+	let marker: Vec<char> = marker.chars().collect();
+	if marker.is_empty() {
+		return;
+	}
+	let mut idx = 0;
+	while idx < characters.len() {
+		if characters[idx..].starts_with(&marker) {
+			for character in characters[idx..].iter_mut().take_while(|character| **character != '\n') {
+				*character = ' ';
+			}
+		}
+		idx += 1;
+	}
+}
+
+/// Splits a program in the `!` convention, where everything after the first `!` is the input to
+/// give it rather than code. The `!` itself is in neither part, and without one there is no input.
+/// The input is the UTF-8 of the characters, what the file held unless it wasn't valid UTF-8.
+pub fn split_bang_input(characters: &[char]) -> (&[char], Option<Vec<u8>>) {
+	split_input_at(characters, characters.iter().position(|&character| character == '!'))
+}
+
+/// [`split_bang_input`] at the `!` of offset `bang`, for sources where not every `!` is one, such
+/// as the `Ook!` tokens of Ook!.
+pub fn split_input_at(characters: &[char], bang: Option<usize>) -> (&[char], Option<Vec<u8>>) {
+	match bang {
+		Some(bang) => {
+			let input: String = characters[bang + 1..].iter().collect();
+			(&characters[..bang], Some(input.into_bytes()))
+		}
+		None => (characters, None),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn chars(text: &str) -> Vec<char> {
+		text.chars().collect()
+	}
+
+	#[test]
+	fn everything_but_instructions_is_a_comment() {
+		let symbols = InterpreterSymbol::tokenize(&chars("+a é\n]"));
+		assert_eq!(symbols, [
+			InterpreterSymbol::Instruction(InterpreterInstruction::IncrementPtr),
+			InterpreterSymbol::Other('a'),
+			InterpreterSymbol::Other(' '),
+			InterpreterSymbol::Other('é'),
+			InterpreterSymbol::Other('\n'),
+			InterpreterSymbol::Instruction(InterpreterInstruction::LoopEnd),
+		]);
+		assert_eq!(InterpreterSymbol::at(&symbols, symbols.len()), InterpreterSymbol::Eof);
+	}
+
+	#[test]
+	fn decode_blanks_the_shebang_line() {
+		assert_eq!(decode_source(b"#!/usr/bin/brainfuck -v\n+."), chars("                       \n+."));
+		// Only a leading one
+		assert_eq!(decode_source(b"+\n#!."), chars("+\n#!."));
+	}
+
+	#[test]
+	fn decode_replaces_invalid_utf8() {
+		assert_eq!(decode_source(b"+\xFF."), chars("+\u{FFFD}."));
+	}
+
+	#[test]
+	fn line_comments_keep_offsets() {
+		let mut characters = chars("+. // print [it]\n-; not this one\n;,");
+		strip_line_comments(&mut characters, "//");
+		assert_eq!(characters, chars("+.              \n-; not this one\n;,"));
+		strip_line_comments(&mut characters, ";");
+		assert_eq!(characters, chars("+.              \n-              \n  "));
+	}
+
+	#[test]
+	fn empty_line_comment_marker_does_nothing() {
+		let mut characters = chars("+.");
+		strip_line_comments(&mut characters, "");
+		assert_eq!(characters, chars("+."));
+	}
+
+	#[test]
+	fn bang_splits_off_the_input() {
+		let characters = chars(",[.,]!hi!");
+		assert_eq!(split_bang_input(&characters), (&characters[..5], Some(b"hi!".to_vec())));
+		let characters = chars(",[.,]");
+		assert_eq!(split_bang_input(&characters), (&characters[..], None));
+		let characters = chars(",!é");
+		assert_eq!(split_bang_input(&characters).1, Some("é".as_bytes().to_vec()));
+	}
+}
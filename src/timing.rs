@@ -0,0 +1,103 @@
+//! `--time`: how long loading and running took, one `time:` prefixed line per measure.
+
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+pub struct RunTiming {
+	/// Reading and preparing the program.
+	pub load: Duration,
+	/// The run itself, waiting on input included.
+	pub run: Duration,
+	/// Time the run spent blocked on `,`.
+	pub input_wait: Duration,
+	/// Executed instructions, `None` when the backend doesn't count them.
+	pub steps: Option<u64>,
+}
+
+impl RunTiming {
+	/// The run without the time blocked on input.
+	pub fn busy(&self) -> Duration {
+		self.run.saturating_sub(self.input_wait)
+	}
+
+	pub fn steps_per_second(&self) -> Option<f64> {
+		let busy = self.busy().as_secs_f64();
+		self.steps.filter(|_| busy > 0.0).map(|steps| steps as f64 / busy)
+	}
+}
+
+impl Display for RunTiming {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		writeln!(f, "time: load {:.6}s", self.load.as_secs_f64())?;
+		writeln!(f, "time: run {:.6}s", self.busy().as_secs_f64())?;
+		writeln!(f, "time: input wait {:.6}s", self.input_wait.as_secs_f64())?;
+		match (self.steps, self.steps_per_second()) {
+			(Some(steps), Some(rate)) => {
+				writeln!(f, "time: steps {steps}")?;
+				write!(f, "time: steps/s {rate:.0}")
+			}
+			(Some(steps), None) => write!(f, "time: steps {steps}"),
+			(None, _) => write!(f, "time: steps not counted by this backend"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Read;
+	use std::thread;
+	use std::time::Instant;
+
+	use brainfuck::interpreter::io::CapturedOutput;
+	use brainfuck::interpreter::Interpreter;
+
+	use super::*;
+
+	/// Input that keeps `,` waiting before every byte.
+	struct SlowInput(&'static [u8]);
+
+	impl Read for SlowInput {
+		fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+			thread::sleep(Duration::from_millis(30));
+			let Some((&byte, rest)) = self.0.split_first() else {
+				return Ok(0);
+			};
+			(buf[0], self.0) = (byte, rest);
+			Ok(1)
+		}
+	}
+
+	#[test]
+	fn waiting_on_input_is_left_out_of_the_run() {
+		let program: Vec<char> = ",.,.".chars().collect();
+		let mut interpreter = Interpreter::with_io(SlowInput(b"ab"), CapturedOutput::new())
+			.with_input_timing()
+			.with_stats();
+		let started = Instant::now();
+		interpreter.run(&program).unwrap();
+		let timing = RunTiming {
+			load: Duration::from_millis(5),
+			run: started.elapsed(),
+			input_wait: interpreter.input_wait().unwrap(),
+			steps: interpreter.stats().map(|stats| stats.steps),
+		};
+
+		assert!(timing.input_wait >= Duration::from_millis(60), "{:?}", timing.input_wait);
+		assert!(timing.busy() < Duration::from_millis(30), "{:?}", timing.busy());
+		assert!(timing.busy() > Duration::ZERO);
+		assert_eq!(timing.steps, Some(4));
+		assert!(timing.steps_per_second().unwrap() > 4.0);
+
+		let shown = timing.to_string();
+		let labels: Vec<&str> = shown.lines().map(|line| line.rsplit_once(' ').unwrap().0).collect();
+		assert_eq!(labels, ["time: load", "time: run", "time: input wait", "time: steps", "time: steps/s"]);
+		assert!(shown.starts_with("time: load 0.005000s\n"));
+	}
+
+	#[test]
+	fn uncounted_steps_have_no_rate() {
+		let timing = RunTiming { load: Duration::ZERO, run: Duration::from_secs(1), input_wait: Duration::ZERO, steps: None };
+		assert_eq!(timing.steps_per_second(), None);
+		assert!(timing.to_string().ends_with("time: steps not counted by this backend"));
+	}
+}
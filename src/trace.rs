@@ -0,0 +1,290 @@
+//! Execution traces written by `--trace`: one tab separated line per executed instruction, or the
+//! Trace Event JSON that `chrome://tracing` and Perfetto open, streamed as the program runs.
+//!
+//! The Chrome trace has a duration event per loop execution, from the `[` entering it to the `[`
+//! finding its cell zero, an instant event per byte read or printed and a counter sampling the data
+//! pointer. Timestamps count steps, one microsecond each.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use brainfuck::interpreter::events::StepEvent;
+use brainfuck::ir::Span;
+use brainfuck::symbol::InterpreterInstruction;
+
+/// Steps between data pointer samples of a Chrome trace unless `--trace-sample` says otherwise.
+pub const DEFAULT_SAMPLE_STEPS: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+	Tsv,
+	Chrome,
+}
+
+impl TraceFormat {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"tsv" => Some(TraceFormat::Tsv),
+			"chrome" => Some(TraceFormat::Chrome),
+			_ => None,
+		}
+	}
+}
+
+/// Classes of events `--trace-filter` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceClass {
+	/// `.` and `,`.
+	Io,
+	/// `[` and `]` that jump.
+	Jumps,
+	/// Instructions that write the cell: `+`, `-` and `,`.
+	Writes,
+}
+
+impl TraceClass {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"io" => Some(TraceClass::Io),
+			"jumps" => Some(TraceClass::Jumps),
+			"writes" => Some(TraceClass::Writes),
+			_ => None,
+		}
+	}
+}
+
+pub struct TraceOptions {
+	pub path: String,
+	pub format: TraceFormat,
+	/// Steps after which tracing stops, the program keeps running.
+	pub limit: Option<u64>,
+	/// Classes to record, everything when empty. Only for the tab separated format.
+	pub filter: Vec<TraceClass>,
+	/// Steps between data pointer samples in a Chrome trace.
+	pub sample: u64,
+}
+
+pub struct Tracer {
+	out: BufWriter<File>,
+	format: TraceFormat,
+	limit: Option<u64>,
+	filter: Vec<TraceClass>,
+	sample: u64,
+	steps: u64,
+	/// Where every character of the program is, to name loops.
+	spans: Vec<Span>,
+	/// `[` of the loops being run, innermost last.
+	open_loops: Vec<usize>,
+	/// Whether the last event was a `]`, coming back to its `[` to check the cell again.
+	looping_back: bool,
+	/// Whether a Chrome event was written, so the next needs a separator.
+	wrote_event: bool,
+}
+
+impl Tracer {
+	pub fn create(options: &TraceOptions, characters: &[char]) -> io::Result<Self> {
+		let mut out = BufWriter::new(File::create(&options.path)?);
+		match options.format {
+			TraceFormat::Tsv => writeln!(out, "# step\tip\top\tptr\tcell\tevent")?,
+			TraceFormat::Chrome => writeln!(out, "[")?,
+		}
+		Ok(Tracer {
+			out,
+			format: options.format,
+			limit: options.limit,
+			filter: options.filter.clone(),
+			sample: options.sample.max(1),
+			steps: 0,
+			spans: Span::table(characters),
+			open_loops: Vec::new(),
+			looping_back: false,
+			wrote_event: false,
+		})
+	}
+
+	/// Records `event`, the execution of the `op` character, if it passes the limit and filter.
+	pub fn record(&mut self, op: char, event: &StepEvent) -> io::Result<()> {
+		self.steps += 1;
+		if self.limit.is_some_and(|limit| self.steps > limit) {
+			return Ok(());
+		}
+		if self.format == TraceFormat::Chrome {
+			return self.record_chrome(event);
+		}
+
+		let flag = match event.instruction {
+			InterpreterInstruction::PrintPtr => Some((TraceClass::Io, "out")),
+			InterpreterInstruction::ReadPtr => Some((TraceClass::Io, "in")),
+			// `[` jumps past the loop on 0 and `]` back to its start otherwise
+			InterpreterInstruction::LoopStart if event.cell == 0 => Some((TraceClass::Jumps, "jump")),
+			InterpreterInstruction::LoopEnd if event.cell != 0 => Some((TraceClass::Jumps, "jump")),
+			_ => None,
+		};
+		let writes = matches!(
+			event.instruction,
+			InterpreterInstruction::IncrementPtr | InterpreterInstruction::DecrementPtr | InterpreterInstruction::ReadPtr
+		);
+		let selected = self.filter.is_empty()
+			|| self.filter.iter().any(|class| match class {
+				TraceClass::Writes => writes,
+				class => flag.is_some_and(|(flagged, _)| flagged == *class),
+			});
+		if !selected {
+			return Ok(());
+		}
+
+		writeln!(
+			self.out,
+			"{}\t{}\t{op}\t{}\t{}\t{}",
+			self.steps,
+			event.instruction_ptr,
+			event.data_ptr,
+			event.cell,
+			flag.map_or("", |(_, name)| name),
+		)
+	}
+
+	fn record_chrome(&mut self, event: &StepEvent) -> io::Result<()> {
+		let ts = self.steps;
+		match event.instruction {
+			InterpreterInstruction::LoopStart => {
+				let looping_back = std::mem::take(&mut self.looping_back);
+				match (looping_back, event.cell) {
+					(false, 0) => {}
+					(false, _) => {
+						self.open_loops.push(event.instruction_ptr);
+						let span = self.spans[event.instruction_ptr];
+						let name = format!("loop {}:{}", span.line, span.column);
+						self.chrome_event(&format!("\"name\":\"{name}\",\"ph\":\"B\",\"ts\":{ts}"))?;
+					}
+					(true, 0) => self.end_loop(ts)?,
+					(true, _) => {}
+				}
+			}
+			InterpreterInstruction::LoopEnd => self.looping_back = true,
+			InterpreterInstruction::PrintPtr | InterpreterInstruction::ReadPtr => {
+				let name = if event.instruction == InterpreterInstruction::PrintPtr { "output" } else { "input" };
+				self.chrome_event(&format!(
+					"\"name\":\"{name}\",\"ph\":\"i\",\"s\":\"t\",\"ts\":{ts},\"args\":{{\"byte\":{}}}",
+					event.cell
+				))?;
+			}
+			_ => {}
+		}
+		if ts.is_multiple_of(self.sample) {
+			self.chrome_event(&format!(
+				"\"name\":\"data_ptr\",\"ph\":\"C\",\"ts\":{ts},\"args\":{{\"data_ptr\":{}}}",
+				event.data_ptr
+			))?;
+		}
+		Ok(())
+	}
+
+	fn end_loop(&mut self, ts: u64) -> io::Result<()> {
+		if self.open_loops.pop().is_some() {
+			self.chrome_event(&format!("\"ph\":\"E\",\"ts\":{ts}"))?;
+		}
+		Ok(())
+	}
+
+	/// Writes a Chrome event with `fields`, on the single process and thread of the trace.
+	fn chrome_event(&mut self, fields: &str) -> io::Result<()> {
+		let separator = if self.wrote_event { ",\n" } else { "" };
+		self.wrote_event = true;
+		write!(self.out, "{separator}{{{fields},\"pid\":1,\"tid\":1}}")
+	}
+
+	/// Ends the trace, closing the loops a failed or limited run left open.
+	pub fn finish(mut self) -> io::Result<()> {
+		if self.format == TraceFormat::Chrome {
+			let ts = self.steps.min(self.limit.unwrap_or(u64::MAX));
+			while !self.open_loops.is_empty() {
+				self.end_loop(ts)?;
+			}
+			writeln!(self.out, "\n]")?;
+		}
+		self.out.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use brainfuck::interpreter::io::CapturedOutput;
+	use brainfuck::interpreter::Interpreter;
+
+	use super::*;
+	use crate::json::read::{parse, Value};
+
+	fn chrome_trace(program: &str, sample: u64) -> Vec<Value> {
+		let path = std::env::temp_dir().join(format!("brainfuck-{}-trace-{sample}.json", std::process::id()));
+		let options = TraceOptions {
+			path: path.to_string_lossy().into_owned(),
+			format: TraceFormat::Chrome,
+			limit: None,
+			filter: Vec::new(),
+			sample,
+		};
+		let characters: Vec<char> = program.chars().collect();
+		let mut tracer = Tracer::create(&options, &characters).unwrap();
+		let mut interpreter = Interpreter::with_output(CapturedOutput::new());
+		for event in interpreter.events(&characters) {
+			let event = event.unwrap();
+			tracer.record(characters[event.instruction_ptr], &event).unwrap();
+		}
+		tracer.finish().unwrap();
+
+		let trace = std::fs::read_to_string(&path).unwrap();
+		let _ = std::fs::remove_file(path);
+		match parse(&trace) {
+			Some(Value::Array(events)) => events,
+			other => panic!("not an array of events: {other:?}\n{trace}"),
+		}
+	}
+
+	fn text<'a>(event: &'a Value, key: &str) -> &'a str {
+		match event.get(key) {
+			Some(Value::String(text)) => text,
+			_ => "",
+		}
+	}
+
+	#[test]
+	fn nested_loops_nest_their_duration_events() {
+		let events = chrome_trace("++[>++\n[-]<-].", 1000);
+		let (mut depth, mut last_ts, mut begun) = (0, 0, Vec::new());
+		for event in &events {
+			let ts = event.get("ts").and_then(Value::as_u64).unwrap();
+			assert!(ts >= last_ts);
+			last_ts = ts;
+			assert_eq!((event.get("pid"), event.get("tid")), (Some(&Value::Number(1.0)), Some(&Value::Number(1.0))));
+			match text(event, "ph") {
+				"B" => {
+					depth += 1;
+					begun.push((depth, text(event, "name").to_string()));
+				}
+				"E" => {
+					assert!(depth > 0, "an end without a begin");
+					depth -= 1;
+				}
+				_ => {}
+			}
+		}
+		assert_eq!(depth, 0);
+		let begun: Vec<(i32, &str)> = begun.iter().map(|(depth, name)| (*depth, name.as_str())).collect();
+		// One per execution of a loop, however many times it goes around
+		assert_eq!(begun, [(1, "loop 1:3"), (2, "loop 2:1"), (2, "loop 2:1")]);
+
+		let output = events.iter().find(|event| text(event, "ph") == "i").unwrap();
+		assert_eq!((text(output, "name"), output.get("args").and_then(|args| args.get("byte")).and_then(Value::as_u64)), ("output", Some(0)));
+	}
+
+	#[test]
+	fn data_pointer_is_sampled() {
+		let events = chrome_trace("+>+>+>", 2);
+		let samples: Vec<_> = events.iter()
+			.filter(|event| text(event, "ph") == "C")
+			.map(|event| (event.get("ts").and_then(Value::as_u64).unwrap(), event.get("args").and_then(|args| args.get("data_ptr")).and_then(Value::as_u64).unwrap()))
+			.collect();
+		assert_eq!(samples, [(2, 1), (4, 2), (6, 3)]);
+	}
+}
@@ -0,0 +1,335 @@
+//! Full-screen debugger: the source with the current instruction highlighted, a tape window with
+//! the data pointer and recently changed cells marked, and a status bar.
+//!
+//! Drawn with plain ANSI escapes; keys are read one at a time by switching the terminal out of
+//! canonical mode with `stty`, so it needs a Unix-like terminal.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+
+use brainfuck::interpreter::events::StepEvent;
+use brainfuck::interpreter::io::CapturedOutput;
+use brainfuck::interpreter::{Interpreter, InterpreterState, MEM_SIZE};
+use brainfuck::ir::Span;
+use brainfuck::symbol::InterpreterInstruction;
+use brainfuck::InterpreterSymbol;
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const HIGHLIGHT: &str = "\x1b[7m";
+const CHANGED: &str = "\x1b[33m";
+const BREAKPOINT: &str = "\x1b[31m";
+const CURSOR: &str = "\x1b[4m";
+const RESET: &str = "\x1b[0m";
+
+const SOURCE_LINES: usize = 12;
+const TAPE_CELLS: usize = 16;
+const RECENT_CHANGES: usize = 8;
+
+const KEYS: &str = "space step  c continue  b breakpoint  h/l cursor  \u{2190}/\u{2192} tape  q quit";
+
+enum LastIo {
+	Printed(u8),
+	Read(u8),
+}
+
+pub struct Tui {
+	characters: Vec<char>,
+	spans: Vec<Span>,
+	interpreter: Interpreter,
+	output: CapturedOutput,
+	breakpoints: BTreeSet<usize>,
+	/// Source position `b` toggles a breakpoint on.
+	cursor: usize,
+	tape_start: usize,
+	steps: u64,
+	/// Most recently written cells, newest last.
+	changed: VecDeque<usize>,
+	last_io: Option<LastIo>,
+	/// Set once the program halted or failed.
+	outcome: Option<String>,
+}
+
+impl Tui {
+	pub fn new(characters: Vec<char>, input: &[u8]) -> Self {
+		let output = CapturedOutput::new();
+		let mut interpreter = Interpreter::with_output(output.clone());
+		interpreter.feed_input(input);
+		Tui {
+			spans: Span::table(&characters),
+			characters,
+			interpreter,
+			output,
+			breakpoints: BTreeSet::new(),
+			cursor: 0,
+			tape_start: 0,
+			steps: 0,
+			changed: VecDeque::new(),
+			last_io: None,
+			outcome: None,
+		}
+	}
+
+	/// The source lines around the current instruction, which is highlighted. Breakpoints are shown
+	/// in red and the cursor underlined.
+	pub fn render_source(&self) -> String {
+		let ip = self.interpreter.get_instruction_ptr();
+		let current_line = self.spans.get(ip).or(self.spans.last()).map_or(1, |span| span.line);
+		let first_line = current_line.saturating_sub(SOURCE_LINES / 2).max(1);
+
+		let mut pane = String::new();
+		let mut line = 0;
+		for (idx, (character, span)) in self.characters.iter().zip(&self.spans).enumerate() {
+			if span.line < first_line {
+				continue;
+			}
+			if span.line >= first_line + SOURCE_LINES {
+				break;
+			}
+			if span.line != line {
+				line = span.line;
+				pane.push_str(&format!("{line:>4} | "));
+			}
+			if *character == '\n' {
+				pane.push('\n');
+				continue;
+			}
+
+			let styles = [(idx == ip, HIGHLIGHT), (self.breakpoints.contains(&idx), BREAKPOINT), (idx == self.cursor, CURSOR)];
+			let mut styled = false;
+			for (applies, style) in styles {
+				if applies {
+					pane.push_str(style);
+					styled = true;
+				}
+			}
+			pane.push(*character);
+			if styled {
+				pane.push_str(RESET);
+			}
+		}
+		if !pane.ends_with('\n') {
+			pane.push('\n');
+		}
+		pane
+	}
+
+	/// A window of cells with the data pointer highlighted and recently written cells colored.
+	pub fn render_tape(&self) -> String {
+		let data_ptr = self.interpreter.get_data_ptr();
+		let mut addresses = String::new();
+		let mut values = String::new();
+		for address in self.tape_start..(self.tape_start + TAPE_CELLS).min(MEM_SIZE) {
+			let value = self.interpreter.cell(address).unwrap_or_default();
+			addresses.push_str(&format!("{address:>5} "));
+			let style = if address == data_ptr {
+				HIGHLIGHT
+			} else if self.changed.contains(&address) {
+				CHANGED
+			} else {
+				values.push_str(&format!("{value:>5} "));
+				continue;
+			};
+			values.push_str(&format!("{style}{value:>5}{RESET} "));
+		}
+		format!("{addresses}\n{values}\n")
+	}
+
+	pub fn render_status(&self) -> String {
+		let last_io = match self.last_io {
+			Some(LastIo::Printed(byte)) => format!("printed {:?}", byte as char),
+			Some(LastIo::Read(byte)) => format!("read {:?}", byte as char),
+			None => "none".to_string(),
+		};
+		let outcome = self.outcome.as_deref().unwrap_or("running");
+		format!(
+			"steps: {}  ip: {}  ptr: {}  loop depth: {}  last I/O: {last_io}  [{outcome}]\n",
+			self.steps,
+			self.interpreter.get_instruction_ptr(),
+			self.interpreter.get_data_ptr(),
+			self.interpreter.loop_stack().len(),
+		)
+	}
+
+	pub fn render(&self) -> String {
+		format!(
+			"{CLEAR_SCREEN}{}\n{}\n{}\n{}\n{KEYS}\n",
+			self.render_source(),
+			self.render_tape(),
+			self.render_status(),
+			String::from_utf8_lossy(&self.output.contents()),
+		)
+	}
+
+	/// Executes one instruction, returning whether the program can keep going.
+	pub fn step(&mut self) -> bool {
+		if self.outcome.is_some() {
+			return false;
+		}
+		let before = self.interpreter.cell(self.interpreter.get_data_ptr());
+		match self.interpreter.events(&self.characters).next() {
+			Some(Ok(event)) => {
+				self.record(&event, before);
+				true
+			}
+			Some(Err(err)) => {
+				self.outcome = Some(format!("failed: {:?}", err.reason));
+				false
+			}
+			None => {
+				self.outcome = Some("finished".to_string());
+				false
+			}
+		}
+	}
+
+	/// Steps until the next instruction has a breakpoint or the program ends.
+	pub fn continue_to_breakpoint(&mut self) {
+		while self.step() {
+			let ip = self.interpreter.get_instruction_ptr();
+			if self.interpreter.get_state() == InterpreterState::Running && self.breakpoints.contains(&ip) {
+				break;
+			}
+		}
+	}
+
+	fn record(&mut self, event: &StepEvent, before: Option<u8>) {
+		self.steps += 1;
+		match event.instruction {
+			InterpreterInstruction::PrintPtr => self.last_io = Some(LastIo::Printed(event.cell)),
+			InterpreterInstruction::ReadPtr => self.last_io = Some(LastIo::Read(event.cell)),
+			_ => {}
+		}
+		if before != Some(event.cell) {
+			self.changed.retain(|&address| address != event.data_ptr);
+			self.changed.push_back(event.data_ptr);
+			if self.changed.len() > RECENT_CHANGES {
+				self.changed.pop_front();
+			}
+		}
+		// Keep the data pointer inside the tape window
+		if event.data_ptr < self.tape_start || event.data_ptr >= self.tape_start + TAPE_CELLS {
+			self.tape_start = event.data_ptr.saturating_sub(TAPE_CELLS / 2).min(MEM_SIZE - TAPE_CELLS);
+		}
+	}
+
+	fn move_cursor(&mut self, forward: bool) {
+		let is_instruction = |idx: &usize| {
+			matches!(InterpreterSymbol::from_char(self.characters.get(*idx)), InterpreterSymbol::Instruction(_))
+		};
+		let next = if forward {
+			(self.cursor + 1..self.characters.len()).find(is_instruction)
+		} else {
+			(0..self.cursor).rev().find(is_instruction)
+		};
+		if let Some(next) = next {
+			self.cursor = next;
+		}
+	}
+
+	fn toggle_breakpoint(&mut self) {
+		if !self.breakpoints.remove(&self.cursor) {
+			self.breakpoints.insert(self.cursor);
+		}
+	}
+
+	fn scroll_tape(&mut self, forward: bool) {
+		self.tape_start = if forward {
+			(self.tape_start + 1).min(MEM_SIZE - TAPE_CELLS)
+		} else {
+			self.tape_start.saturating_sub(1)
+		};
+	}
+
+	/// Runs the key loop on the terminal until `q` or the end of stdin.
+	pub fn run(&mut self) -> io::Result<()> {
+		let saved_mode = stty(&["-g"])?;
+		stty(&["-icanon", "-echo", "min", "1"])?;
+		let result = self.key_loop();
+		stty(&[saved_mode.trim()])?;
+		result
+	}
+
+	fn key_loop(&mut self) -> io::Result<()> {
+		let mut stdout = io::stdout();
+		let mut keys = io::stdin().lock().bytes();
+		loop {
+			write!(stdout, "{}", self.render())?;
+			stdout.flush()?;
+
+			match keys.next().transpose()? {
+				None | Some(b'q') => return Ok(()),
+				Some(b' ') => {
+					self.step();
+				}
+				Some(b'c') => self.continue_to_breakpoint(),
+				Some(b'b') => self.toggle_breakpoint(),
+				Some(b'h') => self.move_cursor(false),
+				Some(b'l') => self.move_cursor(true),
+				// Arrow keys arrive as `ESC [ C` and `ESC [ D`
+				Some(0x1b) => match (keys.next().transpose()?, keys.next().transpose()?) {
+					(Some(b'['), Some(b'C')) => self.scroll_tape(true),
+					(Some(b'['), Some(b'D')) => self.scroll_tape(false),
+					_ => {}
+				},
+				Some(_) => {}
+			}
+		}
+	}
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+	let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+	if output.status.success() {
+		Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+	} else {
+		Err(io::Error::other(String::from_utf8_lossy(&output.stderr).into_owned()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn stepped(program: &str, input: &[u8], steps: usize) -> Tui {
+		let mut tui = Tui::new(program.chars().collect(), input);
+		for _ in 0..steps {
+			tui.step();
+		}
+		tui
+	}
+
+	#[test]
+	fn source_pane_marks_instruction_breakpoint_and_cursor() {
+		let mut tui = stepped(",+.\n>+", b"A", 2);
+		tui.toggle_breakpoint();
+		tui.move_cursor(true);
+		assert_eq!(
+			tui.render_source(),
+			format!("   1 | {BREAKPOINT},{RESET}{CURSOR}+{RESET}{HIGHLIGHT}.{RESET}\n   2 | >+\n")
+		);
+	}
+
+	#[test]
+	fn tape_pane_marks_pointer_and_changed_cells() {
+		let tui = stepped(",+.\n>+", b"A", 6);
+		let addresses: String = (0..16).map(|address| format!("{address:>5} ")).collect();
+		let values = format!("{CHANGED}   66{RESET} {HIGHLIGHT}    1{RESET} {}", "    0 ".repeat(14));
+		assert_eq!(tui.render_tape(), format!("{addresses}\n{values}\n"));
+	}
+
+	#[test]
+	fn status_bar_tracks_steps_and_io() {
+		let mut tui = stepped(",+.\n>+", b"A", 3);
+		assert_eq!(tui.render_status(), "steps: 3  ip: 3  ptr: 0  loop depth: 0  last I/O: printed 'B'  [running]\n");
+		tui.continue_to_breakpoint();
+		assert_eq!(tui.render_status(), "steps: 5  ip: 6  ptr: 1  loop depth: 0  last I/O: printed 'B'  [finished]\n");
+
+		let mut tui = stepped(",[.,]", b"xy", 0);
+		tui.move_cursor(true);
+		tui.move_cursor(true);
+		tui.toggle_breakpoint();
+		tui.continue_to_breakpoint();
+		assert_eq!(tui.render_status(), "steps: 2  ip: 2  ptr: 0  loop depth: 1  last I/O: read 'x'  [running]\n");
+	}
+}
@@ -0,0 +1,44 @@
+//! Live terminal view of the tape, redrawn after every executed instruction.
+
+use std::thread;
+use std::time::Duration;
+
+use brainfuck::interpreter::{Interpreter, MEM_SIZE};
+
+const WINDOW_CELLS: usize = 16;
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const HIGHLIGHT: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+pub const DEFAULT_DELAY_MS: u64 = 50;
+
+/// Draws a window of cells centered on the data pointer, with the current cell highlighted, followed
+/// by everything the program printed so far.
+pub fn render_frame(interpreter: &Interpreter, output: &[u8]) -> String {
+	let data_ptr = interpreter.get_data_ptr();
+	let start = data_ptr.saturating_sub(WINDOW_CELLS / 2).min(MEM_SIZE - WINDOW_CELLS);
+	let tape = interpreter.tape();
+
+	let mut addresses = String::new();
+	let mut values = String::new();
+	for address in start..start + WINDOW_CELLS {
+		let value = tape.get(address).copied().unwrap_or(0);
+		addresses.push_str(&format!("{address:>5} "));
+		if address == data_ptr {
+			values.push_str(&format!("{HIGHLIGHT}{value:>5}{RESET} "));
+		} else {
+			values.push_str(&format!("{value:>5} "));
+		}
+	}
+
+	format!(
+		"{CLEAR_SCREEN}ip: {}  ptr: {data_ptr}\n{addresses}\n{values}\n\n{}\n",
+		interpreter.get_instruction_ptr(),
+		String::from_utf8_lossy(output),
+	)
+}
+
+pub fn show_frame(interpreter: &Interpreter, output: &[u8], delay: Duration) {
+	print!("{}", render_frame(interpreter, output));
+	thread::sleep(delay);
+}
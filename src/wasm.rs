@@ -0,0 +1,21 @@
+//! Entry points for embedding the interpreter in a browser build.
+//!
+//! Everything here runs without touching the process' stdin/stdout: input comes from the given
+//! string and output is collected and handed back, which is what a web playground needs.
+
+use crate::interpreter::io::CapturedOutput;
+use crate::interpreter::Interpreter;
+
+/// Runs `program` reading `,` input from `input`, returning everything the program printed.
+///
+/// Errors are reported as their debug representation, ready to be handed over to JS.
+pub fn run_with_io(program: &str, input: &str) -> Result<String, String> {
+	let characters: Vec<char> = program.chars().collect();
+	let output = CapturedOutput::new();
+	let mut interpreter = Interpreter::with_io(
+		std::io::Cursor::new(input.as_bytes().to_vec()), output.clone(),
+	);
+
+	interpreter.run(&characters).map_err(|err| format!("{:?}", err.reason))?;
+	String::from_utf8(output.contents()).map_err(|err| err.to_string())
+}